@@ -96,7 +96,10 @@ impl ClientProcess {
 impl Process for ClientProcess {
     fn on_local_message(&mut self, msg: Message, ctx: Context) {
         let request_kind = msg.data::<ClientRequestKind>().unwrap();
-        let request = self.request_builder.build_with_kind(request_kind);
+        let request = self
+            .request_builder
+            .with_time(ctx.time())
+            .build_with_kind(request_kind);
         let update_result = self.state_machine.apply_client_request(request);
         self.handle_state_update(update_result, ctx);
     }
@@ -128,6 +128,16 @@ impl RequestBuilder {
         self.build_with_kind(ClientRequestKind::Disconnect)
     }
 
+    /// Set current time of the client.
+    ///
+    /// Ids of the next requests are not less than the time in microseconds,
+    /// so they differ from ids of the requests sent before client restart.
+    /// It allows server to ignore duplicated requests.
+    pub fn with_time(&mut self, time: f64) -> &mut Self {
+        self.id = self.id.max((time * 1e6) as u64);
+        self
+    }
+
     fn next_id(&mut self) -> u64 {
         self.id += 1;
         self.id
@@ -1,5 +1,5 @@
 use std::{
-    collections::{hash_map::Entry, HashMap},
+    collections::{hash_map::Entry, BTreeSet, HashMap},
     sync::Arc,
 };
 
@@ -26,6 +26,9 @@ use super::{
     },
 };
 
+/// Number of the last handled requests, which are remembered for every client.
+const HANDLED_REQUESTS_LIMIT: usize = 1024;
+
 #[derive(Default)]
 pub struct ServerState {
     chat_seq_nums: HashMap<String, u64>,
@@ -35,6 +38,9 @@ pub struct ServerState {
     total_seq: Option<u64>,
     replica: Option<Address>,
     tag: u64,
+    /// Ids of the last handled requests of every client, which allow to ignore
+    /// duplicated requests even if they arrive after the next ones.
+    handled_requests: HashMap<String, BTreeSet<u64>>,
 }
 
 pub type ServerStateLock = Arc<Mutex<ServerState>>;
@@ -49,6 +55,7 @@ impl ServerState {
             total_seq: None,
             replica: Some(replica),
             tag: 0,
+            handled_requests: HashMap::new(),
         }
     }
 
@@ -130,8 +137,12 @@ impl ServerState {
             "replicate_request" => {
                 let request = msg.data::<ReplicateRequest>().unwrap();
                 let seq = self.get_total_seq(ctx.clone()).await;
-                if seq <= request.seq_num {
-                    assert_eq!(seq, request.seq_num);
+                if seq < request.seq_num {
+                    // Requests are missed after crash, so they are requested again.
+                    self.waiting_for_seq = Some(request.seq_num);
+                    request_replica_events_from_range(ctx.clone(), seq, request.seq_num, from)
+                        .await;
+                } else if seq == request.seq_num {
                     if let Some(waiting_for) = self.waiting_for_seq {
                         if waiting_for == request.seq_num {
                             self.waiting_for_seq = None;
@@ -150,6 +161,10 @@ impl ServerState {
             }
             "client_request" => {
                 let user_request = msg.data::<ClientRequest>().unwrap();
+                if self.is_handled(&user_request) {
+                    // Duplicated request is already handled.
+                    return;
+                }
                 if !self.check_replication(ctx.clone()).await {
                     return;
                 }
@@ -159,6 +174,17 @@ impl ServerState {
         }
     }
 
+    /// Checks if the request is handled or it is older than all remembered requests of the client.
+    fn is_handled(&self, request: &ClientRequest) -> bool {
+        self.handled_requests
+            .get(&request.client)
+            .is_some_and(|ids| {
+                ids.contains(&request.id)
+                    || (ids.len() == HANDLED_REQUESTS_LIMIT
+                        && ids.first().is_some_and(|first| request.id < *first))
+            })
+    }
+
     pub async fn get_chat_seq_num(&mut self, ctx: Context, chat: String) -> u64 {
         *self
             .chat_seq_nums
@@ -213,6 +239,14 @@ impl ServerState {
         mut request: ClientRequest,
     ) {
         let from_replica = self.replica.is_some() && *self.replica.as_ref().unwrap() == from;
+        let handled = self
+            .handled_requests
+            .entry(request.client.clone())
+            .or_default();
+        handled.insert(request.id);
+        if handled.len() > HANDLED_REQUESTS_LIMIT {
+            handled.pop_first();
+        }
         if !from_replica {
             request.addr = Some(from.clone());
             request.time = Some(ctx.time());
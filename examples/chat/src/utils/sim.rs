@@ -157,15 +157,24 @@ pub fn rerun_server(
     } else {
         sys.rerun_node(server_node);
     }
-    sys.connect_node_to_network(server_node);
-    sys.add_process(
+    restart_server(sys, server_node, server_addr, replica_addr);
+    sys.send_local_message(
         &server_addr.process_name,
-        ServerProcess::new_with_replica(replica_addr.clone()),
         server_node,
+        check_replica_request(),
     );
-    sys.send_local_message(
+}
+
+pub fn restart_server(
+    sys: &mut Sim,
+    server_node: &str,
+    server_addr: &Address,
+    replica_addr: &Address,
+) {
+    sys.connect_node_to_network(server_node);
+    sys.add_process(
         &server_addr.process_name,
+        ServerProcess::new_with_replica(replica_addr.clone()),
         server_node,
-        check_replica_request(),
     );
 }
@@ -0,0 +1,167 @@
+use chat::{
+    client::requests::{ClientRequestKind, RequestBuilder},
+    server::event::{ChatEvent, ChatEventKind},
+    utils::sim::{build_sim, default_pass, read_history_from_info},
+};
+use dsbuild::{Address, Context, Message, Process, ReorderMode, Sim};
+
+fn check_no_duplicates(history: &[ChatEvent]) {
+    let mut seqs = history.iter().map(|event| event.seq).collect::<Vec<_>>();
+    seqs.dedup();
+    assert_eq!(seqs.len(), history.len());
+}
+
+#[test]
+fn duplicated_messages_are_ignored() {
+    let mut sys = Sim::new(12345);
+
+    let primary_addr = Address::new_ref("primary", 0, "Primary");
+    let replica_addr = Address::new_ref("replica", 0, "Replica");
+
+    build_sim(
+        &mut sys,
+        vec![
+            Address::new_ref("client1", 0, "Client1"),
+            Address::new_ref("client2", 0, "Client2"),
+        ]
+        .as_slice(),
+        primary_addr,
+        replica_addr,
+    );
+    sys.set_network_duplicate_rate(0.5);
+    sys.step_until_no_events();
+
+    sys.send_local_message(
+        "Client1",
+        "Client1",
+        ClientRequestKind::Create("Chat".to_string()).into(),
+    );
+    sys.step_until_no_events();
+
+    for client in ["Client1", "Client2"] {
+        sys.send_local_message(
+            client,
+            client,
+            ClientRequestKind::Connect("Chat".to_string()).into(),
+        );
+    }
+    sys.step_until_no_events();
+
+    for i in 0..5 {
+        for client in ["Client1", "Client2"] {
+            sys.send_local_message(
+                client,
+                client,
+                ClientRequestKind::SendMessage(format!("{} message {}", client, i)).into(),
+            );
+        }
+        sys.step_until_no_events();
+    }
+
+    let client1_history = read_history_from_info(&mut sys, "Client1", "Client1");
+    let client2_history = read_history_from_info(&mut sys, "Client2", "Client2");
+    check_no_duplicates(&client1_history);
+    check_no_duplicates(&client2_history);
+    assert_eq!(client1_history, client2_history);
+
+    let sent_messages = client1_history
+        .iter()
+        .filter(|event| matches!(event.kind, ChatEventKind::SentMessage(_)))
+        .count();
+    assert_eq!(sent_messages, 10);
+}
+
+/// Client, which sends requests without waiting for responses on the previous ones,
+/// so duplicates of the previous requests can arrive after the next ones.
+struct PipelineClient {
+    server: Address,
+    requests: RequestBuilder,
+}
+
+impl Process for PipelineClient {
+    fn on_local_message(&mut self, msg: Message, ctx: Context) {
+        let kind = msg.data::<ClientRequestKind>().unwrap();
+        let request = self.requests.with_time(ctx.time()).build_with_kind(kind);
+        let server = self.server.clone();
+        ctx.clone().spawn(async move {
+            let _ = ctx.send_with_ack(request.into(), server, 5.0).await;
+        });
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {}
+
+    fn on_message(&mut self, _msg: Message, _from: Address, _ctx: Context) {}
+}
+
+#[test]
+fn duplicated_and_reordered_requests_are_ignored() {
+    let mut sys = Sim::new(12345);
+
+    let primary_addr = Address::new_ref("primary", 0, "Primary");
+    let replica_addr = Address::new_ref("replica", 0, "Replica");
+
+    build_sim(
+        &mut sys,
+        vec![Address::new_ref("client", 0, "Client")].as_slice(),
+        primary_addr.clone(),
+        replica_addr,
+    );
+    sys.set_network_drop_rate(0.0);
+    sys.add_node("Pipeline", "pipeline", 0);
+    sys.add_process(
+        "Pipeline",
+        PipelineClient {
+            server: primary_addr,
+            requests: RequestBuilder::new("Pipeline".to_owned(), default_pass()),
+        },
+        "Pipeline",
+    );
+    sys.step_until_no_events();
+
+    sys.send_local_message(
+        "Client",
+        "Client",
+        ClientRequestKind::Create("Chat".to_string()).into(),
+    );
+    sys.step_until_no_events();
+    for client in ["Client", "Pipeline"] {
+        sys.send_local_message(
+            client,
+            client,
+            ClientRequestKind::Connect("Chat".to_string()).into(),
+        );
+        sys.step_until_no_events();
+    }
+
+    sys.set_network_duplicate_rate(0.5);
+    sys.set_reorder_mode(ReorderMode::Window(3.0));
+    for i in 0..10 {
+        sys.send_local_message(
+            "Pipeline",
+            "Pipeline",
+            ClientRequestKind::SendMessage(format!("message {}", i)).into(),
+        );
+    }
+    sys.step_until_no_events();
+
+    // Duplicates of the handled requests are ignored, even if they arrive after the next requests.
+    let mut sent = read_history_from_info(&mut sys, "Client", "Client")
+        .into_iter()
+        .filter_map(|event| match event.kind {
+            ChatEventKind::SentMessage(message) if event.user == "Pipeline" => Some(message),
+            _ => None,
+        })
+        .map(|message| {
+            message
+                .strip_prefix("message ")
+                .unwrap()
+                .parse::<u64>()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    assert!(!sent.is_empty());
+    sent.sort();
+    let len = sent.len();
+    sent.dedup();
+    assert_eq!(sent.len(), len);
+}
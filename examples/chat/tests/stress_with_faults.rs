@@ -2,10 +2,10 @@ use chat::{
     client::requests::ClientRequestKind,
     utils::{
         server::check_replica_request,
-        sim::{build_sim, read_history_from_info, rerun_server, stop_server},
+        sim::{build_sim, read_history_from_info, rerun_server, restart_server, stop_server},
     },
 };
-use dsbuild::{Address, Sim};
+use dsbuild::{Address, FaultPlan, Nemesis, Sim};
 
 #[test]
 fn stress_with_faults_2_users() {
@@ -143,3 +143,89 @@ fn stress_with_faults_2_users() {
     let second = read_history_from_info(&mut sys, "Client2", &client2_addr.process_name);
     assert_eq!(first, second);
 }
+
+#[test]
+fn stress_with_nemesis_2_users() {
+    let mut sys = Sim::new(12345);
+
+    let client1_addr = Address::new_ref("client1", 10024, "Client1");
+    let client2_addr = Address::new_ref("client2", 10024, "Client2");
+    let primary = Address::new_ref("primary", 10024, "Primary");
+    let replica = Address::new_ref("replica", 10024, "Replica");
+
+    build_sim(
+        &mut sys,
+        vec![client1_addr.clone(), client2_addr.clone()].as_slice(),
+        primary.clone(),
+        replica.clone(),
+    );
+
+    // Client1 creates chat.
+    sys.send_local_message(
+        &client1_addr.process_name,
+        "Client1",
+        ClientRequestKind::Create("chat".to_owned()).into(),
+    );
+    sys.step_until_no_events();
+
+    // Replica crashes and recovers while clients send messages, then catches up with primary.
+    // Crash of the primary in the middle of replication is not tolerated by the chat,
+    // so it is checked between the rounds in `stress_with_faults_2_users`.
+    let plan = FaultPlan::new(&["Replica"])
+        .with_interval(5.0, 10.0)
+        .with_duration(5.0, 15.0)
+        .with_crashes();
+    let (primary_clone, replica_clone) = (primary.clone(), replica.clone());
+    let mut nemesis = Nemesis::new(&plan, &sys, 200.0).with_restart_hook(move |sys, node| {
+        restart_server(sys, node, &replica_clone, &primary_clone);
+        sys.send_local_message(&replica_clone.process_name, node, check_replica_request());
+    });
+
+    for iter in 0..15 {
+        for client in [&client1_addr, &client2_addr] {
+            sys.send_local_message(
+                &client.process_name,
+                &client.process_name,
+                ClientRequestKind::Connect("chat".to_owned()).into(),
+            );
+        }
+        nemesis.step_until_no_events(&mut sys);
+
+        for i in 0..10 {
+            for (n, client) in [&client1_addr, &client2_addr].into_iter().enumerate() {
+                sys.send_local_message(
+                    &client.process_name,
+                    &client.process_name,
+                    ClientRequestKind::SendMessage(format!("client{}_{}", n + 1, iter * 10 + i))
+                        .into(),
+                );
+            }
+        }
+        nemesis.step_until_no_events(&mut sys);
+
+        for client in [&client1_addr, &client2_addr] {
+            sys.send_local_message(
+                &client.process_name,
+                &client.process_name,
+                ClientRequestKind::Disconnect.into(),
+            );
+        }
+        nemesis.step_until_no_events(&mut sys);
+    }
+    nemesis.finish(&mut sys);
+    assert!(nemesis.applied().len() >= 4);
+    sys.step_until_no_events();
+
+    for client in [&client1_addr, &client2_addr] {
+        sys.send_local_message(
+            &client.process_name,
+            &client.process_name,
+            ClientRequestKind::Connect("chat".to_owned()).into(),
+        );
+    }
+    sys.step_until_no_events();
+
+    let first = read_history_from_info(&mut sys, "Client1", &client1_addr.process_name);
+    let second = read_history_from_info(&mut sys, "Client2", &client2_addr.process_name);
+    assert_eq!(first, second);
+}
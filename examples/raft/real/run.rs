@@ -9,7 +9,8 @@ pub mod http;
 pub mod io;
 pub mod register;
 
-fn main() -> Result<(), String> {
+#[tokio::main]
+async fn main() -> Result<(), String> {
     // enable logging
     env_logger::Builder::new()
         .filter_level(log::LevelFilter::Info)
@@ -60,17 +61,17 @@ fn main() -> Result<(), String> {
     // get my listen address
     let my_listen_addr = config.listen_net[my_id];
 
-    // spawn I/O activity
-    node.spawn(process_io(
-        my_id,
-        my_listen_addr,
-        proc_io.sender,
-        proc_io.receiver,
-        config.listen_net,
-    ));
-
-    // run spawned activity
-    node.run();
+    // run node together with I/O activity
+    tokio::join!(
+        node.run_async(),
+        process_io(
+            my_id,
+            my_listen_addr,
+            proc_io.sender,
+            proc_io.receiver,
+            config.listen_net,
+        )
+    );
 
     Ok(())
 }
@@ -16,7 +16,7 @@ pub type ValueTypeRef = str;
 /// committed command.
 ///
 /// (responsible_server, sequence_number)
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub struct CommandId(pub usize, pub usize);
 
 impl CommandId {
@@ -4,6 +4,7 @@ mod db;
 mod disk;
 pub mod local;
 mod log;
+pub mod model;
 pub mod proc;
 mod role;
 pub mod sim;
@@ -0,0 +1,168 @@
+use dsbuild::Model;
+
+use crate::cmd::{
+    CommandType, KeyType, ValueType, ALREADY_EXISTS_CODE, CREATED_CODE, DELETED_CODE,
+    NOT_FOUND_CODE, NOT_UPDATED_CODE, UPDATED_CODE,
+};
+
+//////////////////////////////////////////////////////////////////////////////////////////
+
+/// Represents operation on key-value storage invoked by client
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum KvInput {
+    Command(CommandType),
+    Read(KeyType),
+}
+
+impl KvInput {
+    pub fn key(&self) -> &KeyType {
+        match self {
+            KvInput::Command(CommandType::Create(key))
+            | KvInput::Command(CommandType::Update(key, _))
+            | KvInput::Command(CommandType::Delete(key))
+            | KvInput::Command(CommandType::Cas(key, _, _))
+            | KvInput::Read(key) => key,
+        }
+    }
+}
+
+/// Represents result of operation on key-value storage
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum KvOutput {
+    /// Status of command reply
+    Status(u16),
+    /// Read value
+    Value(Option<ValueType>),
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////
+
+/// Sequential specification of database, which is used
+/// to check linearizability of client histories.
+///
+/// Every key is checked independently.
+pub struct KvModel {}
+
+impl Model for KvModel {
+    /// Value of the key or none if key does not exist
+    type State = Option<ValueType>;
+    type Input = KvInput;
+    type Output = KvOutput;
+
+    fn init(&self) -> Self::State {
+        None
+    }
+
+    fn step(
+        &self,
+        state: &Self::State,
+        input: &Self::Input,
+        output: Option<&Self::Output>,
+    ) -> Option<Self::State> {
+        let (expected, next) = match input {
+            KvInput::Read(_) => (KvOutput::Value(state.clone()), state.clone()),
+            KvInput::Command(cmd) => {
+                let (status, next) = apply_command(state, cmd);
+                (KvOutput::Status(status), next)
+            }
+        };
+        match output {
+            Some(output) if *output != expected => None,
+            _ => Some(next),
+        }
+    }
+
+    fn partition(&self, input: &Self::Input) -> String {
+        input.key().clone()
+    }
+}
+
+/// Mirrors command handling of database for single key
+fn apply_command(state: &Option<ValueType>, cmd: &CommandType) -> (u16, Option<ValueType>) {
+    match (cmd, state) {
+        (CommandType::Create(_), None) => (CREATED_CODE, Some(Default::default())),
+        (CommandType::Create(_), Some(_)) => (ALREADY_EXISTS_CODE, state.clone()),
+        (CommandType::Update(_, value), Some(_)) => (UPDATED_CODE, Some(value.clone())),
+        (CommandType::Delete(_), Some(_)) => (DELETED_CODE, None),
+        (CommandType::Cas(_, cmp, new), Some(value)) => {
+            if value == cmp {
+                (UPDATED_CODE, Some(new.clone()))
+            } else {
+                (NOT_UPDATED_CODE, state.clone())
+            }
+        }
+        (_, None) => (NOT_FOUND_CODE, None),
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use dsbuild::History;
+
+    use crate::cmd::{CommandType, CREATED_CODE, NOT_FOUND_CODE, UPDATED_CODE};
+
+    use super::{KvInput, KvModel, KvOutput};
+
+    #[test]
+    fn sequential_history() {
+        let mut history = History::new();
+
+        let ops = [
+            (KvInput::Read("k1".to_owned()), KvOutput::Value(None)),
+            (
+                KvInput::Command(CommandType::update("k1", "v1")),
+                KvOutput::Status(NOT_FOUND_CODE),
+            ),
+            (
+                KvInput::Command(CommandType::create("k1")),
+                KvOutput::Status(CREATED_CODE),
+            ),
+            (
+                KvInput::Read("k1".to_owned()),
+                KvOutput::Value(Some(String::new())),
+            ),
+            (
+                KvInput::Command(CommandType::cas("k1", "", "v2")),
+                KvOutput::Status(UPDATED_CODE),
+            ),
+            (
+                KvInput::Read("k1".to_owned()),
+                KvOutput::Value(Some("v2".to_owned())),
+            ),
+        ];
+        for (time, (input, output)) in ops.into_iter().enumerate() {
+            let time = time as f64;
+            let id = history.invoke("client", input, time);
+            history.complete(id, output, time + 0.5);
+        }
+
+        assert!(history.check(&KvModel {}).is_ok());
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn stale_read() {
+        let mut history = History::new();
+
+        let create = history.invoke("client1", KvInput::Command(CommandType::create("k1")), 0.0);
+        history.complete(create, KvOutput::Status(CREATED_CODE), 1.0);
+
+        let update = history.invoke(
+            "client1",
+            KvInput::Command(CommandType::update("k1", "v1")),
+            2.0,
+        );
+        history.complete(update, KvOutput::Status(UPDATED_CODE), 3.0);
+
+        // read started after update completed, but returned previous value
+        let read = history.invoke("client2", KvInput::Read("k1".to_owned()), 4.0);
+        history.complete(read, KvOutput::Value(Some(String::new())), 5.0);
+
+        let err = history.check(&KvModel {}).unwrap_err();
+        assert_eq!(err.partition, "k1");
+        assert_eq!(err.not_linearized, vec![read]);
+    }
+}
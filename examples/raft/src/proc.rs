@@ -1,6 +1,7 @@
 use std::{future::Future, sync::Arc};
 
-use dsbuild::{Address, Context, Message, Process};
+use dsbuild::{Address, Context, Message, Process, ProcessFactory};
+use serde::Deserialize;
 use tokio::sync::Mutex;
 
 use crate::{
@@ -10,12 +11,27 @@ use crate::{
     },
     cmd::{Command, COMMAND},
     local::{ReadValueRequest, INITIALIZE_REQUEST, READ_VALUE_REQUEST},
-    state::{RaftState, DUMP_STATE_TIMER_NAME, ELECTION_TIMER_NAME, HEARTBEAT_TIMER_NAME},
+    state::{
+        RaftState, StateInfo, DUMP_STATE_TIMER_NAME, ELECTION_TIMER_NAME, HEARTBEAT_TIMER_NAME,
+    },
     vote::{VoteRequest, VoteResponse, VOTE_REQUEST, VOTE_RESPONSE},
 };
 
 //////////////////////////////////////////////////////////////////////////////////////////
 
+/// Kind of raft process in cluster specification
+pub const RAFT_PROCESS_KIND: &str = "raft";
+
+/// Arguments of raft process in cluster specification
+#[derive(Deserialize)]
+pub struct RaftProcessArgs {
+    /// Index of process among raft processes of cluster
+    pub id: usize,
+    pub net_rtt: f64,
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////
+
 /// Serves as proxy between system and [`RaftState`]
 /// Holds lock on state and blocks it during async calls
 pub struct RaftProcess {
@@ -32,6 +48,23 @@ impl RaftProcess {
         }
     }
 
+    /// Allows to register raft process in factory.
+    /// Raft group consists of all raft processes of cluster in order of specification
+    pub fn register(factory: &mut ProcessFactory) {
+        factory.register(
+            RAFT_PROCESS_KIND,
+            |args: RaftProcessArgs, _address, spec| {
+                Self::new(spec.addresses(RAFT_PROCESS_KIND), args.id, args.net_rtt)
+            },
+        );
+    }
+
+    /// Allows to get info about current state of raft.
+    /// Returns `None` if state is locked by pending handler
+    pub fn state_info(&self) -> Option<StateInfo> {
+        self.state.try_lock().ok().map(|state| state.state_info())
+    }
+
     //////////////////////////////////////////////////////////////////////////////////////////
 
     // Handler base
@@ -1,3 +1,5 @@
+use std::collections::BTreeSet;
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
@@ -46,8 +48,8 @@ impl LeaderInfo {
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub enum Role {
     Leader(LeaderInfo),
-    Follower(Option<usize>), // optional id of leader
-    Candidate(usize),        // votes granted
+    Follower(Option<usize>),    // optional id of leader
+    Candidate(BTreeSet<usize>), // ids of nodes granted votes
 }
 
 //////////////////////////////////////////////////////////////////////////////////////////
@@ -1,6 +1,12 @@
-use std::collections::{HashMap, VecDeque};
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+};
 
-use dsbuild::{Address, Message, Sim};
+use dsbuild::{
+    ClusterSpec, DiagramFilter, DiagramFormat, History, LinearizabilityError, Message,
+    MessageAction, NodeSpec, ProcessFactory, ProcessSpec, Sim,
+};
 
 use crate::{
     cmd::{Command, CommandId, CommandReply, CommandType, ValueType},
@@ -8,7 +14,8 @@ use crate::{
         InitializeRequest, InitializeResponse, LocalResponse, LocalResponseType, ReadValueRequest,
         INITIALIZE_RESPONSE, LOCAL_RESPONSE,
     },
-    proc::RaftProcess,
+    model::{KvInput, KvModel, KvOutput},
+    proc::{RaftProcess, RAFT_PROCESS_KIND},
     role::Role,
     state::{StateInfo, STATE_INFO},
 };
@@ -161,28 +168,50 @@ impl ProcessInfo {
 
 //////////////////////////////////////////////////////////////////////////////////////////
 
+/// Wrapper over the simulation of the raft cluster.
+///
+/// If environment variable `RAFT_SIM_DIAGRAM_DIR` is set, HTML timeline of the message flows
+/// of the failed test is written to this directory, named by the test and the seed.
 pub struct SimWrapper {
     sim: Sim,
+    spec: ClusterSpec,
+    factory: ProcessFactory,
     node_cnt: usize,
     proc_info: Vec<ProcessInfo>,
+    diagram_path: Option<PathBuf>,
+    history: History<KvInput, KvOutput>,
+    pending_ops: HashMap<CommandId, usize>,
 }
 
 //////////////////////////////////////////////////////////////////////////////////////////
 
 impl SimWrapper {
     pub fn new(seed: u64, node_cnt: usize) -> Self {
-        let sim = Sim::new(seed);
+        let spec = Self::cluster_spec(node_cnt);
+        let mut factory = ProcessFactory::new();
+        RaftProcess::register(&mut factory);
+
+        let sim = spec.build_sim(seed, &factory).unwrap();
         sim.set_network_delays(0.15 / 2. - 0.025, 0.15 / 2. + 0.025);
 
         let mut sim_wrapper = Self {
             sim,
+            spec,
+            factory,
             node_cnt,
             proc_info: (0..node_cnt).map(ProcessInfo::new).collect(),
+            diagram_path: None,
+            history: History::new(),
+            pending_ops: HashMap::new(),
         };
 
-        for node in 0..node_cnt {
-            sim_wrapper.add_node(node);
-            sim_wrapper.add_proccess_to_node(node);
+        if let Some(dir) = std::env::var_os("RAFT_SIM_DIAGRAM_DIR") {
+            let test_name = std::thread::current()
+                .name()
+                .unwrap_or("raft")
+                .replace("::", "_");
+            let file_name = format!("{}_seed_{}.html", test_name, seed);
+            sim_wrapper.export_diagram_on_failure(Path::new(&dir).join(file_name));
         }
 
         sim_wrapper
@@ -190,20 +219,35 @@ impl SimWrapper {
 
     //////////////////////////////////////////////////////////////////////////////////////////
 
-    fn add_node(&mut self, node: usize) {
-        let node_name = Self::node_name(node);
-        let port = 123;
-        let storage = 1 << 15;
-        self.sim
-            .add_node_with_storage(&node_name, &node_name, port, storage);
+    /// Returns specification of cluster, where every node hosts one raft process
+    pub fn cluster_spec(node_cnt: usize) -> ClusterSpec {
+        let nodes = (0..node_cnt)
+            .map(|node| NodeSpec {
+                name: Self::node_name(node),
+                host: Self::node_name(node),
+                port: 123,
+                storage_mount: None,
+                storage_capacity: 1 << 15,
+                processes: vec![ProcessSpec {
+                    name: Self::process_name(node),
+                    kind: RAFT_PROCESS_KIND.to_owned(),
+                    args: serde_json::json!({ "id": node, "net_rtt": 0.15 }),
+                }],
+            })
+            .collect();
+        ClusterSpec { nodes }
     }
 
     fn add_proccess_to_node(&mut self, node: usize) {
-        let nodes = Self::all_processes(self.node_cnt).collect::<Vec<_>>();
-        let node_name = Self::node_name(node);
-        let process_name = Self::process_name(node);
-        let process = RaftProcess::new(nodes, node, 0.15);
-        self.sim.add_process(&process_name, process, &node_name);
+        let node_spec = &self.spec.nodes[node];
+        for process in node_spec.processes.iter() {
+            let created = self
+                .factory
+                .create(process, &node_spec.address(&process.name), &self.spec)
+                .unwrap();
+            self.sim
+                .add_process(&process.name, created, &node_spec.name);
+        }
     }
 
     fn node_name(node: usize) -> String {
@@ -214,18 +258,6 @@ impl SimWrapper {
         format!("process_{}", proc)
     }
 
-    fn process_addr(node: usize) -> Address {
-        Address {
-            host: Self::node_name(node),
-            port: 123,
-            process_name: Self::process_name(node),
-        }
-    }
-
-    fn all_processes(node_cnt: usize) -> impl Iterator<Item = Address> {
-        (0..node_cnt).map(Self::process_addr)
-    }
-
     //////////////////////////////////////////////////////////////////////////////////////////
 
     /// Allows to send [`InitializeRequest`] for all processes
@@ -280,9 +312,12 @@ impl SimWrapper {
             let proc_name = Self::process_name(proc);
             let node_name = Self::node_name(proc);
             if let Some(messages) = self.sim.read_local_messages(&proc_name, &node_name) {
-                messages
-                    .into_iter()
-                    .for_each(|message| self.proc_info[proc].on_local_message(message));
+                messages.into_iter().for_each(|message| {
+                    if self.proc_info[proc].initialized && message.tip() == LOCAL_RESPONSE {
+                        self.record_response(&message.clone().into());
+                    }
+                    self.proc_info[proc].on_local_message(message)
+                });
             }
         }
     }
@@ -328,6 +363,7 @@ impl SimWrapper {
         let command_id = self.proc_info[proc].next_command_id();
         let process_name = Self::process_name(proc);
         let node_name = Self::node_name(proc);
+        self.record_invoke(command_id, &process_name, KvInput::Command(cmd.clone()));
         let command = Command::new(cmd, command_id);
         self.sim
             .send_local_message(&process_name, &node_name, command.into());
@@ -343,6 +379,7 @@ impl SimWrapper {
             request_id,
             min_commit_id: None,
         };
+        self.record_invoke(request_id, &process_name, KvInput::Read(key.to_owned()));
         self.sim
             .send_local_message(&process_name, &node_name, command.into());
         request_id
@@ -362,6 +399,7 @@ impl SimWrapper {
             request_id,
             min_commit_id: Some(commit_idx),
         };
+        self.record_invoke(request_id, &process_name, KvInput::Read(key.to_owned()));
         self.sim
             .send_local_message(&process_name, &node_name, command.into());
         request_id
@@ -369,6 +407,36 @@ impl SimWrapper {
 
     //////////////////////////////////////////////////////////////////////////////////////////
 
+    fn record_invoke(&mut self, request_id: CommandId, process_name: &str, input: KvInput) {
+        let op = self.history.invoke(process_name, input, self.sim.time());
+        self.pending_ops.insert(request_id, op);
+    }
+
+    fn record_response(&mut self, response: &LocalResponse) {
+        let output = match &response.tp {
+            LocalResponseType::ReadValue(value) => KvOutput::Value(value.clone()),
+            LocalResponseType::Command(reply) => KvOutput::Status(reply.status),
+            // request was not applied, operation remains not completed
+            _ => return,
+        };
+        if let Some(op) = self.pending_ops.remove(&response.request_id) {
+            self.history.complete(op, output, self.sim.time());
+        }
+    }
+
+    /// Allows to get history of commands and read requests
+    /// sent to processes with completion times of replies
+    pub fn history(&self) -> &History<KvInput, KvOutput> {
+        &self.history
+    }
+
+    /// Allows to check history of commands and read requests is linearizable
+    pub fn check_linearizability(&self) -> Result<(), LinearizabilityError> {
+        self.history.check(&KvModel {})
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////////
+
     /// Allows to get current leader
     pub fn current_leader(&self) -> Option<usize> {
         let mut leader: Option<(usize, usize)> = None; // (term, leader)
@@ -434,6 +502,66 @@ impl SimWrapper {
             .split_network(group1_ref.as_slice(), group2_ref.as_slice());
     }
 
+    /// Allows to set probability of message duplication
+    pub fn set_duplicate_rate(&mut self, duplicate_rate: f64) {
+        self.sim.set_network_duplicate_rate(duplicate_rate);
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////////
+
+    /// Allows to write HTML timeline of the message flows to the file
+    /// at `path`, if the test panics
+    pub fn export_diagram_on_failure(&mut self, path: impl Into<PathBuf>) {
+        self.sim.enable_trace();
+        self.diagram_path = Some(path.into());
+    }
+
+    /// Allows to render sequence diagram of the message flows.
+    /// Trace must be enabled with [`SimWrapper::enable_trace`]
+    pub fn sequence_diagram(&self, format: DiagramFormat, filter: &DiagramFilter) -> String {
+        self.sim.sequence_diagram(format, filter)
+    }
+
+    /// Allows to enable trace of the simulation
+    pub fn enable_trace(&mut self) {
+        self.sim.enable_trace();
+    }
+
+    /// Allows to drop all messages with specified tip sent by the node
+    pub fn drop_messages_from(&mut self, node: usize, tip: &str) {
+        let host = Self::node_name(node);
+        let tip = tip.to_owned();
+        self.sim.set_message_filter(move |from, _to, msg| {
+            if from.host == host && *msg.tip() == tip {
+                MessageAction::Drop
+            } else {
+                MessageAction::Deliver
+            }
+        });
+    }
+
+    /// Allows to check there is at most one leader per term after every step.
+    /// Simulation panics on violation
+    pub fn check_single_leader_per_term(&mut self) {
+        self.sim.add_invariant("single leader per term", |view| {
+            let mut leaders = HashMap::new();
+            for (_, process, raft) in view.processes::<RaftProcess>() {
+                let Some(state_info) = raft.state_info() else {
+                    continue;
+                };
+                if let Role::Leader(_) = state_info.role {
+                    if let Some(other) = leaders.insert(state_info.current_term, process) {
+                        return Err(format!(
+                            "{} and {} are leaders in term {}",
+                            other, process, state_info.current_term
+                        ));
+                    }
+                }
+            }
+            Ok(())
+        });
+    }
+
     /// Allows to remove network split
     pub fn repair_network(&mut self) {
         for node in 0..self.node_cnt {
@@ -442,3 +570,20 @@ impl SimWrapper {
         }
     }
 }
+
+impl Drop for SimWrapper {
+    fn drop(&mut self) {
+        if let Some(path) = self.diagram_path.as_ref() {
+            if std::thread::panicking() {
+                let filter = DiagramFilter::new();
+                match self
+                    .sim
+                    .export_sequence_diagram(path, DiagramFormat::Html, &filter)
+                {
+                    Ok(()) => eprintln!("Sequence diagram written to {}", path.display()),
+                    Err(err) => eprintln!("Failed to write sequence diagram: {}", err),
+                }
+            }
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::BTreeSet;
+
 use dsbuild::{Address, Context, Message};
 use log::info;
 use serde::{Deserialize, Serialize};
@@ -227,7 +229,7 @@ impl RaftState {
             }
             Role::Follower(_) | Role::Candidate(_) => {
                 // change current role on candidate
-                self.role = Role::Candidate(0);
+                self.role = Role::Candidate(BTreeSet::new());
 
                 // increment current term
                 self.change_current_term(self.current_term + 1, ctx.clone())
@@ -364,9 +366,9 @@ impl RaftState {
                     info.match_index[respondent_id] = response.match_index;
                     info.next_index[respondent_id] = response.match_index + 1;
                 }
-            } else {
-                // if next_index was zero, then we must match
-                assert!(info.next_index[respondent_id] > 0);
+            } else if info.next_index[respondent_id] > info.match_index[respondent_id] + 1 {
+                // next index can not be less than matched one,
+                // which is possible if response is duplicated
                 info.next_index[respondent_id] -= 1;
             }
 
@@ -424,11 +426,11 @@ impl RaftState {
         }
 
         // if i am candidate and i received majority of votes,
-        // then i transit to leader
-        if let Role::Candidate(mut votes_granted) = self.role {
-            votes_granted += 1;
-            self.role = Role::Candidate(votes_granted);
-            if votes_granted > self.nodes.len() / 2 {
+        // then i transit to leader.
+        // votes are counted by responders, because responses can be duplicated
+        if let Role::Candidate(votes_granted) = &mut self.role {
+            votes_granted.insert(response.responder_id);
+            if votes_granted.len() > self.nodes.len() / 2 {
                 self.transit_to_leader(ctx);
             }
         }
@@ -507,7 +509,7 @@ impl RaftState {
 
         // check if it is good candidate:
         // i must not vote for noone in current term or
-        // i voted for him already (which seem impossible)
+        // i voted for him already (request can be duplicated)
         let good_candidate = self
             .vote_for
             .map(|val| val == vote_request.candidate_id)
@@ -516,8 +518,10 @@ impl RaftState {
             return false;
         }
 
-        // i can not vote for the same candidate twice in one term
-        assert!(self.vote_for.is_none());
+        // repeat vote for the same candidate in current term
+        if self.vote_for.is_some() {
+            return true;
+        }
 
         // candidate's log should be at least up-to-date as mine
         (vote_request.last_log_term, vote_request.last_log_index)
@@ -795,7 +799,7 @@ impl RaftState {
     // Debug utility
     //////////////////////////////////////////////////////////////////////////////////////////
 
-    fn state_info(&self) -> StateInfo {
+    pub fn state_info(&self) -> StateInfo {
         StateInfo {
             role: self.role.clone(),
             current_term: self.current_term,
@@ -1,3 +1,4 @@
+use dsbuild::{DiagramFilter, DiagramFormat, SeedRunner};
 use raft::{
     cmd::{CommandType, CREATED_CODE, UPDATED_CODE},
     local::LocalResponseType,
@@ -203,6 +204,7 @@ fn reelection() {
     // shutdown leader five times and check new leader and term appears
 
     let mut sim = SimWrapper::new(321, 3);
+    sim.check_single_leader_per_term();
     sim.send_init_for_all();
     sim.make_steps_until_all_initialized();
 
@@ -237,6 +239,7 @@ fn log_replication() {
     // send commands to current leader and check
     // they and persistent after leader fails
     let mut sim = SimWrapper::new(333, 3);
+    sim.check_single_leader_per_term();
     sim.send_init_for_all();
     sim.make_steps_until_all_initialized();
 
@@ -321,6 +324,7 @@ fn network_split() {
     // split network and check nothing does wrong
 
     let mut sim = SimWrapper::new(12345, 3);
+    sim.check_single_leader_per_term();
     sim.send_init_for_all();
     sim.make_steps_until_all_initialized();
 
@@ -399,3 +403,212 @@ fn network_split() {
     };
     assert_eq!(read_value, Some("v3".to_owned()));
 }
+
+//////////////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn duplicated_messages() {
+    // every message between replicas is delivered twice
+    // and commands must be applied exactly once
+
+    let mut sim = SimWrapper::new(12345, 3);
+    sim.set_duplicate_rate(1.0);
+    sim.send_init_for_all();
+    sim.make_steps_until_all_initialized();
+
+    // election
+    sim.make_steps(400);
+    let leader = sim.current_leader().unwrap();
+    let term = sim.current_term().unwrap();
+
+    // create and update value several times
+    let create = sim.send_command(leader, CommandType::create("k1"));
+    sim.make_steps_until_response_id(leader, create);
+    sim.process(leader)
+        .pop_local_and_expect_command_reply(CREATED_CODE);
+    for iter in 1..=5 {
+        let update = sim.send_command(
+            leader,
+            CommandType::update("k1", format!("v{}", iter).as_str()),
+        );
+        sim.make_steps_until_response_id(leader, update);
+        sim.process(leader)
+            .pop_local_and_expect_command_reply(UPDATED_CODE);
+    }
+
+    // forward simulation
+    sim.make_steps(500);
+
+    // duplicates must not cause reelection
+    assert_eq!(sim.current_leader(), Some(leader));
+    assert_eq!(sim.current_term(), Some(term));
+
+    // read last value from leader
+    sim.send_read_request(leader, "k1");
+    sim.make_steps_until_local_message(leader);
+    let response = sim.process(leader).pop_next_local().unwrap();
+    let read_value = match response.tp {
+        LocalResponseType::ReadValue(value) => value,
+        LocalResponseType::RedirectedTo(to, Some(commit_index)) => {
+            sim.send_read_request_with_commit_idx(to, "k1", commit_index);
+            sim.make_steps_until_local_message(to);
+            sim.process(to).pop_local_and_assure_read_value()
+        }
+        _ => panic!("unexpected response type"),
+    };
+    assert_eq!(read_value, Some("v5".to_owned()));
+    sim.process(leader).expect_no_local();
+}
+
+#[test]
+fn lost_append_entries_responses() {
+    // create sim and elect leader
+    let mut sim = SimWrapper::new(12345, 3);
+    sim.send_init_for_all();
+    sim.make_steps_until_all_initialized();
+    sim.make_steps(200);
+    let leader = sim.current_leader().expect("leader must be elected");
+    let term = sim.current_term();
+
+    // leader does not receive responses from one follower,
+    // but commits commands with help of another one
+    sim.drop_messages_from((leader + 1) % 3, "append_entries_response");
+    sim.send_command(leader, CommandType::create("key"));
+    sim.make_steps_until_local_message(leader);
+    sim.process(leader)
+        .pop_local_and_expect_command_reply(CREATED_CODE);
+    sim.send_command(leader, CommandType::update("key", "value"));
+    sim.make_steps_until_local_message(leader);
+    sim.process(leader)
+        .pop_local_and_expect_command_reply(UPDATED_CODE);
+
+    // follower still receives heartbeats, so there is no reelection
+    sim.make_steps(200);
+    assert_eq!(sim.current_leader(), Some(leader));
+    assert_eq!(sim.current_term(), term);
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn election_diagram() {
+    // create sim with trace enabled
+    let mut sim = SimWrapper::new(12345, 3);
+    sim.enable_trace();
+    sim.send_init_for_all();
+    sim.make_steps_until_all_initialized();
+
+    // elect leader
+    sim.make_steps(200);
+    let leader = sim.current_leader().expect("leader must be elected");
+
+    // render diagram of election
+    let filter = DiagramFilter::new().with_tips(&["vote_request", "vote_response"]);
+    let diagram = sim.sequence_diagram(DiagramFormat::Mermaid, &filter);
+
+    // leader requested votes and received them
+    assert!(diagram.starts_with("sequenceDiagram\n"));
+    let leader_id = diagram
+        .lines()
+        .find_map(|line| {
+            line.strip_suffix(&format!(" as node_{}/process_{}", leader, leader))
+                .and_then(|line| line.strip_prefix("    participant "))
+        })
+        .expect("leader must participate in election");
+    assert!(diagram.contains(&format!("{}->>", leader_id)));
+    assert!(diagram.contains(&format!(">>{}: vote_response", leader_id)));
+    assert!(!diagram.contains("append_entries_request"));
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////
+
+/// Pops local responses of the leader and resends
+/// read requests redirected by leader to the followers
+fn serve_redirected_reads(sim: &mut SimWrapper, leader: usize) {
+    while let Some(response) = sim.process(leader).pop_next_local() {
+        if let LocalResponseType::RedirectedTo(to, Some(commit_idx)) = response.tp {
+            sim.send_read_request_with_commit_idx(to, "k1", commit_idx);
+        }
+    }
+}
+
+#[test]
+fn linearizable_history() {
+    // send concurrent updates and reads, shutdown leader in the middle
+    // and check history of replies is linearizable
+    let mut sim = SimWrapper::new(12345, 3);
+    sim.check_single_leader_per_term();
+    sim.send_init_for_all();
+    sim.make_steps_until_all_initialized();
+
+    // elect leader
+    sim.make_steps(200);
+    let mut leader = sim.current_leader().expect("leader must be elected");
+
+    // create key
+    let create = sim.send_command(leader, CommandType::create("k1"));
+    sim.make_steps_until_response_id(leader, create);
+    sim.process(leader)
+        .pop_local_and_expect_command_reply(CREATED_CODE);
+
+    for iter in 0..20 {
+        if iter == 10 {
+            // shutdown leader and wait for new one
+            sim.shutdown_node(leader);
+            sim.make_steps(500);
+            let new_leader = sim.current_leader().expect("leader must be elected");
+            assert_ne!(leader, new_leader);
+            sim.rerun_node(leader);
+            leader = new_leader;
+        }
+
+        // send update and reads concurrently
+        sim.send_command(
+            leader,
+            CommandType::update("k1", format!("v{}", iter).as_str()),
+        );
+        sim.send_read_request(leader, "k1");
+        sim.send_command(
+            leader,
+            CommandType::cas("k1", format!("v{}", iter).as_str(), "cas"),
+        );
+        sim.send_read_request(leader, "k1");
+        sim.make_steps(50);
+        serve_redirected_reads(&mut sim, leader);
+        sim.make_steps(50);
+        (0..3).for_each(|node| sim.process(node).clear_locals());
+    }
+
+    let completed = sim
+        .history()
+        .operations()
+        .iter()
+        .filter(|op| op.output.is_some())
+        .count();
+    assert!(completed > 40);
+    sim.check_linearizability().unwrap();
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn reelection_many_seeds() {
+    // check leader is elected and reelected after shutdown for many seeds
+    let report = SeedRunner::from_env(0..32).run(|seed| {
+        let mut sim = SimWrapper::new(seed, 3);
+        sim.check_single_leader_per_term();
+        sim.send_init_for_all();
+        sim.make_steps_until_all_initialized();
+
+        sim.make_steps(300);
+        let leader = sim.current_leader().ok_or("leader is not elected")?;
+
+        sim.shutdown_node(leader);
+        sim.make_steps(300);
+        match sim.current_leader() {
+            Some(new_leader) if new_leader != leader => Ok(()),
+            _ => Err("leader is not reelected".to_owned()),
+        }
+    });
+    assert!(report.is_success(), "{}", report);
+}
@@ -0,0 +1,98 @@
+//! Command line tool, which controls [real node][dsbuild::RealNode] with
+//! [enabled][dsbuild::RealNode::enable_admin] admin service.
+
+use std::process::ExitCode;
+
+use dsbuild::{AdminClient, Message};
+
+const USAGE: &str = "Usage: dsbuild-ctl <node> <command>
+
+Node is specified as <host>:<port> or as path to the Unix domain socket.
+
+Commands:
+    list                            List running processes
+    send <process> <tip> [<data>]   Send local message with JSON data to the process
+    read <process>                  Read local messages sent by the process
+    stop <process>                  Stop the process
+    stats                           Show statistics of the node";
+
+/// Parses node address into the host and port.
+fn parse_node(node: &str) -> Result<(String, u16), String> {
+    if node.starts_with("unix:") || node.starts_with('/') {
+        return Ok((node.to_owned(), 0));
+    }
+    let (host, port) = node
+        .rsplit_once(':')
+        .ok_or(format!("port of the node '{}' is not specified", node))?;
+    let port = port
+        .parse()
+        .map_err(|_| format!("incorrect port of the node '{}'", node))?;
+    Ok((host.to_owned(), port))
+}
+
+async fn run(args: &[String]) -> Result<(), String> {
+    let [node, command, params @ ..] = args else {
+        return Err(USAGE.to_owned());
+    };
+    let (host, port) = parse_node(node)?;
+    let mut client = AdminClient::connect(&host, port)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    match (command.as_str(), params) {
+        ("list", []) => {
+            for process in client.list_processes().await.map_err(|e| e.to_string())? {
+                println!("{}", process);
+            }
+        }
+        ("send", [process, tip, data @ ..]) if data.len() <= 1 => {
+            let data = data.first().map(String::as_str).unwrap_or("null");
+            let msg = Message::new_raw(tip, data.as_bytes())?;
+            client
+                .send_local(process, msg)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        ("read", [process]) => {
+            for msg in client
+                .read_local(process)
+                .await
+                .map_err(|e| e.to_string())?
+            {
+                println!("{} {}", msg.tip(), String::from_utf8_lossy(msg.raw_data()));
+            }
+        }
+        ("stop", [process]) => {
+            client
+                .stop_process(process)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        ("stats", []) => {
+            let stats = client.stats().await.map_err(|e| e.to_string())?;
+            println!("uptime: {:.3}s", stats.uptime);
+            println!("processes: {}", stats.processes);
+            println!("network messages sent: {}", stats.network_messages_sent);
+            println!(
+                "network messages received: {}",
+                stats.network_messages_received
+            );
+            println!("local messages sent: {}", stats.local_messages_sent);
+            println!("local messages received: {}", stats.local_messages_received);
+        }
+        _ => return Err(USAGE.to_owned()),
+    }
+    Ok(())
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match run(&args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(info) => {
+            eprintln!("{}", info);
+            ExitCode::FAILURE
+        }
+    }
+}
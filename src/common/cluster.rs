@@ -0,0 +1,386 @@
+//! Definition of [`ClusterSpec`] and [`ProcessFactory`].
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    real::{builder::ConfigError, io::IOProcessWrapper, node::Node as RealNode},
+    sim::system::Sim,
+};
+
+use super::process::{Address, Process};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Represents error of the [cluster specification][ClusterSpec] processing.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ClusterError {
+    /// Specification file can not be read.
+    Io(String),
+    /// Specification can not be parsed.
+    Parse(String),
+    /// Node or process name is empty or contains `/` symbol.
+    InvalidName(String),
+    /// There are several nodes with the same name.
+    DuplicateNode(String),
+    /// There are several nodes with the same host and port.
+    DuplicateAddress(String, u16),
+    /// There are several processes with the same name on the node.
+    DuplicateProcess(String, String),
+    /// Node with the specified name is not present in the specification.
+    UnknownNode(String),
+    /// Kind of the process is not [registered][ProcessFactory::register] in the factory.
+    UnknownKind(String),
+    /// Arguments of the process can not be parsed.
+    InvalidArgs(String, String),
+    /// Storage mount of the node is required in real mode, but not specified.
+    MissingStorageMount(String),
+    /// Real node can not be created.
+    Node(String, ConfigError),
+    /// OS process of the node can not be [launched][crate::LocalCluster].
+    Spawn(String, String),
+    /// Node [launched][crate::LocalCluster] by the cluster can not bind its socket.
+    Bind(String, String),
+}
+
+impl fmt::Display for ClusterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClusterError::Io(reason) => write!(f, "can not read cluster spec: {}", reason),
+            ClusterError::Parse(reason) => write!(f, "can not parse cluster spec: {}", reason),
+            ClusterError::InvalidName(name) => write!(f, "invalid name '{}'", name),
+            ClusterError::DuplicateNode(node) => write!(f, "duplicate node '{}'", node),
+            ClusterError::DuplicateAddress(host, port) => {
+                write!(f, "duplicate node address {}:{}", host, port)
+            }
+            ClusterError::DuplicateProcess(node, process) => {
+                write!(f, "duplicate process '{}' on node '{}'", process, node)
+            }
+            ClusterError::UnknownNode(node) => write!(f, "unknown node '{}'", node),
+            ClusterError::UnknownKind(kind) => write!(f, "unknown process kind '{}'", kind),
+            ClusterError::InvalidArgs(process, reason) => {
+                write!(f, "invalid args of process '{}': {}", process, reason)
+            }
+            ClusterError::MissingStorageMount(node) => {
+                write!(f, "storage mount of node '{}' is not specified", node)
+            }
+            ClusterError::Node(node, err) => write!(f, "can not create node '{}': {}", node, err),
+            ClusterError::Spawn(node, reason) => {
+                write!(f, "can not launch node '{}': {}", node, reason)
+            }
+            ClusterError::Bind(node, reason) => {
+                write!(f, "node '{}' can not bind its socket: {}", node, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ClusterError {}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Specification of the process in the [cluster specification][ClusterSpec].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProcessSpec {
+    /// Name of the process on the node.
+    pub name: String,
+    /// Kind of the process, which determines [constructor][ProcessFactory::register] of the process.
+    pub kind: String,
+    /// Arguments passed to the constructor of the process.
+    #[serde(default)]
+    pub args: serde_json::Value,
+}
+
+/// Specification of the node in the [cluster specification][ClusterSpec].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NodeSpec {
+    /// Name of the node.
+    pub name: String,
+    /// Host of the node, see [`RealNode::new`][crate::RealNode::new].
+    pub host: String,
+    /// Port of the node.
+    pub port: u16,
+    /// Directory of the node file system mount in real mode.
+    /// It is created if it does not exist.
+    #[serde(default)]
+    pub storage_mount: Option<String>,
+    /// Storage capacity of the node in simulation.
+    #[serde(default)]
+    pub storage_capacity: usize,
+    /// Processes of the node.
+    #[serde(default)]
+    pub processes: Vec<ProcessSpec>,
+}
+
+impl NodeSpec {
+    /// Returns address of the process with the specified name on the node.
+    pub fn address(&self, process: &str) -> Address {
+        Address::new_ref(&self.host, self.port, process)
+    }
+}
+
+/// Declarative specification of the cluster, which consists of nodes and processes on them.
+///
+/// The same specification can be used to build [simulation][ClusterSpec::build_sim]
+/// for tests and [real nodes][ClusterSpec::build_real_node] for deployment.
+/// Processes are created by the [factory][ProcessFactory] from their kinds and arguments.
+///
+/// Specification is stored in JSON format:
+///
+/// ```json
+/// {
+///     "nodes": [
+///         {
+///             "name": "node1",
+///             "host": "127.0.0.1",
+///             "port": 10001,
+///             "storage_mount": "/tmp/node1",
+///             "processes": [
+///                 { "name": "replica", "kind": "replica", "args": { "timeout": 0.5 } }
+///             ]
+///         }
+///     ]
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClusterSpec {
+    /// Nodes of the cluster.
+    pub nodes: Vec<NodeSpec>,
+}
+
+impl ClusterSpec {
+    /// Reads specification from the JSON file.
+    pub fn from_file(path: &str) -> Result<Self, ClusterError> {
+        let json = std::fs::read_to_string(path).map_err(|e| ClusterError::Io(e.to_string()))?;
+        Self::from_json(&json)
+    }
+
+    /// Parses specification from the JSON string and [validates][ClusterSpec::validate] it.
+    pub fn from_json(json: &str) -> Result<Self, ClusterError> {
+        let spec: Self =
+            serde_json::from_str(json).map_err(|e| ClusterError::Parse(e.to_string()))?;
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    /// Returns specification in JSON format.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Checks that names are correct and nodes and processes are unique.
+    pub fn validate(&self) -> Result<(), ClusterError> {
+        let mut names = HashSet::new();
+        let mut addresses = HashSet::new();
+        for node in self.nodes.iter() {
+            check_name(&node.name)?;
+            if !names.insert(node.name.as_str()) {
+                return Err(ClusterError::DuplicateNode(node.name.clone()));
+            }
+            if !addresses.insert((node.host.as_str(), node.port)) {
+                return Err(ClusterError::DuplicateAddress(node.host.clone(), node.port));
+            }
+            let mut processes = HashSet::new();
+            for process in node.processes.iter() {
+                check_name(&process.name)?;
+                if !processes.insert(process.name.as_str()) {
+                    return Err(ClusterError::DuplicateProcess(
+                        node.name.clone(),
+                        process.name.clone(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns node with the specified name.
+    pub fn node(&self, name: &str) -> Option<&NodeSpec> {
+        self.nodes.iter().find(|node| node.name == name)
+    }
+
+    /// Returns addresses of the processes of the specified kind in order of their specification.
+    ///
+    /// It allows processes to find their peers, e.g. other replicas of the group.
+    pub fn addresses(&self, kind: &str) -> Vec<Address> {
+        self.nodes
+            .iter()
+            .flat_map(|node| {
+                node.processes
+                    .iter()
+                    .filter(|process| process.kind == kind)
+                    .map(|process| node.address(&process.name))
+            })
+            .collect()
+    }
+
+    /// Creates simulation with the specified seed, which contains nodes and processes of the cluster.
+    pub fn build_sim(&self, seed: u64, factory: &ProcessFactory) -> Result<Sim, ClusterError> {
+        self.validate()?;
+        let mut sim = Sim::new(seed);
+        for node in self.nodes.iter() {
+            sim.add_node_with_storage(&node.name, &node.host, node.port, node.storage_capacity);
+            for process in node.processes.iter() {
+                let created = factory.create(process, &node.address(&process.name), self)?;
+                sim.add_process(&process.name, created, &node.name);
+            }
+        }
+        Ok(sim)
+    }
+
+    /// Creates real node with the specified name, which contains processes of the node
+    /// from the specification.
+    pub fn build_real_node(
+        &self,
+        name: &str,
+        factory: &ProcessFactory,
+    ) -> Result<ClusterNode, ClusterError> {
+        self.validate()?;
+        let spec = self
+            .node(name)
+            .ok_or(ClusterError::UnknownNode(name.to_owned()))?;
+        let storage_mount = spec
+            .storage_mount
+            .as_ref()
+            .ok_or(ClusterError::MissingStorageMount(name.to_owned()))?;
+
+        let mut node = RealNode::builder(&spec.host, spec.port, storage_mount)
+            .create_storage_mount(true)
+            .build()
+            .map_err(|e| ClusterError::Node(name.to_owned(), e))?;
+        let mut processes = BTreeMap::new();
+        for process in spec.processes.iter() {
+            let created = factory.create(process, &spec.address(&process.name), self)?;
+            let io = node.add_process(created, process.name.clone());
+            processes.insert(process.name.clone(), io);
+        }
+
+        Ok(ClusterNode {
+            name: name.to_owned(),
+            node,
+            processes,
+        })
+    }
+
+    /// Creates real nodes of the cluster.
+    ///
+    /// See [`ClusterSpec::build_real_node`] for more details.
+    pub fn build_real_nodes(
+        &self,
+        factory: &ProcessFactory,
+    ) -> Result<Vec<ClusterNode>, ClusterError> {
+        self.nodes
+            .iter()
+            .map(|node| self.build_real_node(&node.name, factory))
+            .collect()
+    }
+}
+
+fn check_name(name: &str) -> Result<(), ClusterError> {
+    if name.is_empty() || name.contains('/') {
+        Err(ClusterError::InvalidName(name.to_owned()))
+    } else {
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Real node, which is [built][ClusterSpec::build_real_node] from the cluster specification.
+pub struct ClusterNode {
+    /// Name of the node in the specification.
+    pub name: String,
+    /// Node itself, which is ready to [run][crate::RealNode::run].
+    pub node: RealNode,
+    /// Wrappers of the node processes by their names,
+    /// which allow to exchange local messages with processes.
+    pub processes: BTreeMap<String, IOProcessWrapper<Box<dyn Process>>>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+type Constructor =
+    Box<dyn Fn(&serde_json::Value, &Address, &ClusterSpec) -> Result<Box<dyn Process>, String>>;
+
+/// Allows to create processes from the [cluster specification][ClusterSpec].
+///
+/// Factory maps kinds of the processes to their constructors.
+///
+/// # Example
+///
+/// ```no_run
+/// use dsbuild::{Address, ClusterSpec, Context, Message, Process, ProcessFactory};
+///
+/// struct Replica {
+///     peers: Vec<Address>,
+///     timeout: f64,
+/// }
+///
+/// impl Process for Replica {
+///     fn on_local_message(&mut self, _msg: Message, _ctx: Context) {}
+///
+///     fn on_timer(&mut self, _name: String, _ctx: Context) {}
+///
+///     fn on_message(&mut self, _msg: Message, _from: Address, _ctx: Context) {}
+/// }
+///
+/// let mut factory = ProcessFactory::new();
+/// factory.register("replica", |timeout: f64, _address, spec: &ClusterSpec| Replica {
+///     peers: spec.addresses("replica"),
+///     timeout,
+/// });
+///
+/// let spec = ClusterSpec::from_file("cluster.json").unwrap();
+/// let mut sim = spec.build_sim(12345, &factory).unwrap();
+/// sim.step_until_no_events();
+/// ```
+#[derive(Default)]
+pub struct ProcessFactory {
+    constructors: HashMap<String, Constructor>,
+}
+
+impl ProcessFactory {
+    /// Creates factory without registered constructors.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers constructor of the processes of the specified kind.
+    ///
+    /// Constructor receives [arguments][ProcessSpec::args] of the process deserialized into `A`,
+    /// address of the process and the whole cluster specification.
+    pub fn register<A, P, F>(&mut self, kind: &str, constructor: F) -> &mut Self
+    where
+        A: DeserializeOwned,
+        P: Process + 'static,
+        F: Fn(A, &Address, &ClusterSpec) -> P + 'static,
+    {
+        self.constructors.insert(
+            kind.to_owned(),
+            Box::new(move |args, address, spec| {
+                let args = A::deserialize(args).map_err(|e| e.to_string())?;
+                Ok(Box::new(constructor(args, address, spec)))
+            }),
+        );
+        self
+    }
+
+    /// Creates process from its specification.
+    pub fn create(
+        &self,
+        process: &ProcessSpec,
+        address: &Address,
+        spec: &ClusterSpec,
+    ) -> Result<Box<dyn Process>, ClusterError> {
+        let constructor = self
+            .constructors
+            .get(&process.kind)
+            .ok_or(ClusterError::UnknownKind(process.kind.clone()))?;
+        constructor(&process.args, address, spec)
+            .map_err(|e| ClusterError::InvalidArgs(process.name.clone(), e))
+    }
+}
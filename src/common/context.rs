@@ -2,11 +2,16 @@
 
 use std::future::Future;
 
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64;
+
 use crate::{real::context::RealContext, sim::context::VirtualContext, SendResult};
 
 use super::{
-    fs::{File, FsResult},
+    fs::{check_file_name, File, FsResult},
+    membership::{Member, MembershipView},
     message::{Message, Tag},
+    network::{check_message_size, RetryPolicy, SendError},
     process::Address,
 };
 
@@ -33,18 +38,37 @@ enum ContextVariant {
 #[derive(Clone)]
 pub struct Context {
     context_variant: ContextVariant,
+    retry_policy: Option<RetryPolicy>,
 }
 
 impl Context {
     pub(crate) fn new_real(real_ctx: RealContext) -> Self {
         Self {
             context_variant: ContextVariant::Real(real_ctx),
+            retry_policy: None,
         }
     }
 
     pub(crate) fn new_virt(virt_ctx: VirtualContext) -> Self {
         Self {
             context_variant: ContextVariant::Virtual(virt_ctx),
+            retry_policy: None,
+        }
+    }
+
+    /// Returns context, which retries reliable sends according to the specified [policy][RetryPolicy].
+    ///
+    /// Policy is applied to [`send_with_ack`][Context::send_with_ack] and
+    /// [`send_with_tag`][Context::send_with_tag]. Failed attempts are retried after the backoff delay,
+    /// including attempts failed with [`ProcessNotFound`][crate::SendError::ProcessNotFound],
+    /// as receiver process can be added later. Attempts failed with
+    /// [`PayloadTooLarge`][crate::SendError::PayloadTooLarge] or
+    /// [`Serialization`][crate::SendError::Serialization] errors are not retried. In simulation backoff delays are measured in virtual time
+    /// and jitter is deterministic.
+    pub fn with_retry_policy(&self, policy: RetryPolicy) -> Self {
+        Self {
+            context_variant: self.context_variant.clone(),
+            retry_policy: Some(policy),
         }
     }
 
@@ -59,6 +83,10 @@ impl Context {
     /// Simulation allows to configure delivery [probability][crate::Sim::set_network_drop_rate]
     /// and [delay][crate::Sim::set_network_delays].
     pub fn send(&self, msg: Message, dst: Address) {
+        if let Err(err) = check_message_size(&msg) {
+            log::warn!("Message not sent: {}", err);
+            return;
+        }
         match &self.context_variant {
             ContextVariant::Real(ctx) => ctx.send(msg, dst),
             ContextVariant::Virtual(ctx) => ctx.send(msg, dst),
@@ -72,7 +100,27 @@ impl Context {
     ///
     /// If acknowledgment was not received for `timeout` seconds,
     /// method will return with [`Timeout`][crate::SendError::Timeout].
+    ///
+    /// If message size exceeds [`MAX_MESSAGE_SIZE`][crate::MAX_MESSAGE_SIZE],
+    /// method will return with [`PayloadTooLarge`][crate::SendError::PayloadTooLarge].
+    /// See [`SendError`] for other reasons of failure.
+    ///
+    /// If [retry policy][Context::with_retry_policy] is set, failed attempts will be retried
+    /// and `timeout` limits the single attempt.
     pub async fn send_with_ack(&self, msg: Message, dst: Address, timeout: f64) -> SendResult<()> {
+        check_message_size(&msg)?;
+        match &self.retry_policy {
+            Some(policy) => {
+                self.retry(policy, timeout, &dst, |timeout| {
+                    self.send_with_ack_once(msg.clone(), dst.clone(), timeout)
+                })
+                .await
+            }
+            None => self.send_with_ack_once(msg, dst, timeout).await,
+        }
+    }
+
+    async fn send_with_ack_once(&self, msg: Message, dst: Address, timeout: f64) -> SendResult<()> {
         match &self.context_variant {
             ContextVariant::Real(ctx) => ctx.send_with_ack(msg, dst, timeout).await,
             ContextVariant::Virtual(ctx) => ctx.send_with_ack(msg, dst, timeout).await,
@@ -88,12 +136,34 @@ impl Context {
     /// Note that receiver will not be explicitly
     /// notified about message was tagged, so it is on user to additionaly pass tag within
     /// the message if it is needed.
+    ///
+    /// If [retry policy][Context::with_retry_policy] is set, failed attempts will be retried
+    /// and `timeout` limits the single attempt.
     pub async fn send_with_tag(
         &self,
         msg: Message,
         tag: Tag,
         to: Address,
         timeout: f64,
+    ) -> SendResult<()> {
+        check_message_size(&msg)?;
+        match &self.retry_policy {
+            Some(policy) => {
+                self.retry(policy, timeout, &to, |timeout| {
+                    self.send_with_tag_once(msg.clone(), tag, to.clone(), timeout)
+                })
+                .await
+            }
+            None => self.send_with_tag_once(msg, tag, to, timeout).await,
+        }
+    }
+
+    async fn send_with_tag_once(
+        &self,
+        msg: Message,
+        tag: Tag,
+        to: Address,
+        timeout: f64,
     ) -> SendResult<()> {
         match &self.context_variant {
             ContextVariant::Real(ctx) => ctx.send_with_tag(msg, tag, to, timeout).await,
@@ -117,12 +187,67 @@ impl Context {
         to: Address,
         timeout: f64,
     ) -> SendResult<Message> {
+        check_message_size(&msg)?;
         match &self.context_variant {
             ContextVariant::Real(ctx) => ctx.send_recv_with_tag(msg, tag, to, timeout).await,
             ContextVariant::Virtual(ctx) => ctx.send_recv_with_tag(msg, tag, to, timeout).await,
         }
     }
 
+    /// Repeats send attempts according to the retry policy.
+    async fn retry<F, Fut>(
+        &self,
+        policy: &RetryPolicy,
+        timeout: f64,
+        dst: &Address,
+        mut attempt: F,
+    ) -> SendResult<()>
+    where
+        F: FnMut(f64) -> Fut,
+        Fut: Future<Output = SendResult<()>>,
+    {
+        let start = self.time();
+        let mut rng = Pcg64::seed_from_u64(self.random_seed(dst));
+        let mut attempt_num = 0;
+        loop {
+            attempt_num += 1;
+
+            let attempt_timeout = match policy.deadline {
+                Some(deadline) => timeout.min(start + deadline - self.time()),
+                None => timeout,
+            };
+            if attempt_timeout <= 0.0 {
+                return Err(SendError::Timeout);
+            }
+
+            let error = match attempt(attempt_timeout).await {
+                Ok(()) => return Ok(()),
+                Err(error) => error,
+            };
+
+            if attempt_num >= policy.max_attempts || error.is_permanent() {
+                return Err(error);
+            }
+
+            let backoff = policy.backoff(attempt_num, rng.gen());
+            if let Some(deadline) = policy.deadline {
+                if self.time() + backoff >= start + deadline {
+                    return Err(error);
+                }
+            }
+            self.sleep(backoff).await;
+        }
+    }
+
+    /// Returns random seed for the activity related to the specified address.
+    /// In simulation seed is determined by the simulation seed, so runs are reproducible.
+    pub(crate) fn random_seed(&self, dst: &Address) -> u64 {
+        match &self.context_variant {
+            ContextVariant::Real(ctx) => ctx.random_seed(),
+            ContextVariant::Virtual(ctx) => ctx.random_seed(dst),
+        }
+    }
+
     ////////////////////////////////////////////////////////////////////////////////
     // Local
     ////////////////////////////////////////////////////////////////////////////////
@@ -162,6 +287,47 @@ impl Context {
         }
     }
 
+    ////////////////////////////////////////////////////////////////////////////////
+    // Membership
+    ////////////////////////////////////////////////////////////////////////////////
+
+    /// Returns members of the cluster known to the node, including the node itself.
+    ///
+    /// Membership must be enabled on the node with [`Sim::enable_membership`][crate::Sim::enable_membership]
+    /// or [`RealNode::enable_membership`][crate::RealNode::enable_membership],
+    /// otherwise the returned list is empty.
+    pub fn members(&self) -> Vec<Member> {
+        self.membership().members()
+    }
+
+    /// Subscribes the process to changes of the cluster membership.
+    ///
+    /// On every change the process receives network message with tip
+    /// [`MEMBERSHIP_EVENT`][crate::MEMBERSHIP_EVENT] from the
+    /// [membership process][crate::MEMBERSHIP_PROCESS] of its node.
+    pub fn subscribe_members(&self) {
+        let process_name = match &self.context_variant {
+            ContextVariant::Real(ctx) => &ctx.address.process_name,
+            ContextVariant::Virtual(ctx) => &ctx.address.process_name,
+        };
+        self.membership().subscribe(process_name);
+    }
+
+    /// Allows node to gracefully leave the cluster.
+    ///
+    /// Node announces it left the cluster on the next protocol period and stops
+    /// to participate in the membership protocol.
+    pub fn leave_cluster(&self) {
+        self.membership().request_leave();
+    }
+
+    fn membership(&self) -> &MembershipView {
+        match &self.context_variant {
+            ContextVariant::Real(ctx) => &ctx.output.membership,
+            ContextVariant::Virtual(ctx) => &ctx.membership,
+        }
+    }
+
     ////////////////////////////////////////////////////////////////////////////////
     // File system
     ////////////////////////////////////////////////////////////////////////////////
@@ -170,9 +336,15 @@ impl Context {
     ///
     /// On success, created [file][File] will be returned.
     ///
-    /// If there is no enough space to create file,
-    /// [`BufferSizeExceed`][crate::FsError::BufferSizeExceed] error will be returned.
+    /// If there is no enough space to create file, [`NoSpace`][crate::FsError::NoSpace] error
+    /// will be returned in real mode and [`BufferSizeExceed`][crate::FsError::BufferSizeExceed]
+    /// in simulation.
+    ///
+    /// Every file system method returns [`InvalidName`][crate::FsError::InvalidName] error
+    /// if file name is empty, `.` or `..`, or contains `/`, `\` or NUL character,
+    /// so files can not be located outside of the storage mount or in its subdirectories.
     pub async fn create_file<'a>(&'a self, name: &'a str) -> FsResult<File> {
+        check_file_name(name)?;
         match &self.context_variant {
             ContextVariant::Real(ctx) => ctx.create_file(name).await,
             ContextVariant::Virtual(ctx) => ctx.create_file(name).await,
@@ -186,6 +358,7 @@ impl Context {
     /// its removal may fail for a numbers of reasons, such as not sufficient
     /// permissions.
     pub async fn delete_file<'a>(&'a self, name: &'a str) -> FsResult<()> {
+        check_file_name(name)?;
         match &self.context_variant {
             ContextVariant::Real(ctx) => ctx.delete_file(name).await,
             ContextVariant::Virtual(ctx) => ctx.delete_file(name).await,
@@ -194,6 +367,7 @@ impl Context {
 
     /// Allows to check if file exists.
     pub async fn file_exists<'a>(&'a self, name: &'a str) -> FsResult<bool> {
+        check_file_name(name)?;
         match &self.context_variant {
             ContextVariant::Real(ctx) => ctx.file_exists(name).await,
             ContextVariant::Virtual(ctx) => ctx.file_exists(name).await,
@@ -207,6 +381,7 @@ impl Context {
     /// If file does not exists, method will return with [`NotFound`][crate::FsError::NotFound]
     /// error.
     pub async fn open_file<'a>(&'a self, name: &'a str) -> FsResult<File> {
+        check_file_name(name)?;
         match &self.context_variant {
             ContextVariant::Real(ctx) => ctx.open_file(name).await,
             ContextVariant::Virtual(ctx) => ctx.open_file(name).await,
@@ -225,6 +400,16 @@ impl Context {
         }
     }
 
+    /// Allows to sleep for the specified number of seconds.
+    ///
+    /// In simulation the virtual time is used.
+    pub async fn sleep(&self, duration: f64) {
+        match &self.context_variant {
+            ContextVariant::Real(ctx) => ctx.sleep(duration).await,
+            ContextVariant::Virtual(ctx) => ctx.sleep(duration).await,
+        }
+    }
+
     /// Set timer with specified name and delay.
     ///
     /// If timer with such name already exists, the delay will be override.
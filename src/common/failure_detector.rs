@@ -0,0 +1,348 @@
+//! Definition of [`FailureDetector`].
+
+use std::collections::{BTreeMap, VecDeque};
+
+use super::{context::Context, message::Message, process::Address};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Tip of the heartbeat messages, which are sent by the failure detector.
+pub const FAILURE_DETECTOR_HEARTBEAT: &str = "failure_detector_heartbeat";
+
+/// Default name of the timer, which is used by the failure detector to send heartbeats.
+const DEFAULT_TIMER_NAME: &str = "failure_detector";
+
+/// Default number of the heartbeat intervals used by the phi-accrual detector.
+const DEFAULT_WINDOW_SIZE: usize = 100;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Represents transition of the monitored process state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DetectorEvent {
+    /// Process is suspected to be failed.
+    Suspect(Address),
+    /// Previously suspected process is alive again.
+    Alive(Address),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Strategy {
+    Heartbeat { timeout: f64 },
+    PhiAccrual { threshold: f64 },
+}
+
+struct PeerState {
+    last_heartbeat: f64,
+    heartbeat_received: bool,
+    intervals: VecDeque<f64>,
+    suspected: bool,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Allows process to detect failures of other processes.
+///
+/// Detector periodically sends [heartbeats][FAILURE_DETECTOR_HEARTBEAT] to the monitored processes
+/// and suspects processes, from which heartbeats were not received in time.
+/// Monitored processes must run failure detector too and monitor this process,
+/// e.g. every process of the group monitors all other processes of the group.
+///
+/// Detector is attached to the process by passing timers and network messages to
+/// [`on_timer`][FailureDetector::on_timer] and [`on_message`][FailureDetector::on_message]
+/// methods, which return [suspect and alive transitions][DetectorEvent] of monitored processes.
+/// Detector uses timer with the [specified name][FailureDetector::with_timer_name],
+/// so process must not use timer with the same name for other purposes.
+/// As detector uses timers and messages of the [context][Context], it behaves
+/// the same way in simulation and in real mode.
+///
+/// Two detection strategies are supported:
+///
+/// - [Heartbeat][FailureDetector::heartbeat] strategy suspects process if no heartbeats
+///   were received from it during fixed timeout.
+/// - [Phi-accrual][FailureDetector::phi_accrual] strategy estimates distribution of intervals
+///   between heartbeats and suspects process if suspicion level `phi` exceeds threshold.
+///
+/// # Example
+///
+/// ```no_run
+/// use dsbuild::{Address, Context, DetectorEvent, FailureDetector, Message, Process};
+///
+/// struct Replica {
+///     detector: FailureDetector,
+/// }
+///
+/// impl Replica {
+///     fn handle(&mut self, events: Vec<DetectorEvent>) {
+///         for event in events {
+///             println!("{:?}", event);
+///         }
+///     }
+/// }
+///
+/// impl Process for Replica {
+///     fn on_local_message(&mut self, _msg: Message, ctx: Context) {
+///         self.detector.start(&ctx);
+///     }
+///
+///     fn on_timer(&mut self, name: String, ctx: Context) {
+///         if let Some(events) = self.detector.on_timer(&name, &ctx) {
+///             self.handle(events);
+///         }
+///     }
+///
+///     fn on_message(&mut self, msg: Message, from: Address, ctx: Context) {
+///         if let Some(events) = self.detector.on_message(&msg, &from, &ctx) {
+///             self.handle(events);
+///         }
+///     }
+/// }
+/// ```
+pub struct FailureDetector {
+    strategy: Strategy,
+    interval: f64,
+    window_size: usize,
+    timer_name: String,
+    peers: BTreeMap<Address, PeerState>,
+    started: bool,
+}
+
+impl FailureDetector {
+    /// Creates detector, which sends heartbeats to `peers` every `interval` seconds
+    /// and suspects peer if no heartbeats were received from it for `timeout` seconds.
+    pub fn heartbeat(peers: Vec<Address>, interval: f64, timeout: f64) -> Self {
+        Self::new(peers, interval, Strategy::Heartbeat { timeout })
+    }
+
+    /// Creates detector, which sends heartbeats to `peers` every `interval` seconds
+    /// and suspects peer if its suspicion level `phi` exceeds `threshold`.
+    ///
+    /// Suspicion level is `-log10` of the probability that heartbeat will arrive later
+    /// than now, i.e. `phi = 1` corresponds to 10% probability of mistake,
+    /// `phi = 2` corresponds to 1% probability and so on. Commonly used threshold is `8`.
+    pub fn phi_accrual(peers: Vec<Address>, interval: f64, threshold: f64) -> Self {
+        Self::new(peers, interval, Strategy::PhiAccrual { threshold })
+    }
+
+    fn new(peers: Vec<Address>, interval: f64, strategy: Strategy) -> Self {
+        let mut detector = Self {
+            strategy,
+            interval,
+            window_size: DEFAULT_WINDOW_SIZE,
+            timer_name: DEFAULT_TIMER_NAME.to_owned(),
+            peers: BTreeMap::new(),
+            started: false,
+        };
+        for peer in peers {
+            detector.add_peer(peer, 0.0);
+        }
+        detector
+    }
+
+    /// Set maximum number of the last intervals between heartbeats,
+    /// which are used to estimate their distribution by the phi-accrual detector.
+    pub fn with_window_size(mut self, window_size: usize) -> Self {
+        self.window_size = window_size.max(1);
+        self
+    }
+
+    /// Set name of the timer, which is used by detector to send heartbeats.
+    ///
+    /// Allows to avoid conflicts with timers of the process or other detectors of the process.
+    /// Default name is `failure_detector`.
+    pub fn with_timer_name(mut self, name: &str) -> Self {
+        self.timer_name = name.to_owned();
+        self
+    }
+
+    /// Starts sending heartbeats and monitoring peers.
+    ///
+    /// Peers are considered alive at the moment of start.
+    pub fn start(&mut self, ctx: &Context) {
+        self.started = true;
+        let now = ctx.time();
+        for state in self.peers.values_mut() {
+            state.last_heartbeat = now;
+        }
+        self.send_heartbeats(ctx);
+        ctx.set_timer(&self.timer_name, self.interval);
+    }
+
+    /// Stops sending heartbeats and monitoring peers.
+    pub fn stop(&mut self, ctx: &Context) {
+        self.started = false;
+        ctx.cancel_timer(&self.timer_name);
+    }
+
+    /// Adds peer to the set of monitored processes.
+    ///
+    /// Peer is considered alive at the moment of addition.
+    pub fn add_peer(&mut self, peer: Address, now: f64) {
+        self.peers.entry(peer).or_insert_with(|| PeerState {
+            last_heartbeat: now,
+            heartbeat_received: false,
+            intervals: VecDeque::new(),
+            suspected: false,
+        });
+    }
+
+    /// Removes peer from the set of monitored processes.
+    pub fn remove_peer(&mut self, peer: &Address) {
+        self.peers.remove(peer);
+    }
+
+    /// Returns monitored processes.
+    pub fn peers(&self) -> Vec<Address> {
+        self.peers.keys().cloned().collect()
+    }
+
+    /// Returns if the peer is currently suspected.
+    ///
+    /// Unknown peers are not suspected.
+    pub fn is_suspected(&self, peer: &Address) -> bool {
+        self.peers.get(peer).is_some_and(|state| state.suspected)
+    }
+
+    /// Returns currently suspected peers.
+    pub fn suspected(&self) -> Vec<Address> {
+        self.peers
+            .iter()
+            .filter(|(_, state)| state.suspected)
+            .map(|(peer, _)| peer.clone())
+            .collect()
+    }
+
+    /// Returns suspicion level of the peer at the specified time.
+    ///
+    /// For heartbeat detector it is the time since the last heartbeat divided by timeout,
+    /// for phi-accrual detector it is `phi`. Returns `None` for unknown peers.
+    pub fn suspicion_level(&self, peer: &Address, now: f64) -> Option<f64> {
+        self.peers
+            .get(peer)
+            .map(|state| self.level(state, now - state.last_heartbeat))
+    }
+
+    /// Handles timer of the process.
+    ///
+    /// Returns `None` if timer does not belong to detector,
+    /// otherwise returns transitions of the monitored processes.
+    ///
+    /// Timer with the [name][FailureDetector::with_timer_name] of the detector is always
+    /// handled as the heartbeat timer, so it must be set only by the detector.
+    pub fn on_timer(&mut self, name: &str, ctx: &Context) -> Option<Vec<DetectorEvent>> {
+        if name != self.timer_name {
+            return None;
+        }
+        if !self.started {
+            return Some(Vec::new());
+        }
+
+        self.send_heartbeats(ctx);
+        ctx.set_timer(&self.timer_name, self.interval);
+
+        let now = ctx.time();
+        let mut events = Vec::new();
+        for (peer, state) in self.peers.iter() {
+            if !state.suspected && self.is_failed(state, now - state.last_heartbeat) {
+                events.push(DetectorEvent::Suspect(peer.clone()));
+            }
+        }
+        for event in events.iter() {
+            if let DetectorEvent::Suspect(peer) = event {
+                self.peers.get_mut(peer).unwrap().suspected = true;
+            }
+        }
+        Some(events)
+    }
+
+    /// Handles network message of the process.
+    ///
+    /// Returns `None` if message does not belong to detector,
+    /// otherwise returns transitions of the monitored processes.
+    pub fn on_message(
+        &mut self,
+        msg: &Message,
+        from: &Address,
+        ctx: &Context,
+    ) -> Option<Vec<DetectorEvent>> {
+        if msg.tip() != FAILURE_DETECTOR_HEARTBEAT {
+            return None;
+        }
+        if !self.started {
+            return Some(Vec::new());
+        }
+
+        let now = ctx.time();
+        let window_size = self.window_size;
+        let Some(state) = self.peers.get_mut(from) else {
+            return Some(Vec::new());
+        };
+
+        // Interval before the first heartbeat depends on the start time, so it is not counted.
+        if state.heartbeat_received {
+            state.intervals.push_back(now - state.last_heartbeat);
+            while state.intervals.len() > window_size {
+                state.intervals.pop_front();
+            }
+        }
+        state.heartbeat_received = true;
+        state.last_heartbeat = now;
+
+        if state.suspected {
+            state.suspected = false;
+            Some(vec![DetectorEvent::Alive(from.clone())])
+        } else {
+            Some(Vec::new())
+        }
+    }
+
+    fn send_heartbeats(&self, ctx: &Context) {
+        for peer in self.peers.keys() {
+            ctx.send(
+                Message::new(FAILURE_DETECTOR_HEARTBEAT, &()).unwrap(),
+                peer.clone(),
+            );
+        }
+    }
+
+    fn is_failed(&self, state: &PeerState, elapsed: f64) -> bool {
+        let level = self.level(state, elapsed);
+        match self.strategy {
+            Strategy::Heartbeat { .. } => level >= 1.0,
+            Strategy::PhiAccrual { threshold } => level > threshold,
+        }
+    }
+
+    fn level(&self, state: &PeerState, elapsed: f64) -> f64 {
+        match self.strategy {
+            Strategy::Heartbeat { timeout } => elapsed / timeout,
+            Strategy::PhiAccrual { .. } => phi(&state.intervals, self.interval, elapsed),
+        }
+    }
+}
+
+/// Calculates suspicion level `phi` using normal distribution of the heartbeat intervals.
+///
+/// Uses logistic approximation of the normal cumulative distribution function
+/// as in [Akka](https://doc.akka.io/docs/akka/current/typed/failure-detector.html).
+pub(crate) fn phi(intervals: &VecDeque<f64>, expected_interval: f64, elapsed: f64) -> f64 {
+    let (mean, std_deviation) = if intervals.is_empty() {
+        // Bootstrap estimation from the expected interval.
+        (expected_interval, expected_interval / 4.0)
+    } else {
+        let count = intervals.len() as f64;
+        let mean = intervals.iter().sum::<f64>() / count;
+        let variance = intervals.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / count;
+        (mean, variance.sqrt())
+    };
+    // Limit deviation from below to avoid suspicion on small jitter.
+    let std_deviation = std_deviation.max(expected_interval / 10.0);
+
+    let y = (elapsed - mean) / std_deviation;
+    let e = (-y * (1.5976 + 0.070566 * y * y)).exp();
+    if elapsed > mean {
+        -(e / (1.0 + e)).log10()
+    } else {
+        -(1.0 - 1.0 / (1.0 + e)).log10()
+    }
+}
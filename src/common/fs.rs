@@ -1,6 +1,9 @@
 //! Manipulations with files.
 
-use std::io::SeekFrom;
+use std::{
+    fmt,
+    io::{ErrorKind, SeekFrom},
+};
 
 use crate::sim::fs::FileWrapper;
 use async_std::{
@@ -40,12 +43,10 @@ impl File {
         match &mut self.0 {
             FileVariant::SimulationFile(file) => file.read(offset, buf).await,
             FileVariant::RealFile(file) => {
-                file.seek(SeekFrom::Start(offset))
-                    .await
-                    .map_err(|_| FsError::Unavailable)?;
+                file.seek(SeekFrom::Start(offset)).await?;
                 file.read(buf)
                     .await
-                    .map_err(|_| FsError::Unavailable)
+                    .map_err(FsError::from)
                     .map(|bytes| u64::try_from(bytes).unwrap())
             }
         }
@@ -58,16 +59,10 @@ impl File {
         match &mut self.0 {
             FileVariant::SimulationFile(file) => file.append(data).await,
             FileVariant::RealFile(file) => {
-                file.seek(SeekFrom::End(0)).await.map_err(|e| {
-                    eprintln!("seed error: {}", e);
-                    FsError::Unavailable
-                })?;
+                file.seek(SeekFrom::End(0)).await?;
                 file.write(data)
                     .await
-                    .map_err(|e| {
-                        eprintln!("write error: {}", e);
-                        FsError::Unavailable
-                    })
+                    .map_err(FsError::from)
                     .map(|bytes| u64::try_from(bytes).unwrap())
             }
         }
@@ -88,9 +83,39 @@ pub enum FsError {
     /// Requested operation can be completed or not.
     Unavailable,
     /// Passed buffer size exceeds size limit.
+    ///
+    /// In simulation it is returned when data does not fit into the storage capacity.
     BufferSizeExceed,
+    /// Permission to access resource is denied.
+    PermissionDenied,
+    /// There is no space left in the storage of the real node.
+    NoSpace,
+    /// Name of the file is invalid.
+    ///
+    /// Name must be non-empty, must not be `.` or `..` and must not contain
+    /// path separators or `NUL` characters.
+    InvalidName(String),
+    /// Other input/output error of the specified kind.
+    Io(ErrorKind),
 }
 
+impl fmt::Display for FsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FsError::AlreadyExists => write!(f, "file already exists"),
+            FsError::NotFound => write!(f, "file not found"),
+            FsError::Unavailable => write!(f, "storage is unavailable"),
+            FsError::BufferSizeExceed => write!(f, "buffer size exceeds limit"),
+            FsError::PermissionDenied => write!(f, "permission denied"),
+            FsError::NoSpace => write!(f, "no space left in the storage"),
+            FsError::InvalidName(name) => write!(f, "invalid file name '{}'", name),
+            FsError::Io(kind) => write!(f, "input/output error: {}", kind),
+        }
+    }
+}
+
+impl std::error::Error for FsError {}
+
 impl From<DSLabFsError> for FsError {
     fn from(value: DSLabFsError) -> Self {
         match value {
@@ -102,6 +127,30 @@ impl From<DSLabFsError> for FsError {
     }
 }
 
+impl From<std::io::Error> for FsError {
+    fn from(value: std::io::Error) -> Self {
+        match value.kind() {
+            ErrorKind::AlreadyExists => Self::AlreadyExists,
+            ErrorKind::NotFound => Self::NotFound,
+            ErrorKind::PermissionDenied => Self::PermissionDenied,
+            ErrorKind::StorageFull => Self::NoSpace,
+            kind => Self::Io(kind),
+        }
+    }
+}
+
+/// Checks the file name does not escape the storage mount.
+///
+/// Returns [`FsError::InvalidName`] if name is empty, `.` or `..`,
+/// or contains `/`, `\` or NUL character.
+pub(crate) fn check_file_name(name: &str) -> FsResult<()> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\', '\0']) {
+        Err(FsError::InvalidName(name.to_owned()))
+    } else {
+        Ok(())
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 /// Represents result of the file system operation.
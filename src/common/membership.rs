@@ -0,0 +1,691 @@
+//! Definition of cluster membership service.
+//!
+//! Membership service is based on the [SWIM](https://www.cs.cornell.edu/projects/Quicksilver/public_pdfs/SWIM.pdf)
+//! gossip protocol. Every node runs the membership process, which periodically probes
+//! random member of the cluster and piggybacks membership updates on probe messages.
+//! Members, which do not respond to direct and indirect probes, become suspected and
+//! are declared dead after the suspicion timeout, unless they refute the suspicion.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, RwLock},
+};
+
+use rand::{seq::SliceRandom, SeedableRng};
+use rand_pcg::Pcg64;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{
+    context::Context,
+    message::Message,
+    process::{Address, Process},
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Name of the membership process, which is added on the node when membership is enabled.
+pub const MEMBERSHIP_PROCESS: &str = "membership";
+
+/// Tip of the messages, which are sent to the [subscribed][crate::Context::subscribe_members]
+/// processes on every change of the cluster membership.
+///
+/// Data of the message is the [`Member`] with updated state.
+pub const MEMBERSHIP_EVENT: &str = "membership_event";
+
+/// Tip of the local message, which starts the membership process.
+pub(crate) const MEMBERSHIP_START: &str = "membership_start";
+
+const PING: &str = "swim_ping";
+const ACK: &str = "swim_ack";
+const PING_REQ: &str = "swim_ping_req";
+const JOIN: &str = "swim_join";
+const SYNC: &str = "swim_sync";
+
+const PROTOCOL_TIMER: &str = "swim_protocol";
+const ACK_TIMER: &str = "swim_ack_timeout";
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Represents state of the cluster member.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MemberState {
+    /// Member responds to probes.
+    Alive,
+    /// Member did not respond to the recent probe and is suspected to be failed.
+    Suspect,
+    /// Member did not refute suspicion in time and is considered failed.
+    Dead,
+    /// Member gracefully left the cluster.
+    Left,
+}
+
+impl MemberState {
+    /// Returns precedence of the state among updates with the same incarnation.
+    fn precedence(self) -> u8 {
+        match self {
+            MemberState::Alive => 0,
+            MemberState::Suspect => 1,
+            MemberState::Dead => 2,
+            MemberState::Left => 3,
+        }
+    }
+}
+
+/// Represents member of the cluster.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Member {
+    /// Address of the membership process on the member node.
+    pub address: Address,
+    /// State of the member.
+    pub state: MemberState,
+    /// Incarnation of the member, which is increased by the member to refute suspicion.
+    pub incarnation: u64,
+}
+
+impl Member {
+    /// Returns address of the process with specified name on the member node.
+    pub fn process_address(&self, process_name: &str) -> Address {
+        Address::new_ref(&self.address.host, self.address.port, process_name)
+    }
+
+    /// Returns if the member is alive or suspected.
+    pub fn is_active(&self) -> bool {
+        matches!(self.state, MemberState::Alive | MemberState::Suspect)
+    }
+
+    /// Returns if the update overrides the current knowledge about member.
+    fn overrides(&self, current: &Member) -> bool {
+        (self.incarnation, self.state.precedence())
+            > (current.incarnation, current.state.precedence())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Configuration of the membership service.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MembershipConfig {
+    /// Addresses of the nodes, which are contacted to join the cluster.
+    /// Process names of the addresses are ignored.
+    pub seeds: Vec<Address>,
+    /// Interval between probes in seconds.
+    pub protocol_period: f64,
+    /// Timeout in seconds for the direct probe, after which indirect probes are sent.
+    /// Must be less than the protocol period.
+    pub ack_timeout: f64,
+    /// Number of members, which are asked to probe the member indirectly.
+    pub indirect_probes: usize,
+    /// Time in seconds after which suspected member is declared dead.
+    pub suspect_timeout: f64,
+    /// Maximum number of membership updates piggybacked on every message.
+    pub max_piggyback: usize,
+}
+
+impl Default for MembershipConfig {
+    fn default() -> Self {
+        Self {
+            seeds: Vec::new(),
+            protocol_period: 1.0,
+            ack_timeout: 0.3,
+            indirect_probes: 3,
+            suspect_timeout: 5.0,
+            max_piggyback: 8,
+        }
+    }
+}
+
+impl MembershipConfig {
+    /// Creates new configuration with specified seed nodes and default protocol settings.
+    pub fn new(seeds: Vec<Address>) -> Self {
+        Self {
+            seeds,
+            ..Default::default()
+        }
+    }
+
+    /// Set protocol period and timeout of the direct probe.
+    pub fn with_protocol_period(mut self, protocol_period: f64, ack_timeout: f64) -> Self {
+        self.protocol_period = protocol_period;
+        self.ack_timeout = ack_timeout;
+        self
+    }
+
+    /// Set number of indirect probes.
+    pub fn with_indirect_probes(mut self, indirect_probes: usize) -> Self {
+        self.indirect_probes = indirect_probes;
+        self
+    }
+
+    /// Set suspicion timeout.
+    pub fn with_suspect_timeout(mut self, suspect_timeout: f64) -> Self {
+        self.suspect_timeout = suspect_timeout;
+        self
+    }
+
+    /// Set maximum number of piggybacked updates.
+    pub fn with_max_piggyback(mut self, max_piggyback: usize) -> Self {
+        self.max_piggyback = max_piggyback;
+        self
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Default)]
+struct ViewState {
+    members: Vec<Member>,
+    subscribers: BTreeSet<String>,
+    leave_requested: bool,
+}
+
+/// Represents membership of the cluster as seen by the node.
+///
+/// View is shared between the membership process and other processes on the node.
+#[derive(Clone, Default)]
+pub(crate) struct MembershipView {
+    inner: Arc<RwLock<ViewState>>,
+}
+
+impl MembershipView {
+    /// Returns known members of the cluster.
+    pub fn members(&self) -> Vec<Member> {
+        self.inner.read().unwrap().members.clone()
+    }
+
+    /// Subscribes process with specified name to membership events.
+    pub fn subscribe(&self, process_name: &str) {
+        self.inner
+            .write()
+            .unwrap()
+            .subscribers
+            .insert(process_name.to_owned());
+    }
+
+    /// Requests node to leave the cluster.
+    pub fn request_leave(&self) {
+        self.inner.write().unwrap().leave_requested = true;
+    }
+
+    fn reset(&self) {
+        let mut state = self.inner.write().unwrap();
+        state.members.clear();
+        state.leave_requested = false;
+    }
+
+    fn set_members(&self, members: Vec<Member>) {
+        self.inner.write().unwrap().members = members;
+    }
+
+    fn subscribers(&self) -> Vec<String> {
+        self.inner
+            .read()
+            .unwrap()
+            .subscribers
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    fn leave_requested(&self) -> bool {
+        self.inner.read().unwrap().leave_requested
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Relay {
+    requester: Address,
+    seq: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Ping {
+    seq: u64,
+    relay: Option<Relay>,
+    updates: Vec<Member>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Ack {
+    seq: u64,
+    relay: Option<Relay>,
+    updates: Vec<Member>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PingReq {
+    seq: u64,
+    target: Address,
+    updates: Vec<Member>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Gossip {
+    updates: Vec<Member>,
+}
+
+struct Probe {
+    target: Address,
+    seq: u64,
+    acked: bool,
+}
+
+type NodeKey = (String, u16);
+
+fn node_key(address: &Address) -> NodeKey {
+    (address.host.clone(), address.port)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Process, which implements the membership protocol on the node.
+pub(crate) struct MembershipProcess {
+    config: MembershipConfig,
+    view: MembershipView,
+    me: Address,
+    incarnation: u64,
+    members: BTreeMap<NodeKey, Member>,
+    suspected_at: BTreeMap<NodeKey, f64>,
+    updates: Vec<(Member, u32)>,
+    probe_order: Vec<Address>,
+    probe: Option<Probe>,
+    seq: u64,
+    rng: Pcg64,
+    running: bool,
+    left: bool,
+}
+
+impl MembershipProcess {
+    /// Creates membership process of the node with specified host and port.
+    pub fn new(config: MembershipConfig, view: MembershipView, host: &str, port: u16) -> Self {
+        let me = Address::new_ref(host, port, MEMBERSHIP_PROCESS);
+
+        Self {
+            config,
+            view,
+            me,
+            incarnation: 0,
+            members: BTreeMap::new(),
+            suspected_at: BTreeMap::new(),
+            updates: Vec::new(),
+            probe_order: Vec::new(),
+            probe: None,
+            seq: 0,
+            // Seeded from the context on start.
+            rng: Pcg64::seed_from_u64(0),
+            running: false,
+            left: false,
+        }
+    }
+
+    fn self_member(&self) -> Member {
+        Member {
+            address: self.me.clone(),
+            state: if self.left {
+                MemberState::Left
+            } else {
+                MemberState::Alive
+            },
+            incarnation: self.incarnation,
+        }
+    }
+
+    fn is_me(&self, address: &Address) -> bool {
+        node_key(address) == node_key(&self.me)
+    }
+
+    fn active_members(&self) -> Vec<Address> {
+        self.members
+            .values()
+            .filter(|member| member.is_active())
+            .map(|member| member.address.clone())
+            .collect()
+    }
+
+    fn start(&mut self, ctx: Context) {
+        if self.running {
+            return;
+        }
+        self.running = true;
+        // Seed is taken from the context to keep simulation deterministic for the same seed.
+        self.rng = Pcg64::seed_from_u64(ctx.random_seed(&self.me));
+        self.view.reset();
+        self.publish();
+        self.join(&ctx);
+        ctx.set_timer(PROTOCOL_TIMER, self.config.protocol_period);
+    }
+
+    fn join(&mut self, ctx: &Context) {
+        let seeds = self
+            .config
+            .seeds
+            .iter()
+            .filter(|seed| !self.is_me(seed))
+            .map(|seed| Address::new_ref(&seed.host, seed.port, MEMBERSHIP_PROCESS))
+            .collect::<Vec<_>>();
+        for seed in seeds {
+            let msg = Gossip {
+                updates: vec![self.self_member()],
+            };
+            ctx.send(Message::new(JOIN, &msg).unwrap(), seed);
+        }
+    }
+
+    fn leave(&mut self, ctx: &Context) {
+        if !self.running {
+            return;
+        }
+        self.running = false;
+        self.left = true;
+        self.incarnation += 1;
+        self.probe = None;
+        ctx.cancel_timer(PROTOCOL_TIMER);
+        ctx.cancel_timer(ACK_TIMER);
+
+        let me = self.self_member();
+        for member in self.active_members() {
+            let msg = Gossip {
+                updates: vec![me.clone()],
+            };
+            ctx.send(Message::new(SYNC, &msg).unwrap(), member);
+        }
+        self.publish();
+        self.notify(&me, ctx);
+    }
+
+    ////////////////////////////////////////////////////////////////////////////////
+    // Failure detection
+    ////////////////////////////////////////////////////////////////////////////////
+
+    fn protocol_round(&mut self, ctx: Context) {
+        if self.view.leave_requested() {
+            self.leave(&ctx);
+            return;
+        }
+
+        // Suspect member, which did not respond during the previous round.
+        if let Some(probe) = self.probe.take() {
+            if !probe.acked {
+                self.suspect(&probe.target, &ctx);
+            }
+        }
+
+        // Declare dead members, which did not refute suspicion in time.
+        let now = ctx.time();
+        let expired = self
+            .suspected_at
+            .iter()
+            .filter(|(_, since)| now - **since >= self.config.suspect_timeout)
+            .filter_map(|(key, _)| self.members.get(key))
+            .map(|member| Member {
+                state: MemberState::Dead,
+                ..member.clone()
+            })
+            .collect::<Vec<_>>();
+        for member in expired {
+            self.apply(member, &ctx);
+        }
+
+        // Retry to join if there are no known members.
+        if self.active_members().is_empty() {
+            self.join(&ctx);
+        }
+
+        if let Some(target) = self.next_target() {
+            self.seq += 1;
+            self.probe = Some(Probe {
+                target: target.clone(),
+                seq: self.seq,
+                acked: false,
+            });
+            let ping = Ping {
+                seq: self.seq,
+                relay: None,
+                updates: self.piggyback(),
+            };
+            ctx.send(Message::new(PING, &ping).unwrap(), target);
+            ctx.set_timer(ACK_TIMER, self.config.ack_timeout);
+        }
+
+        ctx.set_timer(PROTOCOL_TIMER, self.config.protocol_period);
+    }
+
+    /// Returns next member to probe.
+    ///
+    /// Members are probed in round-robin order, which is shuffled on every round.
+    fn next_target(&mut self) -> Option<Address> {
+        while let Some(target) = self.probe_order.pop() {
+            if let Some(member) = self.members.get(&node_key(&target)) {
+                if member.is_active() {
+                    return Some(target);
+                }
+            }
+        }
+        self.probe_order = self.active_members();
+        self.probe_order.shuffle(&mut self.rng);
+        self.probe_order.pop()
+    }
+
+    fn probe_indirectly(&mut self, ctx: Context) {
+        let (target, seq) = match &self.probe {
+            Some(probe) if !probe.acked => (probe.target.clone(), probe.seq),
+            _ => return,
+        };
+        let mut helpers = self
+            .active_members()
+            .into_iter()
+            .filter(|member| node_key(member) != node_key(&target))
+            .collect::<Vec<_>>();
+        helpers.shuffle(&mut self.rng);
+        helpers.truncate(self.config.indirect_probes);
+        for helper in helpers {
+            let req = PingReq {
+                seq,
+                target: target.clone(),
+                updates: self.piggyback(),
+            };
+            ctx.send(Message::new(PING_REQ, &req).unwrap(), helper);
+        }
+    }
+
+    fn suspect(&mut self, target: &Address, ctx: &Context) {
+        if let Some(member) = self.members.get(&node_key(target)) {
+            if member.state == MemberState::Alive {
+                let update = Member {
+                    state: MemberState::Suspect,
+                    ..member.clone()
+                };
+                self.apply(update, ctx);
+            }
+        }
+    }
+
+    ////////////////////////////////////////////////////////////////////////////////
+    // Dissemination
+    ////////////////////////////////////////////////////////////////////////////////
+
+    /// Applies membership update if it overrides the current knowledge about member.
+    fn apply(&mut self, update: Member, ctx: &Context) {
+        if self.is_me(&update.address) {
+            // Refute suspicion about the node by increasing its incarnation.
+            if !self.left
+                && update.state != MemberState::Alive
+                && update.incarnation >= self.incarnation
+            {
+                self.incarnation = update.incarnation + 1;
+                self.enqueue(self.self_member());
+                self.publish();
+            }
+            return;
+        }
+
+        let key = node_key(&update.address);
+        if let Some(current) = self.members.get(&key) {
+            if !update.overrides(current) {
+                return;
+            }
+        }
+
+        if update.state == MemberState::Suspect {
+            self.suspected_at.insert(key.clone(), ctx.time());
+        } else {
+            self.suspected_at.remove(&key);
+        }
+        self.members.insert(key, update.clone());
+        self.enqueue(update.clone());
+        self.publish();
+        self.notify(&update, ctx);
+    }
+
+    fn merge(&mut self, updates: Vec<Member>, ctx: &Context) {
+        for update in updates {
+            self.apply(update, ctx);
+        }
+    }
+
+    /// Enqueues update to be piggybacked on the following messages.
+    fn enqueue(&mut self, update: Member) {
+        self.updates
+            .retain(|(member, _)| node_key(&member.address) != node_key(&update.address));
+
+        // Every update is retransmitted logarithmic in cluster size number of times.
+        let cluster_size = u32::try_from(self.members.len() + 1).unwrap_or(u32::MAX);
+        let retransmits = 3 * (u32::BITS - cluster_size.leading_zeros());
+        self.updates.push((update, retransmits));
+    }
+
+    /// Returns updates to be piggybacked on the message.
+    fn piggyback(&mut self) -> Vec<Member> {
+        let mut result = vec![self.self_member()];
+        for (update, retransmits) in self.updates.iter_mut().take(self.config.max_piggyback) {
+            result.push(update.clone());
+            *retransmits -= 1;
+        }
+        self.updates.retain(|(_, retransmits)| *retransmits > 0);
+        result
+    }
+
+    fn publish(&self) {
+        let mut members = vec![self.self_member()];
+        members.extend(self.members.values().cloned());
+        self.view.set_members(members);
+    }
+
+    fn notify(&self, member: &Member, ctx: &Context) {
+        for subscriber in self.view.subscribers() {
+            let address = Address::new_ref(&self.me.host, self.me.port, &subscriber);
+            ctx.send(Message::new(MEMBERSHIP_EVENT, member).unwrap(), address);
+        }
+    }
+}
+
+/// Decodes data of the membership message, malformed messages are logged and ignored.
+fn decode<T: DeserializeOwned>(msg: &Message, from: &Address) -> Option<T> {
+    match msg.data::<T>() {
+        Ok(data) => Some(data),
+        Err(err) => {
+            log::warn!(
+                "Malformed membership message '{}' from {:?} is ignored: {}",
+                msg.tip(),
+                from,
+                err
+            );
+            None
+        }
+    }
+}
+
+impl Process for MembershipProcess {
+    fn on_local_message(&mut self, msg: Message, ctx: Context) {
+        if msg.tip() == MEMBERSHIP_START {
+            self.start(ctx);
+        }
+    }
+
+    fn on_timer(&mut self, name: String, ctx: Context) {
+        if !self.running {
+            return;
+        }
+        match name.as_str() {
+            PROTOCOL_TIMER => self.protocol_round(ctx),
+            ACK_TIMER => self.probe_indirectly(ctx),
+            _ => {}
+        }
+    }
+
+    fn on_message(&mut self, msg: Message, from: Address, ctx: Context) {
+        if !self.running {
+            return;
+        }
+        match msg.tip().as_str() {
+            PING => {
+                let Some(ping) = decode::<Ping>(&msg, &from) else {
+                    return;
+                };
+                self.merge(ping.updates, &ctx);
+                let ack = Ack {
+                    seq: ping.seq,
+                    relay: ping.relay,
+                    updates: self.piggyback(),
+                };
+                ctx.send(Message::new(ACK, &ack).unwrap(), from);
+            }
+            ACK => {
+                let Some(ack) = decode::<Ack>(&msg, &from) else {
+                    return;
+                };
+                self.merge(ack.updates, &ctx);
+                if let Some(relay) = ack.relay {
+                    // Forward indirect probe result to the requester.
+                    let ack = Ack {
+                        seq: relay.seq,
+                        relay: None,
+                        updates: self.piggyback(),
+                    };
+                    ctx.send(Message::new(ACK, &ack).unwrap(), relay.requester);
+                } else if let Some(probe) = self.probe.as_mut() {
+                    if probe.seq == ack.seq {
+                        probe.acked = true;
+                        ctx.cancel_timer(ACK_TIMER);
+                    }
+                }
+            }
+            PING_REQ => {
+                let Some(req) = decode::<PingReq>(&msg, &from) else {
+                    return;
+                };
+                self.merge(req.updates, &ctx);
+                let ping = Ping {
+                    seq: req.seq,
+                    relay: Some(Relay {
+                        requester: from,
+                        seq: req.seq,
+                    }),
+                    updates: self.piggyback(),
+                };
+                ctx.send(Message::new(PING, &ping).unwrap(), req.target);
+            }
+            JOIN => {
+                let Some(join) = decode::<Gossip>(&msg, &from) else {
+                    return;
+                };
+                self.merge(join.updates, &ctx);
+                let mut updates = vec![self.self_member()];
+                updates.extend(self.members.values().cloned());
+                ctx.send(Message::new(SYNC, &Gossip { updates }).unwrap(), from);
+            }
+            SYNC => {
+                let Some(sync) = decode::<Gossip>(&msg, &from) else {
+                    return;
+                };
+                self.merge(sync.updates, &ctx);
+            }
+            _ => {}
+        }
+    }
+
+    fn on_shutdown(&mut self, ctx: Context) {
+        self.leave(&ctx);
+    }
+}
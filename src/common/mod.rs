@@ -1,8 +1,11 @@
 //! Definition of structures and functions,
 //! which are used by [`real`][`crate::RealNode`] and [`virtual`][`crate::Sim`] systems.
 
+pub mod cluster;
 pub mod context;
+pub mod failure_detector;
 pub mod fs;
+pub mod membership;
 pub mod message;
 pub mod network;
 pub mod process;
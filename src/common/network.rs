@@ -1,9 +1,18 @@
 //! Definition of network-related structures.
 
+use std::fmt;
+
 use dslab_async_mp::network::result::SendError as DSLabSendError;
 
+use super::message::Message;
+
 ////////////////////////////////////////////////////////////////////////////////
 
+/// Maximum size of the [message][Message] in bytes, including its tip.
+///
+/// Larger messages are rejected in both real mode and simulation.
+pub const MAX_MESSAGE_SIZE: usize = 4 << 20;
+
 /// Represents error type of [send][crate::Context::send] operation.
 #[derive(Debug, Clone, PartialEq)]
 pub enum SendError {
@@ -11,8 +20,47 @@ pub enum SendError {
     Timeout,
     /// Message was not sent.
     NotSent,
+    /// Message was delivered to the node, but receiver process does not exist or is stopped.
+    ProcessNotFound,
+    /// Receiver node refused connection, e.g. it is not listening.
+    ConnectionRefused,
+    /// Receiver node can not be reached, e.g. its host can not be resolved.
+    NodeUnreachable,
+    /// Size of the message in bytes exceeds [`MAX_MESSAGE_SIZE`].
+    PayloadTooLarge(usize),
+    /// Message can not be serialized or deserialized.
+    Serialization(String),
+}
+
+impl SendError {
+    /// Returns if the error will repeat on the next attempt to send the same message.
+    pub(crate) fn is_permanent(&self) -> bool {
+        matches!(self, Self::PayloadTooLarge(_) | Self::Serialization(_))
+    }
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::Timeout => write!(f, "message was not acknowledged in time"),
+            SendError::NotSent => write!(f, "message was not sent"),
+            SendError::ProcessNotFound => write!(f, "receiver process not found"),
+            SendError::ConnectionRefused => write!(f, "receiver node refused connection"),
+            SendError::NodeUnreachable => write!(f, "receiver node is unreachable"),
+            SendError::PayloadTooLarge(size) => write!(
+                f,
+                "message size {} exceeds limit of {} bytes",
+                size, MAX_MESSAGE_SIZE
+            ),
+            SendError::Serialization(reason) => {
+                write!(f, "message serialization failed: {}", reason)
+            }
+        }
+    }
 }
 
+impl std::error::Error for SendError {}
+
 impl From<DSLabSendError> for SendError {
     fn from(value: DSLabSendError) -> Self {
         match value {
@@ -26,3 +74,97 @@ impl From<DSLabSendError> for SendError {
 
 /// Represents result of [send][crate::Context::send] operation.
 pub type SendResult<T> = Result<T, SendError>;
+
+/// Checks the message does not exceed [`MAX_MESSAGE_SIZE`].
+pub(crate) fn check_message_size(msg: &Message) -> SendResult<()> {
+    let size = msg.tip().len() + msg.raw_data().len();
+    if size > MAX_MESSAGE_SIZE {
+        Err(SendError::PayloadTooLarge(size))
+    } else {
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Represents policy of retrying reliable sends.
+///
+/// Failed attempt is retried after the backoff delay, which grows exponentially
+/// with every attempt and is randomized with jitter to avoid synchronized retries
+/// of different processes.
+///
+/// Policy can be attached to the context using
+/// [`with_retry_policy`][crate::Context::with_retry_policy].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Maximum number of send attempts, including the first one.
+    pub max_attempts: u32,
+    /// Backoff delay after the first failed attempt in seconds.
+    pub initial_backoff: f64,
+    /// Maximum backoff delay in seconds.
+    pub max_backoff: f64,
+    /// Factor by which backoff delay grows after every failed attempt.
+    pub multiplier: f64,
+    /// Fraction of the backoff delay which is randomized, must be between `0` and `1`.
+    pub jitter: f64,
+    /// Optional deadline for all attempts in seconds.
+    pub deadline: Option<f64>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: 0.1,
+            max_backoff: 5.0,
+            multiplier: 2.0,
+            jitter: 0.2,
+            deadline: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Creates new retry policy with specified maximum number of attempts
+    /// and default backoff settings.
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            ..Default::default()
+        }
+    }
+
+    /// Set initial and maximum backoff delays.
+    pub fn with_backoff(mut self, initial_backoff: f64, max_backoff: f64) -> Self {
+        self.initial_backoff = initial_backoff;
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Set backoff multiplier.
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Set backoff jitter.
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Set deadline for all attempts.
+    pub fn with_deadline(mut self, deadline: f64) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Returns backoff delay after the specified failed attempt (starting from 1).
+    ///
+    /// Here `random` must be uniformly distributed between `0` and `1`.
+    pub fn backoff(&self, attempt: u32, random: f64) -> f64 {
+        let exp = i32::try_from(attempt.saturating_sub(1)).unwrap_or(i32::MAX);
+        let delay = (self.initial_backoff * self.multiplier.powi(exp)).min(self.max_backoff);
+        delay * (1.0 - self.jitter * random)
+    }
+}
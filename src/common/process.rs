@@ -42,6 +42,34 @@ pub trait Process: Send + Sync {
 
     /// Called when process receives network message from other process.
     fn on_message(&mut self, msg: Message, from: Address, ctx: Context);
+
+    /// Called when the [real node][crate::RealNode] is gracefully
+    /// [shut down][crate::ShutdownHandle] while process is running.
+    ///
+    /// Pending timers of the process are already cancelled at this moment, but
+    /// process still can send network messages, which will be delivered before node stops.
+    /// Default implementation does nothing.
+    fn on_shutdown(&mut self, _ctx: Context) {}
+}
+
+/// Allows to add processes, which type is determined in runtime,
+/// e.g. processes created from the [cluster specification][crate::ClusterSpec].
+impl Process for Box<dyn Process> {
+    fn on_local_message(&mut self, msg: Message, ctx: Context) {
+        self.as_mut().on_local_message(msg, ctx);
+    }
+
+    fn on_timer(&mut self, name: String, ctx: Context) {
+        self.as_mut().on_timer(name, ctx);
+    }
+
+    fn on_message(&mut self, msg: Message, from: Address, ctx: Context) {
+        self.as_mut().on_message(msg, from, ctx);
+    }
+
+    fn on_shutdown(&mut self, ctx: Context) {
+        self.as_mut().on_shutdown(ctx);
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -89,9 +117,19 @@ impl<P: Process + 'static> ProcessWrapper<P> {
 
 /// [Process][crate::Process] address, which is used to route
 /// [network messages][crate::Message].
-#[derive(Clone, Debug, PartialEq, PartialOrd, Hash, Eq, serde::Deserialize, serde::Serialize)]
+#[derive(
+    Clone, Debug, PartialEq, PartialOrd, Ord, Hash, Eq, serde::Deserialize, serde::Serialize,
+)]
 pub struct Address {
     /// Specifies listen host of the owner node.
+    ///
+    /// In real mode host can be one of:
+    ///
+    /// - IPv4 address, e.g. `127.0.0.1`.
+    /// - IPv6 address, optionally in brackets, which are stripped, e.g. `[::1]`.
+    /// - Hostname, e.g. `localhost`.
+    /// - Path to the Unix domain socket of the node, either absolute or with `unix:` prefix.
+    ///   In such case port is not used to locate the node.
     pub host: String,
 
     /// Specifies listen port of the owner node.
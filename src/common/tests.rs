@@ -1,6 +1,15 @@
 use serde::{Deserialize, Serialize};
 
-use super::message::Message;
+use std::collections::VecDeque;
+
+use super::{
+    cluster::{ClusterError, ClusterSpec},
+    failure_detector::phi,
+    fs::{check_file_name, FsError},
+    message::Message,
+    network::{check_message_size, RetryPolicy, SendError, MAX_MESSAGE_SIZE},
+    process::Address,
+};
 
 #[test]
 pub fn test_message_basic() {
@@ -71,3 +80,170 @@ pub fn test_serialize_works() {
 
     assert_eq!(fetched_data, data);
 }
+
+#[test]
+pub fn test_retry_policy_backoff() {
+    let policy = RetryPolicy::new(5)
+        .with_backoff(0.1, 1.0)
+        .with_multiplier(2.0)
+        .with_jitter(0.5);
+
+    assert_eq!(policy.backoff(1, 0.0), 0.1);
+    assert_eq!(policy.backoff(2, 0.0), 0.2);
+    assert_eq!(policy.backoff(3, 0.0), 0.4);
+    assert_eq!(policy.backoff(5, 0.0), 1.0);
+    assert_eq!(policy.backoff(10, 0.0), 1.0);
+
+    // Jitter decreases backoff.
+    assert_eq!(policy.backoff(1, 1.0), 0.05);
+    assert_eq!(policy.backoff(5, 0.5), 0.75);
+}
+
+#[test]
+pub fn test_phi_grows_with_elapsed_time() {
+    let intervals = VecDeque::from(vec![1.0, 1.1, 0.9, 1.0, 1.05, 0.95]);
+
+    let levels = [0.5, 1.0, 1.5, 2.0, 3.0]
+        .into_iter()
+        .map(|elapsed| phi(&intervals, 1.0, elapsed))
+        .collect::<Vec<_>>();
+    assert!(levels.windows(2).all(|pair| pair[0] < pair[1]));
+
+    // Heartbeat which is on time is not suspicious.
+    assert!(levels[1] < 1.0);
+    // Heartbeat which is late for three intervals is suspicious.
+    assert!(levels[4] > 8.0);
+
+    // Without history detector estimates distribution from the expected interval.
+    assert!(phi(&VecDeque::new(), 1.0, 1.0) < 1.0);
+    assert!(phi(&VecDeque::new(), 1.0, 3.0) > 8.0);
+}
+
+#[test]
+pub fn test_fs_error_from_io_error() {
+    let from_kind = |kind: std::io::ErrorKind| FsError::from(std::io::Error::from(kind));
+
+    assert_eq!(from_kind(std::io::ErrorKind::NotFound), FsError::NotFound);
+    assert_eq!(
+        from_kind(std::io::ErrorKind::AlreadyExists),
+        FsError::AlreadyExists
+    );
+    assert_eq!(
+        from_kind(std::io::ErrorKind::PermissionDenied),
+        FsError::PermissionDenied
+    );
+    assert_eq!(from_kind(std::io::ErrorKind::StorageFull), FsError::NoSpace);
+    assert_eq!(
+        from_kind(std::io::ErrorKind::Interrupted),
+        FsError::Io(std::io::ErrorKind::Interrupted)
+    );
+}
+
+#[test]
+pub fn test_file_name_check() {
+    for name in ["file", "file.txt", ".hidden", "a..b"] {
+        assert_eq!(check_file_name(name), Ok(()));
+    }
+    for name in ["", ".", "..", "../file", "dir/file", "dir\\file", "file\0"] {
+        assert_eq!(
+            check_file_name(name),
+            Err(FsError::InvalidName(name.to_owned()))
+        );
+    }
+}
+
+#[test]
+pub fn test_message_size_check() {
+    let data = vec![0u8; MAX_MESSAGE_SIZE - 3];
+    let message = Message::new_raw("tip", &data).unwrap();
+    assert_eq!(check_message_size(&message), Ok(()));
+
+    let message = Message::new_raw("tip_", &data).unwrap();
+    let err = check_message_size(&message).unwrap_err();
+    assert_eq!(err, SendError::PayloadTooLarge(MAX_MESSAGE_SIZE + 1));
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "message size {} exceeds limit of {} bytes",
+            MAX_MESSAGE_SIZE + 1,
+            MAX_MESSAGE_SIZE
+        )
+    );
+}
+
+#[test]
+pub fn test_cluster_spec_parse() {
+    let spec = ClusterSpec::from_json(
+        r#"{
+            "nodes": [
+                {
+                    "name": "node1",
+                    "host": "127.0.0.1",
+                    "port": 10001,
+                    "storage_mount": "/tmp/node1",
+                    "processes": [
+                        { "name": "replica", "kind": "replica", "args": { "timeout": 0.5 } },
+                        { "name": "client", "kind": "client" }
+                    ]
+                },
+                {
+                    "name": "node2",
+                    "host": "127.0.0.1",
+                    "port": 10002,
+                    "processes": [{ "name": "replica", "kind": "replica" }]
+                }
+            ]
+        }"#,
+    )
+    .unwrap();
+
+    assert_eq!(spec.nodes.len(), 2);
+    assert_eq!(spec.nodes[1].storage_mount, None);
+    assert_eq!(spec.nodes[0].processes[1].args, serde_json::Value::Null);
+    assert_eq!(
+        spec.addresses("replica"),
+        vec![
+            Address::new_ref("127.0.0.1", 10001, "replica"),
+            Address::new_ref("127.0.0.1", 10002, "replica")
+        ]
+    );
+    assert_eq!(ClusterSpec::from_json(&spec.to_json()), Ok(spec));
+}
+
+#[test]
+pub fn test_cluster_spec_validation() {
+    let check = |json: &str| ClusterSpec::from_json(json).unwrap_err();
+
+    assert!(matches!(check("{}"), ClusterError::Parse(_)));
+    assert_eq!(
+        check(r#"{ "nodes": [{ "name": "a/b", "host": "h", "port": 1 }] }"#),
+        ClusterError::InvalidName("a/b".to_owned())
+    );
+    assert_eq!(
+        check(
+            r#"{ "nodes": [
+                { "name": "a", "host": "h", "port": 1 },
+                { "name": "a", "host": "h", "port": 2 }
+            ] }"#
+        ),
+        ClusterError::DuplicateNode("a".to_owned())
+    );
+    assert_eq!(
+        check(
+            r#"{ "nodes": [
+                { "name": "a", "host": "h", "port": 1 },
+                { "name": "b", "host": "h", "port": 1 }
+            ] }"#
+        ),
+        ClusterError::DuplicateAddress("h".to_owned(), 1)
+    );
+    assert_eq!(
+        check(
+            r#"{ "nodes": [{ "name": "a", "host": "h", "port": 1, "processes": [
+                { "name": "p", "kind": "k" },
+                { "name": "p", "kind": "k" }
+            ] }] }"#
+        ),
+        ClusterError::DuplicateProcess("a".to_owned(), "p".to_owned())
+    );
+}
@@ -33,7 +33,8 @@
 //!
 //! **Network**. In simulation, network represents abstraction other the nodes' communication environment.
 //! User can control delay and drop rate of network. Also, simulation allows to make network partitions
-//! or disconnect nodes from it.
+//! or disconnect nodes from it. The same faults can be injected into the real network using
+//! [`NetworkFaults`].
 //!
 //! **File system**. File system represents abstraction over node's storage. In real mode it allows to
 //! manipulate with files in the specified dirrectory. In simulation file system is virtual and allows
@@ -51,15 +52,30 @@
 mod real;
 
 // Re-export public entities.
+pub use real::admin::{AdminClient, AdminError, NodeStats};
+pub use real::builder::{Builder as RealNodeBuilder, ConfigError as RealNodeConfigError};
 pub use real::io::IOProcessWrapper;
+pub use real::launcher::LocalCluster;
+pub use real::network::NetworkFaults;
 pub use real::node::Node as RealNode;
+pub use real::shutdown::{shutdown_signal, ShutdownHandle};
 
 ////////////////////////////////////////////////////////////////////////////////
 
 mod sim;
 
 // Re-export public entities.
-pub use sim::system::Sim;
+pub use sim::{
+    diagram::{DiagramFilter, DiagramFormat},
+    invariant::SimView,
+    linearizability::{History, LinearizabilityError, Model, Operation},
+    mc::{McEvent, McStats, McViolation, ModelChecker, SearchStrategy},
+    nemesis::{Fault, FaultPlan, Nemesis, ScheduledFault},
+    network::{LinkSettings, MessageAction, ReorderMode},
+    runner::{SeedFailure, SeedReport, SeedRunner},
+    system::Sim,
+    trace::{TraceEvent, TraceEventKind},
+};
 
 ////////////////////////////////////////////////////////////////////////////////
 
@@ -67,10 +83,13 @@ mod common;
 
 // Re-export public entities.
 pub use common::{
+    cluster::{ClusterError, ClusterNode, ClusterSpec, NodeSpec, ProcessFactory, ProcessSpec},
     context::Context,
+    failure_detector::{DetectorEvent, FailureDetector, FAILURE_DETECTOR_HEARTBEAT},
     fs::{File, FsError, FsResult},
+    membership::{Member, MemberState, MembershipConfig, MEMBERSHIP_EVENT, MEMBERSHIP_PROCESS},
     message::{Message, Tag},
-    network::{SendError, SendResult},
+    network::{RetryPolicy, SendError, SendResult, MAX_MESSAGE_SIZE},
     process::{Address, Process, ProcessGuard, ProcessWrapper},
 };
 
@@ -0,0 +1,444 @@
+//! Definition of the admin service, which allows to control [real node][crate::RealNode] remotely.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::Instant,
+};
+
+use tokio::sync::mpsc::Sender;
+use tonic::{transport::Channel, Request, Response, Status};
+
+use crate::Message;
+
+use super::{
+    messenger::GRpcMessenger,
+    process::{ProcessRegistry, ToSystemMessage},
+    transport::{Transport, TransportSettings},
+};
+
+mod proto {
+    tonic::include_proto!("admin");
+}
+
+use proto::admin_client::AdminClient as GRpcAdminClient;
+use proto::admin_server::Admin;
+pub(crate) use proto::admin_server::AdminServer;
+use proto::{
+    GetStatsRequest, GetStatsResponse, ListProcessesRequest, ListProcessesResponse, LocalMessage,
+    ReadLocalRequest, ReadLocalResponse, SendLocalRequest, SendLocalResponse, StopProcessRequest,
+    StopProcessResponse,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Maximum number of the recorded local messages of every process,
+/// which were not read by the admin client yet. The oldest messages are dropped first.
+const MAX_RECORDED_MESSAGES: usize = 4 << 10;
+
+/// Statistics of the [real node][crate::RealNode], which is returned by [`AdminClient::stats`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeStats {
+    /// Time in seconds since the node was created.
+    pub uptime: f64,
+    /// Number of the running processes.
+    pub processes: usize,
+    /// Number of the network messages sent by processes of the node.
+    pub network_messages_sent: u64,
+    /// Number of the network messages received by processes of the node.
+    pub network_messages_received: u64,
+    /// Number of the local messages sent by processes of the node.
+    pub local_messages_sent: u64,
+    /// Number of the local messages received by processes of the node.
+    pub local_messages_received: u64,
+}
+
+/// Represents error of the request to the admin service of the node.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AdminError {
+    /// Node can not be reached.
+    Unavailable(String),
+    /// Admin service is not [enabled][crate::RealNode::enable_admin] on the node.
+    Disabled,
+    /// Process with the specified name is not running on the node.
+    ProcessNotFound(String),
+    /// Request failed by other reason.
+    Failed(String),
+}
+
+impl fmt::Display for AdminError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdminError::Unavailable(reason) => write!(f, "node is unavailable: {}", reason),
+            AdminError::Disabled => write!(f, "admin service is not enabled on the node"),
+            AdminError::ProcessNotFound(process) => write!(f, "process '{}' not found", process),
+            AdminError::Failed(reason) => write!(f, "request failed: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for AdminError {}
+
+impl From<Status> for AdminError {
+    fn from(status: Status) -> Self {
+        match status.code() {
+            tonic::Code::Unimplemented => AdminError::Disabled,
+            tonic::Code::NotFound => AdminError::ProcessNotFound(status.message().to_owned()),
+            tonic::Code::Unavailable => AdminError::Unavailable(status.message().to_owned()),
+            _ => AdminError::Failed(status.message().to_owned()),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Default)]
+struct MonitorState {
+    recording: AtomicBool,
+    outputs: Mutex<HashMap<String, VecDeque<Message>>>,
+    local_senders: RwLock<HashMap<String, Sender<Message>>>,
+    network_messages_sent: AtomicU64,
+    network_messages_received: AtomicU64,
+    local_messages_sent: AtomicU64,
+    local_messages_received: AtomicU64,
+}
+
+/// Collects statistics of the node and local messages of its processes,
+/// which are requested by the admin service.
+#[derive(Clone)]
+pub(crate) struct NodeMonitor {
+    created: Instant,
+    state: Arc<MonitorState>,
+}
+
+impl Default for NodeMonitor {
+    fn default() -> Self {
+        Self {
+            created: Instant::now(),
+            state: Arc::default(),
+        }
+    }
+}
+
+impl NodeMonitor {
+    /// Starts recording local messages sent by processes.
+    pub fn enable_recording(&self) {
+        self.state.recording.store(true, Ordering::Relaxed);
+    }
+
+    /// Registers sender of the local messages to the process.
+    pub fn register_process(&self, name: &str, sender: Sender<Message>) {
+        self.state
+            .local_senders
+            .write()
+            .unwrap()
+            .insert(name.to_owned(), sender);
+    }
+
+    pub fn on_network_message_sent(&self) {
+        self.state
+            .network_messages_sent
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn on_network_message_received(&self) {
+        self.state
+            .network_messages_received
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn on_local_message_received(&self) {
+        self.state
+            .local_messages_received
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn on_local_message_sent(&self, process: &str, msg: &Message) {
+        self.state
+            .local_messages_sent
+            .fetch_add(1, Ordering::Relaxed);
+
+        if !self.state.recording.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut outputs = self.state.outputs.lock().unwrap();
+        let recorded = outputs.entry(process.to_owned()).or_default();
+        if recorded.len() == MAX_RECORDED_MESSAGES {
+            recorded.pop_front();
+        }
+        recorded.push_back(msg.clone());
+    }
+
+    fn local_sender(&self, process: &str) -> Option<Sender<Message>> {
+        self.state
+            .local_senders
+            .read()
+            .unwrap()
+            .get(process)
+            .cloned()
+    }
+
+    fn is_registered(&self, process: &str) -> bool {
+        self.state
+            .local_senders
+            .read()
+            .unwrap()
+            .contains_key(process)
+    }
+
+    fn take_outputs(&self, process: &str) -> Vec<Message> {
+        self.state
+            .outputs
+            .lock()
+            .unwrap()
+            .remove(process)
+            .map(Vec::from)
+            .unwrap_or_default()
+    }
+
+    fn stats(&self, processes: usize) -> NodeStats {
+        NodeStats {
+            uptime: self.created.elapsed().as_secs_f64(),
+            processes,
+            network_messages_sent: self.state.network_messages_sent.load(Ordering::Relaxed),
+            network_messages_received: self.state.network_messages_received.load(Ordering::Relaxed),
+            local_messages_sent: self.state.local_messages_sent.load(Ordering::Relaxed),
+            local_messages_received: self.state.local_messages_received.load(Ordering::Relaxed),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Serves requests of the [admin client][AdminClient].
+pub(crate) struct AdminService {
+    monitor: NodeMonitor,
+    processes: ProcessRegistry,
+    system: Sender<ToSystemMessage>,
+}
+
+impl AdminService {
+    pub fn new(
+        monitor: NodeMonitor,
+        processes: ProcessRegistry,
+        system: Sender<ToSystemMessage>,
+    ) -> Self {
+        Self {
+            monitor,
+            processes,
+            system,
+        }
+    }
+
+    fn is_running(&self, process: &str) -> bool {
+        self.processes.read().unwrap().contains(process)
+    }
+}
+
+#[tonic::async_trait]
+impl Admin for AdminService {
+    async fn list_processes(
+        &self,
+        _request: Request<ListProcessesRequest>,
+    ) -> Result<Response<ListProcessesResponse>, Status> {
+        let mut processes = self
+            .processes
+            .read()
+            .unwrap()
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        processes.sort();
+        Ok(Response::new(ListProcessesResponse { processes }))
+    }
+
+    async fn send_local(
+        &self,
+        request: Request<SendLocalRequest>,
+    ) -> Result<Response<SendLocalResponse>, Status> {
+        let req = request.into_inner();
+        let msg = req
+            .message
+            .ok_or(Status::invalid_argument("message is absent"))?;
+        let msg = Message::new_raw(&msg.tip, &msg.data).map_err(Status::invalid_argument)?;
+
+        let sender = self
+            .monitor
+            .local_sender(&req.process)
+            .filter(|_| self.is_running(&req.process))
+            .ok_or(Status::not_found(req.process))?;
+        sender
+            .send(msg)
+            .await
+            .map_err(|e| Status::unavailable(e.to_string()))?;
+
+        Ok(Response::new(SendLocalResponse {}))
+    }
+
+    async fn read_local(
+        &self,
+        request: Request<ReadLocalRequest>,
+    ) -> Result<Response<ReadLocalResponse>, Status> {
+        let req = request.into_inner();
+        // Messages of the stopped processes can be read too.
+        if !self.monitor.is_registered(&req.process) {
+            return Err(Status::not_found(req.process));
+        }
+
+        let messages = self
+            .monitor
+            .take_outputs(&req.process)
+            .into_iter()
+            .map(|msg| LocalMessage {
+                tip: msg.tip().clone(),
+                data: msg.raw_data().to_vec(),
+            })
+            .collect();
+
+        Ok(Response::new(ReadLocalResponse { messages }))
+    }
+
+    async fn stop_process(
+        &self,
+        request: Request<StopProcessRequest>,
+    ) -> Result<Response<StopProcessResponse>, Status> {
+        let req = request.into_inner();
+        if !self.is_running(&req.process) {
+            return Err(Status::not_found(req.process));
+        }
+
+        self.system
+            .send(ToSystemMessage::ProcessStopped(req.process))
+            .await
+            .map_err(|e| Status::unavailable(e.to_string()))?;
+
+        Ok(Response::new(StopProcessResponse {}))
+    }
+
+    async fn get_stats(
+        &self,
+        _request: Request<GetStatsRequest>,
+    ) -> Result<Response<GetStatsResponse>, Status> {
+        let stats = self.monitor.stats(self.processes.read().unwrap().len());
+        Ok(Response::new(GetStatsResponse {
+            uptime: stats.uptime,
+            processes: stats.processes as u64,
+            network_messages_sent: stats.network_messages_sent,
+            network_messages_received: stats.network_messages_received,
+            local_messages_sent: stats.local_messages_sent,
+            local_messages_received: stats.local_messages_received,
+        }))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Allows to control [real node][crate::RealNode] with [enabled][crate::RealNode::enable_admin]
+/// admin service remotely.
+///
+/// Client allows to list running processes, send local messages to them,
+/// read local messages sent by them, stop them and query statistics of the node.
+/// The same functionality is provided by the `dsbuild-ctl` command line tool.
+///
+/// # Example
+///
+/// ```no_run
+/// use dsbuild::{AdminClient, Message};
+///
+/// # async fn run() -> Result<(), dsbuild::AdminError> {
+/// let mut client = AdminClient::connect("127.0.0.1", 10024).await?;
+/// for process in client.list_processes().await? {
+///     client.send_local(&process, Message::new("ping", &()).unwrap()).await?;
+/// }
+/// println!("{:?}", client.stats().await?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct AdminClient {
+    client: GRpcAdminClient<Channel>,
+}
+
+impl AdminClient {
+    /// Connects to the admin service of the node with specified host and port.
+    ///
+    /// Host and port are the same as the ones passed to [`RealNode::new`][crate::RealNode::new].
+    pub async fn connect(host: &str, port: u16) -> Result<Self, AdminError> {
+        let channel =
+            GRpcMessenger::connect(Transport::new(host, port), &TransportSettings::default())
+                .await
+                .map_err(|e| AdminError::Unavailable(e.to_string()))?;
+        Ok(Self {
+            client: GRpcAdminClient::new(channel),
+        })
+    }
+
+    /// Returns names of the running processes in the lexicographical order.
+    pub async fn list_processes(&mut self) -> Result<Vec<String>, AdminError> {
+        let response = self.client.list_processes(ListProcessesRequest {}).await?;
+        Ok(response.into_inner().processes)
+    }
+
+    /// Sends local message to the process.
+    pub async fn send_local(&mut self, process: &str, msg: Message) -> Result<(), AdminError> {
+        let request = SendLocalRequest {
+            process: process.to_owned(),
+            message: Some(LocalMessage {
+                tip: msg.tip().clone(),
+                data: msg.raw_data().to_vec(),
+            }),
+        };
+        self.client.send_local(request).await?;
+        Ok(())
+    }
+
+    /// Returns local messages sent by the process since the previous call.
+    ///
+    /// Only messages sent after the admin service was enabled are returned.
+    /// Messages are still delivered to the [receiver][crate::IOProcessWrapper::receiver] of the process.
+    pub async fn read_local(&mut self, process: &str) -> Result<Vec<Message>, AdminError> {
+        let request = ReadLocalRequest {
+            process: process.to_owned(),
+        };
+        let response = self.client.read_local(request).await?;
+        response
+            .into_inner()
+            .messages
+            .into_iter()
+            .map(|msg| Message::new_raw(&msg.tip, &msg.data).map_err(AdminError::Failed))
+            .collect()
+    }
+
+    /// Stops the process.
+    ///
+    /// As with [`IOProcessWrapper::stop_process`][crate::IOProcessWrapper::stop_process],
+    /// it is not guaranteed the process is stopped when method returns.
+    pub async fn stop_process(&mut self, process: &str) -> Result<(), AdminError> {
+        let request = StopProcessRequest {
+            process: process.to_owned(),
+        };
+        self.client.stop_process(request).await?;
+        Ok(())
+    }
+
+    /// Returns statistics of the node.
+    pub async fn stats(&mut self) -> Result<NodeStats, AdminError> {
+        let stats = self
+            .client
+            .get_stats(GetStatsRequest {})
+            .await?
+            .into_inner();
+        Ok(NodeStats {
+            uptime: stats.uptime,
+            processes: stats.processes as usize,
+            network_messages_sent: stats.network_messages_sent,
+            network_messages_received: stats.network_messages_received,
+            local_messages_sent: stats.local_messages_sent,
+            local_messages_received: stats.local_messages_received,
+        })
+    }
+}
@@ -0,0 +1,299 @@
+//! Definition of [real node][crate::RealNode] builder.
+
+use std::{fmt, path::Path};
+
+use super::{node::Node, transport::TransportSettings};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Default capacity of the node channels.
+const DEFAULT_CHANNEL_CAPACITY: usize = 4 << 10;
+
+/// Default number of the runtime worker threads.
+const DEFAULT_WORKER_THREADS: usize = 4;
+
+/// Represents kind of the runtime, which executes node.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum RuntimeKind {
+    /// Multi-thread runtime with the specified number of worker threads.
+    MultiThread(usize),
+    /// Runtime which executes everything on the thread, which runs the node.
+    CurrentThread,
+}
+
+/// Configuration of the node.
+#[derive(Clone, Debug)]
+pub(crate) struct NodeConfig {
+    pub host: String,
+    pub port: u16,
+    pub listen_host: String,
+    pub listen_port: u16,
+    pub mount_dir: String,
+    pub network_channel_capacity: usize,
+    pub process_channel_capacity: usize,
+    pub local_channel_capacity: usize,
+    pub runtime: RuntimeKind,
+    pub transport: TransportSettings,
+}
+
+impl NodeConfig {
+    /// Returns default configuration of the node with the specified host, port and storage mount.
+    pub fn new(host: &str, port: u16, storage_mount: &str) -> Self {
+        Self {
+            host: host.to_owned(),
+            port,
+            listen_host: host.to_owned(),
+            listen_port: port,
+            mount_dir: storage_mount.to_owned(),
+            network_channel_capacity: DEFAULT_CHANNEL_CAPACITY,
+            process_channel_capacity: DEFAULT_CHANNEL_CAPACITY,
+            local_channel_capacity: DEFAULT_CHANNEL_CAPACITY,
+            runtime: RuntimeKind::MultiThread(DEFAULT_WORKER_THREADS),
+            transport: TransportSettings::default(),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Represents error of the [real node][crate::RealNode] configuration,
+/// which is returned by [`RealNodeBuilder::build`][crate::RealNodeBuilder::build].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigError {
+    /// Host of the node or its listen host is empty.
+    EmptyHost,
+    /// Capacity of the specified channel is zero.
+    ZeroChannelCapacity(&'static str),
+    /// Number of the runtime worker threads is zero.
+    ZeroWorkerThreads,
+    /// Specified timeout or interval is not positive finite number of seconds.
+    InvalidDuration(&'static str, f64),
+    /// Storage mount directory does not exist.
+    StorageMountNotFound(String),
+    /// Storage mount path exists, but it is not a directory.
+    StorageMountNotDirectory(String),
+    /// Storage mount directory can not be created.
+    StorageMountNotCreated(String, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::EmptyHost => write!(f, "host of the node is empty"),
+            ConfigError::ZeroChannelCapacity(channel) => {
+                write!(f, "capacity of the {} channel must be positive", channel)
+            }
+            ConfigError::ZeroWorkerThreads => {
+                write!(f, "number of the worker threads must be positive")
+            }
+            ConfigError::InvalidDuration(name, value) => {
+                write!(
+                    f,
+                    "{} must be positive number of seconds, got {}",
+                    name, value
+                )
+            }
+            ConfigError::StorageMountNotFound(path) => {
+                write!(f, "storage mount directory '{}' does not exist", path)
+            }
+            ConfigError::StorageMountNotDirectory(path) => {
+                write!(f, "storage mount '{}' is not a directory", path)
+            }
+            ConfigError::StorageMountNotCreated(path, reason) => {
+                write!(
+                    f,
+                    "can not create storage mount directory '{}': {}",
+                    path, reason
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Allows to configure and create the [real node][crate::RealNode].
+///
+/// Unlike [`RealNode::new`][crate::RealNode::new], which uses default settings,
+/// builder allows to specify capacities of the node channels, runtime, network timeouts,
+/// transport and storage settings. Configuration is validated on [build][Builder::build].
+///
+/// # Example
+///
+/// ```no_run
+/// use dsbuild::RealNodeBuilder;
+///
+/// let node = RealNodeBuilder::new("127.0.0.1", 10000, "/tmp")
+///     .listen_address("0.0.0.0", 10000)
+///     .worker_threads(2)
+///     .connect_timeout(1.0)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Builder {
+    config: NodeConfig,
+    create_storage_mount: bool,
+}
+
+impl Builder {
+    /// Creates builder of the node with specified host, port and directory of file system mount.
+    ///
+    /// See [`RealNode::new`][crate::RealNode::new] for the meaning of arguments.
+    pub fn new(host: &str, port: u16, storage_mount: &str) -> Self {
+        Self {
+            config: NodeConfig::new(host, port, storage_mount),
+            create_storage_mount: false,
+        }
+    }
+
+    /// Specifies host and port, on which node will listen for incoming messages.
+    ///
+    /// See [`RealNode::set_listen_address`][crate::RealNode::set_listen_address] for more details.
+    pub fn listen_address(mut self, host: &str, port: u16) -> Self {
+        self.config.listen_host = host.to_owned();
+        self.config.listen_port = port;
+        self
+    }
+
+    /// Specifies capacity of the channels between node and network.
+    pub fn network_channel_capacity(mut self, capacity: usize) -> Self {
+        self.config.network_channel_capacity = capacity;
+        self
+    }
+
+    /// Specifies capacity of the channels between node and processes,
+    /// which are used to deliver network messages and timers to processes.
+    pub fn process_channel_capacity(mut self, capacity: usize) -> Self {
+        self.config.process_channel_capacity = capacity;
+        self
+    }
+
+    /// Specifies capacity of the channels between processes and user,
+    /// which are used to pass local messages.
+    pub fn local_channel_capacity(mut self, capacity: usize) -> Self {
+        self.config.local_channel_capacity = capacity;
+        self
+    }
+
+    /// Specifies number of the runtime worker threads.
+    ///
+    /// By default node runs on multi-thread runtime with 4 worker threads.
+    pub fn worker_threads(mut self, threads: usize) -> Self {
+        self.config.runtime = RuntimeKind::MultiThread(threads);
+        self
+    }
+
+    /// Specifies node must be executed on the thread, which [runs][crate::RealNode::run] it.
+    pub fn current_thread(mut self) -> Self {
+        self.config.runtime = RuntimeKind::CurrentThread;
+        self
+    }
+
+    /// Specifies timeout in seconds for establishing connection with other node.
+    ///
+    /// By default timeout is not limited.
+    pub fn connect_timeout(mut self, timeout: f64) -> Self {
+        self.config.transport.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Specifies timeout in seconds for sending message to other node.
+    ///
+    /// Timeout limits both unreliable and reliable sends. For reliable sends,
+    /// the minimum of this timeout and timeout passed to [`Context`][crate::Context] is used.
+    /// By default timeout is not limited.
+    pub fn send_timeout(mut self, timeout: f64) -> Self {
+        self.config.transport.send_timeout = Some(timeout);
+        self
+    }
+
+    /// Specifies if `TCP_NODELAY` option must be set on TCP connections.
+    ///
+    /// Enabled by default.
+    pub fn tcp_nodelay(mut self, enabled: bool) -> Self {
+        self.config.transport.tcp_nodelay = enabled;
+        self
+    }
+
+    /// Specifies interval in seconds of TCP keepalive probes.
+    ///
+    /// By default keepalive is disabled.
+    pub fn tcp_keepalive(mut self, interval: f64) -> Self {
+        self.config.transport.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Specifies if storage mount directory must be created if it does not exist.
+    ///
+    /// Disabled by default, in which case [build][Builder::build] returns
+    /// [`ConfigError::StorageMountNotFound`] for missing directory.
+    pub fn create_storage_mount(mut self, create: bool) -> Self {
+        self.create_storage_mount = create;
+        self
+    }
+
+    /// Validates configuration and creates the node.
+    pub fn build(self) -> Result<Node, ConfigError> {
+        self.validate()?;
+        Ok(Node::from_config(self.config))
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let config = &self.config;
+
+        if config.host.is_empty() || config.listen_host.is_empty() {
+            return Err(ConfigError::EmptyHost);
+        }
+
+        for (channel, capacity) in [
+            ("network", config.network_channel_capacity),
+            ("process", config.process_channel_capacity),
+            ("local", config.local_channel_capacity),
+        ] {
+            if capacity == 0 {
+                return Err(ConfigError::ZeroChannelCapacity(channel));
+            }
+        }
+
+        if config.runtime == RuntimeKind::MultiThread(0) {
+            return Err(ConfigError::ZeroWorkerThreads);
+        }
+
+        for (name, value) in [
+            ("connect timeout", config.transport.connect_timeout),
+            ("send timeout", config.transport.send_timeout),
+            ("tcp keepalive", config.transport.tcp_keepalive),
+        ] {
+            if let Some(value) = value {
+                if !value.is_finite() || value <= 0.0 {
+                    return Err(ConfigError::InvalidDuration(name, value));
+                }
+            }
+        }
+
+        self.validate_storage_mount()
+    }
+
+    fn validate_storage_mount(&self) -> Result<(), ConfigError> {
+        let mount_dir = &self.config.mount_dir;
+        let path = Path::new(mount_dir);
+
+        if !path.exists() {
+            if !self.create_storage_mount {
+                return Err(ConfigError::StorageMountNotFound(mount_dir.clone()));
+            }
+            std::fs::create_dir_all(path).map_err(|e| {
+                ConfigError::StorageMountNotCreated(mount_dir.clone(), e.to_string())
+            })?;
+        }
+
+        if !path.is_dir() {
+            return Err(ConfigError::StorageMountNotDirectory(mount_dir.clone()));
+        }
+
+        Ok(())
+    }
+}
@@ -5,7 +5,10 @@ use std::{
     time::{Duration, SystemTime},
 };
 
-use tokio::{select, sync::oneshot};
+use tokio::{
+    select,
+    sync::{mpsc::error::TrySendError, oneshot},
+};
 
 use crate::{
     common::{
@@ -19,7 +22,7 @@ use crate::{
 use std::io::ErrorKind;
 
 use super::{
-    network::{self, NetworkRequest},
+    network::NetworkRequest,
     process::{InteractionBlock, ToSystemMessage},
 };
 
@@ -34,6 +37,9 @@ pub(crate) struct RealContext {
 impl RealContext {
     /// Send local message.
     pub fn send_local(&self, message: Message) {
+        self.output
+            .monitor
+            .on_local_message_sent(&self.address.process_name, &message);
         if let Err(info) = self.output.local.try_send(message) {
             log::warn!("can not send local message: {}", info);
         }
@@ -66,20 +72,35 @@ impl RealContext {
 
     /// Send network message.
     pub fn send(&self, msg: Message, dst: Address) {
+        self.output.monitor.on_network_message_sent();
         let msg = RoutedMessage {
             msg,
             from: self.address.clone(),
             to: dst,
             tag: None,
         };
-        let sender = self.output.network.clone();
-        tokio::spawn(async move {
-            let result = sender.send(NetworkRequest::SendMessage(msg)).await;
-
-            if let Err(info) = result {
-                log::warn!("Can not send network message: {}", info);
+        // Try to enqueue message immediately to keep order of sends,
+        // which allows network to drain them on shutdown.
+        match self
+            .output
+            .network
+            .try_send(NetworkRequest::SendMessage(msg))
+        {
+            Ok(()) => {}
+            Err(TrySendError::Full(request)) => {
+                let sender = self.output.network.clone();
+                self.output.tasks.spawn(async move {
+                    let result = sender.send(request).await;
+
+                    if let Err(info) = result {
+                        log::warn!("Can not send network message: {}", info);
+                    }
+                });
             }
-        });
+            Err(TrySendError::Closed(_)) => {
+                log::warn!("Can not send network message: network is suspended");
+            }
+        }
     }
 
     /// Send network message reliable.
@@ -90,6 +111,7 @@ impl RealContext {
     /// - Error if message was not delivered.
     /// - Ok if message was delivered
     pub async fn send_with_ack(&self, msg: Message, dst: Address, timeout: f64) -> SendResult<()> {
+        self.output.monitor.on_network_message_sent();
         let msg = RoutedMessage {
             msg,
             from: self.address.clone(),
@@ -97,7 +119,7 @@ impl RealContext {
             tag: None,
         };
 
-        network::send_message_with_ack_timeout(msg, timeout).await
+        self.send_reliable(msg, timeout).await
     }
 
     /// See [`crate::common::context::Context::send_with_tag`].
@@ -108,6 +130,7 @@ impl RealContext {
         to: Address,
         timeout: f64,
     ) -> SendResult<()> {
+        self.output.monitor.on_network_message_sent();
         let msg = RoutedMessage {
             msg,
             from: self.address.clone(),
@@ -115,7 +138,7 @@ impl RealContext {
             tag: Some(tag),
         };
 
-        network::send_message_with_ack_timeout(msg, timeout).await
+        self.send_reliable(msg, timeout).await
     }
 
     /// See [`crate::common::context::Context::send_recv_with_tag`].
@@ -126,12 +149,12 @@ impl RealContext {
         to: Address,
         timeout: f64,
     ) -> SendResult<Message> {
+        self.output.monitor.on_network_message_sent();
         let (sender, receiver) = oneshot::channel();
 
-        let output = self.output.clone();
         let from = self.address.clone();
 
-        output
+        self.output
             .message_waiters
             .lock()
             .unwrap()
@@ -139,32 +162,50 @@ impl RealContext {
             .or_default()
             .push(sender);
 
-        let timeout = Duration::from_millis((timeout * 1000.0) as u64);
-
         let send_future = async move {
-            network::send_message_with_ack(RoutedMessage {
-                msg,
-                from,
-                to,
-                tag: None,
-            })
+            self.send_reliable(
+                RoutedMessage {
+                    msg,
+                    from,
+                    to,
+                    tag: None,
+                },
+                timeout,
+            )
             .await?;
 
             receiver.await.map_err(|_| SendError::NotSent)
         };
 
+        let timeout = Duration::from_millis((timeout * 1000.0) as u64);
         select! {
             result = send_future => result,
             _ = tokio::time::sleep(timeout) => Err(SendError::Timeout)
         }
     }
 
+    /// Pass message to the network, which sends it reliably within the timeout.
+    /// Network tracks the message until the result is known,
+    /// so it is waited for on shutdown as well as unreliable sends.
+    async fn send_reliable(&self, msg: RoutedMessage, timeout: f64) -> SendResult<()> {
+        let (sender, receiver) = oneshot::channel();
+        self.output
+            .network
+            .send(NetworkRequest::SendMessageWithAck(msg, timeout, sender))
+            .await
+            .map_err(|_| {
+                log::warn!("Can not send network message: network is suspended");
+                SendError::NotSent
+            })?;
+        receiver.await.map_err(|_| SendError::NotSent)?
+    }
+
     /// Spawn asynchronous activity.
     pub fn spawn<F>(&self, future: F)
     where
         F: Future<Output = ()> + Send + 'static,
     {
-        tokio::spawn(future);
+        self.output.tasks.spawn(future);
     }
 
     /// Stop the process.
@@ -192,7 +233,7 @@ impl RealContext {
             Ok(_) => Ok(true),
             Err(e) => match e.kind() {
                 ErrorKind::NotFound => Ok(false),
-                _ => Err(FsError::Unavailable),
+                _ => Err(e.into()),
             },
         }
     }
@@ -211,7 +252,7 @@ impl RealContext {
             .create(true)
             .open(mount_dir + "/" + name)
             .await
-            .map_err(|_| FsError::Unavailable)
+            .map_err(FsError::from)
             .map(File::from_real)
     }
 
@@ -225,10 +266,7 @@ impl RealContext {
 
         async_std::fs::remove_file(mount_dir + "/" + name)
             .await
-            .map_err(|e| match e.kind() {
-                ErrorKind::NotFound => FsError::NotFound,
-                _ => FsError::Unavailable,
-            })
+            .map_err(FsError::from)
             .map(|_| ())
     }
 
@@ -241,13 +279,20 @@ impl RealContext {
             .write(true)
             .open(mount_dir + "/" + name)
             .await
-            .map_err(|error| match error.kind() {
-                ErrorKind::NotFound => FsError::NotFound,
-                _ => FsError::Unavailable,
-            })
+            .map_err(FsError::from)
             .map(File::from_real)
     }
 
+    /// Sleep for the specified number of seconds.
+    pub async fn sleep(&self, duration: f64) {
+        tokio::time::sleep(Duration::from_secs_f64(duration)).await;
+    }
+
+    /// Returns random seed.
+    pub fn random_seed(&self) -> u64 {
+        rand::random()
+    }
+
     /// Returns current time in seconds since the Unix epoch.
     pub fn time(&self) -> f64 {
         SystemTime::now()
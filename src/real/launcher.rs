@@ -0,0 +1,431 @@
+//! Definition of [`LocalCluster`], which runs nodes of the cluster as local OS processes.
+
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, BufReader, Read},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+use crate::common::cluster::{ClusterError, ClusterSpec, ProcessFactory};
+
+use super::{messenger::strip_brackets, transport::Transport};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Environment variable, which specifies path to the cluster specification of the launched node.
+const CLUSTER_SPEC_ENV: &str = "DSBUILD_CLUSTER_SPEC";
+
+/// Environment variable, which specifies name of the launched node.
+const CLUSTER_NODE_ENV: &str = "DSBUILD_CLUSTER_NODE";
+
+/// Printed by the launched node, when its socket is bound.
+const NODE_BOUND_PREFIX: &str = "dsbuild: node is bound to ";
+
+/// Printed by the launched node, when its socket can not be bound.
+const NODE_BIND_FAILED_PREFIX: &str = "dsbuild: node can not bind: ";
+
+/// Number of attempts to launch the cluster or restart the node,
+/// ports of the nodes are chosen again on every attempt.
+const LAUNCH_ATTEMPTS: usize = 5;
+
+/// Time in seconds to wait until the launched nodes bind their sockets.
+const BIND_TIMEOUT: f64 = 30.0;
+
+/// Used to make names of the cluster directories unique within the OS process.
+static CLUSTER_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+type Logs = Arc<Mutex<BTreeMap<String, Vec<String>>>>;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Allows to run nodes of the [cluster][ClusterSpec] as separate OS processes on the local machine.
+///
+/// It is a real mode counterpart of the [simulation][crate::Sim], which allows to
+/// [kill][LocalCluster::kill_node] and [restart][LocalCluster::restart_node] nodes
+/// in the end-to-end tests.
+///
+/// Every node is launched by running the specified program, which must call
+/// [`LocalCluster::run_node`] to build and run the node from the specification.
+/// Before the launch, nodes get free ports on their hosts and storage mounts in the temporary
+/// directory, which is removed when cluster is dropped. Nodes listening on Unix sockets keep
+/// their ports. Storage mounts are kept between restarts of the nodes.
+/// Port can be taken by another program before the node binds it, in this case
+/// all nodes are launched again with other ports.
+/// The resulting specification is available with [`LocalCluster::spec`].
+///
+/// Nodes are launched with [enabled][crate::RealNode::enable_admin] admin service,
+/// so [`AdminClient`][crate::AdminClient] can be used to exchange local messages with processes.
+///
+/// Output of the nodes is printed with `[<node name>]` prefix and
+/// can be inspected with [`LocalCluster::logs`].
+///
+/// # Example
+///
+/// ```no_run
+/// use dsbuild::{ClusterSpec, LocalCluster, ProcessFactory};
+///
+/// let spec = ClusterSpec::from_file("cluster.json").unwrap();
+/// if std::env::args().nth(1).as_deref() == Some("node") {
+///     let factory = ProcessFactory::new();
+///     LocalCluster::run_node(&factory).unwrap();
+/// } else {
+///     let program = std::env::current_exe().unwrap();
+///     let mut cluster = LocalCluster::start(&spec, &program, &["node"]).unwrap();
+///     assert!(cluster.wait_until_ready(5.0));
+///     cluster.kill_node("node1").unwrap();
+///     cluster.restart_node("node1").unwrap();
+/// }
+/// ```
+pub struct LocalCluster {
+    spec: ClusterSpec,
+    dir: PathBuf,
+    spec_path: PathBuf,
+    program: PathBuf,
+    args: Vec<String>,
+    children: BTreeMap<String, Child>,
+    outputs: BTreeMap<String, JoinHandle<()>>,
+    logs: Logs,
+}
+
+impl LocalCluster {
+    /// Launches all nodes of the cluster by running `program` with `args`.
+    ///
+    /// Returns when all nodes have bound their sockets.
+    pub fn start(spec: &ClusterSpec, program: &Path, args: &[&str]) -> Result<Self, ClusterError> {
+        spec.validate()?;
+
+        let dir = std::env::temp_dir().join(format!(
+            "dsbuild-cluster-{}-{}",
+            std::process::id(),
+            CLUSTER_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let mut spec = spec.clone();
+        for node in spec.nodes.iter_mut() {
+            let mount = dir.join(&node.name);
+            std::fs::create_dir_all(&mount).map_err(|e| ClusterError::Io(e.to_string()))?;
+            node.storage_mount = Some(mount.to_string_lossy().into_owned());
+        }
+
+        let mut cluster = Self {
+            spec,
+            spec_path: dir.join("cluster.json"),
+            dir,
+            program: program.to_owned(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            children: BTreeMap::new(),
+            outputs: BTreeMap::new(),
+            logs: Logs::default(),
+        };
+        let mut attempt = 1;
+        loop {
+            match cluster.launch() {
+                Err(ClusterError::Bind(..)) if attempt < LAUNCH_ATTEMPTS => attempt += 1,
+                result => return result.map(|_| cluster),
+            }
+        }
+    }
+
+    /// Launches all nodes with new free ports and waits until they bind their sockets.
+    ///
+    /// If some node can not bind its socket, all nodes are killed.
+    fn launch(&mut self) -> Result<(), ClusterError> {
+        let names = self
+            .spec
+            .nodes
+            .iter()
+            .map(|node| node.name.clone())
+            .collect::<Vec<_>>();
+        for name in names.iter() {
+            self.choose_port(name)?;
+        }
+        self.write_spec()?;
+
+        // Output of the previous attempt is dropped.
+        self.logs = Logs::default();
+        for name in names.iter() {
+            self.spawn_node(name)?;
+        }
+        self.wait_until_bound(&names, 0)
+    }
+
+    /// Waits until launched nodes bind their sockets,
+    /// looking for the report of the node in its output lines starting from `skip_lines`.
+    ///
+    /// If some node can not bind its socket, all of these nodes are killed.
+    fn wait_until_bound(
+        &mut self,
+        names: &[String],
+        skip_lines: usize,
+    ) -> Result<(), ClusterError> {
+        let deadline = Instant::now() + Duration::from_secs_f64(BIND_TIMEOUT);
+        for name in names.iter() {
+            loop {
+                let exited = !self.is_running(name);
+                if exited {
+                    // Wait until output of the node is read.
+                    if let Some(output) = self.outputs.remove(name) {
+                        let _ = output.join();
+                    }
+                }
+                let mut logs = self.logs(name);
+                logs.drain(..skip_lines.min(logs.len()));
+                // Line can be preceded by output of the program, which is not terminated.
+                if logs.iter().any(|line| line.contains(NODE_BOUND_PREFIX)) {
+                    break;
+                }
+                if let Some(reason) = logs
+                    .iter()
+                    .find_map(|line| line.split_once(NODE_BIND_FAILED_PREFIX))
+                    .map(|(_, reason)| reason.to_owned())
+                {
+                    for name in names.iter() {
+                        self.kill_node(name)?;
+                    }
+                    return Err(ClusterError::Bind(name.clone(), reason));
+                }
+                if exited {
+                    return Err(ClusterError::Spawn(
+                        name.clone(),
+                        "node exited before binding its socket".to_owned(),
+                    ));
+                }
+                if Instant::now() >= deadline {
+                    return Err(ClusterError::Spawn(
+                        name.clone(),
+                        "node did not bind its socket in time".to_owned(),
+                    ));
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds node from the specification passed by [`LocalCluster`] and runs it.
+    ///
+    /// Must be called by the program, which is launched by the cluster.
+    /// Node runs until all its processes are stopped or it receives `SIGINT` or `SIGTERM` signal.
+    /// Node reports to the cluster, whether its socket is bound, by printing the line to stdout.
+    pub fn run_node(factory: &ProcessFactory) -> Result<(), ClusterError> {
+        let spec_path = std::env::var(CLUSTER_SPEC_ENV)
+            .map_err(|_| ClusterError::Io(format!("{} is not set", CLUSTER_SPEC_ENV)))?;
+        let name = std::env::var(CLUSTER_NODE_ENV)
+            .map_err(|_| ClusterError::Io(format!("{} is not set", CLUSTER_NODE_ENV)))?;
+
+        let spec = ClusterSpec::from_file(&spec_path)?;
+        let mut cluster_node = spec.build_real_node(&name, factory)?;
+        cluster_node.node.enable_admin();
+        cluster_node.node.shutdown_on_signal();
+
+        // Local messages are read with admin client.
+        for (_, mut io) in cluster_node.processes {
+            cluster_node
+                .node
+                .spawn(async move { while io.receiver.recv().await.is_some() {} });
+        }
+
+        let node = spec.node(&name).unwrap();
+        match cluster_node.node.try_bind() {
+            Ok(listener) => {
+                println!("{}{}:{}", NODE_BOUND_PREFIX, node.host, node.port);
+                cluster_node.node.run_blocking(Some(listener));
+                Ok(())
+            }
+            Err(reason) => {
+                println!("{}{}", NODE_BIND_FAILED_PREFIX, reason);
+                Err(ClusterError::Bind(name, reason))
+            }
+        }
+    }
+
+    /// Returns specification of the launched cluster with the assigned ports and storage mounts.
+    pub fn spec(&self) -> &ClusterSpec {
+        &self.spec
+    }
+
+    /// Checks if the node is running.
+    pub fn is_running(&mut self, name: &str) -> bool {
+        self.children
+            .get_mut(name)
+            .is_some_and(|child| matches!(child.try_wait(), Ok(None)))
+    }
+
+    /// Waits until all running nodes accept connections.
+    ///
+    /// Returns `false` if nodes are not ready within `timeout` seconds.
+    pub fn wait_until_ready(&mut self, timeout: f64) -> bool {
+        let deadline = Instant::now() + Duration::from_secs_f64(timeout);
+        let names = self.children.keys().cloned().collect::<Vec<_>>();
+        for name in names {
+            let node = self.spec.node(&name).unwrap();
+            let transport = Transport::new(&node.host, node.port);
+            while !accepts_connections(&transport) {
+                if Instant::now() >= deadline || !self.is_running(&name) {
+                    return false;
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+        true
+    }
+
+    /// Kills the node without graceful shutdown.
+    ///
+    /// Storage of the node is kept, so node can be [restarted][LocalCluster::restart_node].
+    pub fn kill_node(&mut self, name: &str) -> Result<(), ClusterError> {
+        let mut child = self
+            .children
+            .remove(name)
+            .ok_or(ClusterError::UnknownNode(name.to_owned()))?;
+        let _ = child.kill();
+        let _ = child.wait();
+        Ok(())
+    }
+
+    /// Launches the node again with the same storage mount and waits until it binds its socket.
+    ///
+    /// If node is running, it is [killed][LocalCluster::kill_node] first.
+    /// Node keeps its port, unless the port is taken by another program while node is down.
+    /// In this case node is launched again with another port, which is available
+    /// with [`LocalCluster::spec`], while other running nodes keep using the previous one.
+    pub fn restart_node(&mut self, name: &str) -> Result<(), ClusterError> {
+        if self.spec.node(name).is_none() {
+            return Err(ClusterError::UnknownNode(name.to_owned()));
+        }
+        if self.children.contains_key(name) {
+            self.kill_node(name)?;
+        }
+        let names = [name.to_owned()];
+        let mut attempt = 1;
+        loop {
+            let skip_lines = self.logs(name).len();
+            self.spawn_node(name)?;
+            match self.wait_until_bound(&names, skip_lines) {
+                Err(ClusterError::Bind(..)) if attempt < LAUNCH_ATTEMPTS => {
+                    attempt += 1;
+                    self.choose_port(name)?;
+                    self.write_spec()?;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Returns lines printed by the node to the standard output and error since the cluster start.
+    pub fn logs(&self, name: &str) -> Vec<String> {
+        self.logs
+            .lock()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Assigns free port on the host of the node, if node does not listen on Unix socket.
+    fn choose_port(&mut self, name: &str) -> Result<(), ClusterError> {
+        let node = self
+            .spec
+            .nodes
+            .iter_mut()
+            .find(|node| node.name == name)
+            .unwrap();
+        if let Transport::Tcp { host, .. } = Transport::new(&node.host, node.port) {
+            node.port = free_port(&host).map_err(|e| ClusterError::Spawn(name.to_owned(), e))?;
+        }
+        Ok(())
+    }
+
+    fn write_spec(&self) -> Result<(), ClusterError> {
+        std::fs::write(&self.spec_path, self.spec.to_json())
+            .map_err(|e| ClusterError::Io(e.to_string()))
+    }
+
+    fn spawn_node(&mut self, name: &str) -> Result<(), ClusterError> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .env(CLUSTER_SPEC_ENV, &self.spec_path)
+            .env(CLUSTER_NODE_ENV, name)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| ClusterError::Spawn(name.to_owned(), e.to_string()))?;
+
+        let output = forward_output(name, child.stdout.take().unwrap(), self.logs.clone(), false);
+        forward_output(name, child.stderr.take().unwrap(), self.logs.clone(), true);
+
+        self.children.insert(name.to_owned(), child);
+        self.outputs.insert(name.to_owned(), output);
+        Ok(())
+    }
+}
+
+impl Drop for LocalCluster {
+    fn drop(&mut self) {
+        for child in self.children.values_mut() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Returns port, which is currently not used on the specified host of the local machine.
+fn free_port(host: &str) -> Result<u16, String> {
+    TcpListener::bind((strip_brackets(host), 0))
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+        .map_err(|e| format!("can not find free port: {}", e))
+}
+
+/// Checks if the node accepts connections on its address.
+fn accepts_connections(transport: &Transport) -> bool {
+    match transport {
+        Transport::Tcp { host, port } => TcpStream::connect((host.as_str(), *port)).is_ok(),
+        #[cfg(unix)]
+        Transport::Unix { path } => UnixStream::connect(path).is_ok(),
+        #[cfg(not(unix))]
+        Transport::Unix { .. } => false,
+    }
+}
+
+/// Prints output of the node with prefix and saves it to the logs.
+///
+/// Returns handle of the thread, which completes when output is closed.
+fn forward_output(
+    name: &str,
+    output: impl Read + Send + 'static,
+    logs: Logs,
+    stderr: bool,
+) -> JoinHandle<()> {
+    let name = name.to_owned();
+    std::thread::spawn(move || {
+        for line in BufReader::new(output).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if stderr {
+                eprintln!("[{}] {}", name, line);
+            } else {
+                println!("[{}] {}", name, line);
+            }
+            logs.lock()
+                .unwrap()
+                .entry(name.clone())
+                .or_default()
+                .push(line);
+        }
+    })
+}
@@ -1,13 +1,21 @@
 //! Definition of asynchronous messenger [`GRpcMessenger`] structure.
 
-use std::net::{IpAddr, SocketAddr};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::sync::mpsc::Sender;
-use tonic::transport::server::TcpIncoming;
+use tokio_stream::Stream;
+use tonic::transport::{
+    server::{Connected, TcpIncoming},
+    Channel, Endpoint,
+};
 
 use crate::common::{
     message::{Message, RoutedMessage, Tag},
+    network::{SendError, MAX_MESSAGE_SIZE},
     process::Address,
 };
 
@@ -21,6 +29,13 @@ use message_passing::{SendMessageRequest, SendMessageResponse};
 
 use tonic::{transport::Server, Request, Response, Status};
 
+use super::{
+    admin::{AdminServer, AdminService},
+    network::{ListenServices, NetworkFaults},
+    process::ProcessRegistry,
+    transport::{Transport, TransportSettings},
+};
+
 pub struct ProcessSendRequest {
     /// Address of process, which sends request.
     pub sender_address: Address,
@@ -40,9 +55,10 @@ pub struct ProcessSendResponse {
     pub status: String,
 }
 
-#[derive(Debug)]
 pub struct MessagePassingService {
     pub send_to: Sender<RoutedMessage>,
+    pub faults: NetworkFaults,
+    pub processes: ProcessRegistry,
 }
 
 #[tonic::async_trait]
@@ -63,7 +79,26 @@ impl MessagePassing for MessagePassingService {
         );
 
         let message = Message::new_raw(&req.message_tip, &req.message_data)
-            .map_err(|e| Status::new(tonic::Code::Internal, e))?;
+            .map_err(|e| Status::new(tonic::Code::InvalidArgument, e))?;
+
+        // Message is lost by the injected network faults.
+        if self.faults.drops_incoming(&sender_address) {
+            return Ok(Response::new(SendMessageResponse {
+                status: "dropped".to_owned(),
+            }));
+        }
+
+        // Report delivery failure, so reliable sends do not succeed.
+        if !self
+            .processes
+            .read()
+            .unwrap()
+            .contains(&receiver_address.process_name)
+        {
+            return Ok(Response::new(SendMessageResponse {
+                status: "process_not_found".to_owned(),
+            }));
+        }
 
         let msg = RoutedMessage {
             msg: message,
@@ -85,11 +120,22 @@ impl MessagePassing for MessagePassingService {
     }
 }
 
+/// Socket bound to the listen address of the node.
+pub enum Listener {
+    Tcp(std::net::TcpListener),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixListener),
+}
+
 #[derive(Default)]
 pub struct GRpcMessenger {}
 
 impl GRpcMessenger {
-    pub async fn send(request: ProcessSendRequest) -> Result<ProcessSendResponse, String> {
+    pub async fn send(
+        request: ProcessSendRequest,
+        settings: &TransportSettings,
+    ) -> Result<ProcessSendResponse, SendError> {
+        let message_size = request.message.tip().len() + request.message.raw_data().len();
         let grpc_request = tonic::Request::new(SendMessageRequest {
             sender_host: request.sender_address.host.clone(),
             sender_port: u32::from(request.sender_address.port),
@@ -102,18 +148,19 @@ impl GRpcMessenger {
             tag: request.tag,
         });
 
-        let receiver_host = request.receiver_address.host.clone();
-        let receiver_port = request.receiver_address.port;
+        let transport = Transport::new(
+            &request.receiver_address.host,
+            request.receiver_address.port,
+        );
 
-        let mut client =
-            MessagePassingClient::connect(format!("http://{}:{}", receiver_host, receiver_port))
-                .await
-                .map_err(|e| "can not connect to the receiver: ".to_owned() + &e.to_string())?;
+        let channel = Self::connect(transport, settings).await?;
 
-        let response = client
-            .send_message(grpc_request)
-            .await
-            .map_err(|e| "can not send message to the receiver: ".to_owned() + &e.to_string())?;
+        let mut client = MessagePassingClient::new(channel);
+
+        let response = client.send_message(grpc_request).await.map_err(|status| {
+            log::debug!("Can not send message to the receiver: {}", status);
+            Self::status_error(status, message_size)
+        })?;
 
         let process_response = ProcessSendResponse {
             status: response.into_inner().status,
@@ -122,34 +169,274 @@ impl GRpcMessenger {
         Ok(process_response)
     }
 
+    /// Binds socket to the listen address of the node, hostname is resolved synchronously.
+    ///
+    /// Incoming connections are queued from this moment, so messages sent to the node
+    /// are not lost while it starts to [listen][GRpcMessenger::listen].
+    pub fn bind(host: &str, port: u16) -> Result<Listener, String> {
+        match Transport::new(host, port) {
+            Transport::Tcp { host, port } => {
+                let host = strip_brackets(&host);
+                let sock_addrs = (host, port)
+                    .to_socket_addrs()
+                    .map_err(|e| format!("Can not resolve host '{}': {}", host, e))?;
+                Self::bind_tcp(host, sock_addrs)
+            }
+            Transport::Unix { path } => Self::bind_unix(path),
+        }
+    }
+
+    /// Binds socket to the listen address of the node, hostname is resolved
+    /// without blocking of the runtime. See [bind][GRpcMessenger::bind].
+    pub async fn bind_async(host: &str, port: u16) -> Result<Listener, String> {
+        match Transport::new(host, port) {
+            Transport::Tcp { host, port } => {
+                let sock_addrs = Self::resolve(&host, port).await?;
+                Self::bind_tcp(strip_brackets(&host), sock_addrs)
+            }
+            Transport::Unix { path } => Self::bind_unix(path),
+        }
+    }
+
+    /// Checks if host of the listen address is a hostname, which must be resolved to bind socket.
+    pub fn requires_lookup(host: &str, port: u16) -> bool {
+        match Transport::new(host, port) {
+            Transport::Tcp { host, .. } => IpAddr::from_str(strip_brackets(&host)).is_err(),
+            Transport::Unix { .. } => false,
+        }
+    }
+
     pub async fn listen(
-        host: String,
-        port: u16,
+        listener: Listener,
         send_to: Sender<RoutedMessage>,
+        faults: NetworkFaults,
+        services: ListenServices,
+        settings: TransportSettings,
     ) -> Result<(), String> {
-        // Create ip address.
-        let ip_addr =
-            IpAddr::from_str(&host).map_err(|e| "Invalid host: ".to_owned() + &e.to_string())?;
+        let service = MessagePassingService {
+            send_to,
+            faults,
+            processes: services.processes,
+        };
+        let admin = services.admin;
+        match listener {
+            Listener::Tcp(listener) => {
+                let listener = listener
+                    .set_nonblocking(true)
+                    .and_then(|_| tokio::net::TcpListener::from_std(listener))
+                    .map_err(|e| "Can not register Tcp listener: ".to_owned() + &e.to_string())?;
+                let incoming_stream = TcpIncoming::from_listener(
+                    listener,
+                    settings.tcp_nodelay,
+                    settings.tcp_keepalive.map(Duration::from_secs_f64),
+                )
+                .map_err(|e| "Can not create Tcp incoming stream: ".to_owned() + &e.to_string())?;
+                Self::serve(incoming_stream, service, admin).await
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener) => {
+                let listener = listener
+                    .set_nonblocking(true)
+                    .and_then(|_| tokio::net::UnixListener::from_std(listener))
+                    .map_err(|e| "Can not register Unix listener: ".to_owned() + &e.to_string())?;
+                let incoming_stream = tokio_stream::wrappers::UnixListenerStream::new(listener);
+                Self::serve(incoming_stream, service, admin).await
+            }
+        }
+    }
 
-        // Create socket address.
-        let sock_addr = SocketAddr::new(ip_addr, port);
+    pub async fn connect(
+        transport: Transport,
+        settings: &TransportSettings,
+    ) -> Result<Channel, SendError> {
+        match transport {
+            Transport::Tcp { host, port } => {
+                let sock_addrs = Self::resolve(&host, port).await.map_err(|e| {
+                    log::debug!("{}", e);
+                    SendError::NodeUnreachable
+                })?;
+                // Addresses are tried in order, the error of the last one is returned.
+                let mut error = SendError::NodeUnreachable;
+                for sock_addr in sock_addrs {
+                    match Self::connect_tcp(sock_addr, settings).await {
+                        Ok(channel) => return Ok(channel),
+                        Err(e) => error = e,
+                    }
+                }
+                Err(error)
+            }
+            Transport::Unix { path } => Self::connect_unix(path, settings).await,
+        }
+    }
 
-        // Create incoming stream.
-        let incoming_stream = TcpIncoming::new(sock_addr, true, None).map_err(|e| {
-            "Can not create Tcp incoming stream: ".to_owned() + e.to_string().as_str()
-        })?;
+    async fn connect_tcp(
+        sock_addr: SocketAddr,
+        settings: &TransportSettings,
+    ) -> Result<Channel, SendError> {
+        // Socket address display wraps IPv6 addresses in brackets as uri requires.
+        let endpoint = Endpoint::from_shared(format!("http://{}", sock_addr))
+            .map_err(|_| SendError::NodeUnreachable)?
+            .tcp_nodelay(settings.tcp_nodelay)
+            .tcp_keepalive(settings.tcp_keepalive.map(Duration::from_secs_f64));
+        Self::configure(endpoint, settings)
+            .connect()
+            .await
+            .map_err(Self::connect_error)
+    }
+
+    /// Applies timeouts from the settings to the endpoint.
+    fn configure(mut endpoint: Endpoint, settings: &TransportSettings) -> Endpoint {
+        if let Some(timeout) = settings.connect_timeout {
+            endpoint = endpoint.connect_timeout(Duration::from_secs_f64(timeout));
+        }
+        if let Some(timeout) = settings.send_timeout {
+            endpoint = endpoint.timeout(Duration::from_secs_f64(timeout));
+        }
+        endpoint
+    }
 
+    #[cfg(unix)]
+    async fn connect_unix(
+        path: PathBuf,
+        settings: &TransportSettings,
+    ) -> Result<Channel, SendError> {
+        // Uri is ignored by connector, but it must be valid.
+        Self::configure(Endpoint::from_static("http://[::]:0"), settings)
+            .connect_with_connector(tower::service_fn(move |_: tonic::transport::Uri| {
+                tokio::net::UnixStream::connect(path.clone())
+            }))
+            .await
+            .map_err(Self::connect_error)
+    }
+
+    #[cfg(not(unix))]
+    async fn connect_unix(
+        _path: PathBuf,
+        _settings: &TransportSettings,
+    ) -> Result<Channel, SendError> {
+        log::debug!("Unix domain sockets are not supported on this platform");
+        Err(SendError::NodeUnreachable)
+    }
+
+    /// Maps error of connection establishment into the [send error][SendError].
+    fn connect_error(error: tonic::transport::Error) -> SendError {
+        log::debug!("Can not connect to the receiver: {:?}", error);
+
+        // Find the underlying io error to distinguish refused connections.
+        let mut source = std::error::Error::source(&error);
+        while let Some(err) = source {
+            if let Some(io_error) = err.downcast_ref::<std::io::Error>() {
+                return match io_error.kind() {
+                    // Socket file of the Unix domain socket does not exist if node is not listening.
+                    std::io::ErrorKind::ConnectionRefused | std::io::ErrorKind::NotFound => {
+                        SendError::ConnectionRefused
+                    }
+                    std::io::ErrorKind::TimedOut => SendError::Timeout,
+                    _ => SendError::NodeUnreachable,
+                };
+            }
+            source = err.source();
+        }
+        SendError::NodeUnreachable
+    }
+
+    /// Maps status of the failed request into the [send error][SendError].
+    fn status_error(status: Status, message_size: usize) -> SendError {
+        match status.code() {
+            tonic::Code::ResourceExhausted | tonic::Code::OutOfRange => {
+                SendError::PayloadTooLarge(message_size)
+            }
+            tonic::Code::InvalidArgument => SendError::Serialization(status.message().to_owned()),
+            tonic::Code::DeadlineExceeded | tonic::Code::Cancelled => SendError::Timeout,
+            tonic::Code::Unavailable => SendError::NodeUnreachable,
+            _ => SendError::NotSent,
+        }
+    }
+
+    /// Resolves host, which can be IPv4 or IPv6 address or hostname, into the socket addresses.
+    ///
+    /// Hostname can be resolved into several addresses, which are returned in order of resolution.
+    async fn resolve(host: &str, port: u16) -> Result<Vec<SocketAddr>, String> {
+        let host = strip_brackets(host);
+        if let Ok(ip_addr) = IpAddr::from_str(host) {
+            return Ok(vec![SocketAddr::new(ip_addr, port)]);
+        }
+
+        let sock_addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|e| format!("Can not resolve host '{}': {}", host, e))?
+            .collect();
+        if sock_addrs.is_empty() {
+            return Err(format!(
+                "Can not resolve host '{}': no addresses found",
+                host
+            ));
+        }
+        Ok(sock_addrs)
+    }
+
+    fn bind_tcp(
+        host: &str,
+        sock_addrs: impl IntoIterator<Item = SocketAddr>,
+    ) -> Result<Listener, String> {
+        // Bind the first resolved address, which can be bound.
+        let mut error = format!("Can not resolve host '{}': no addresses found", host);
+        for sock_addr in sock_addrs {
+            match std::net::TcpListener::bind(sock_addr) {
+                Ok(listener) => return Ok(Listener::Tcp(listener)),
+                Err(e) => error = format!("Can not bind {}: {}", sock_addr, e),
+            }
+        }
+        Err(error)
+    }
+
+    #[cfg(unix)]
+    fn bind_unix(path: PathBuf) -> Result<Listener, String> {
+        // Remove socket file left by the previous run.
+        if path.exists() {
+            std::fs::remove_file(&path)
+                .map_err(|e| "Can not remove stale socket file: ".to_owned() + &e.to_string())?;
+        }
+
+        std::os::unix::net::UnixListener::bind(&path)
+            .map(Listener::Unix)
+            .map_err(|e| "Can not bind Unix socket: ".to_owned() + &e.to_string())
+    }
+
+    #[cfg(not(unix))]
+    fn bind_unix(_path: PathBuf) -> Result<Listener, String> {
+        Err("Unix domain sockets are not supported on this platform".to_owned())
+    }
+
+    async fn serve<I, IO, IE>(
+        incoming_stream: I,
+        service: MessagePassingService,
+        admin: Option<AdminService>,
+    ) -> Result<(), String>
+    where
+        I: Stream<Item = Result<IO, IE>>,
+        IO: AsyncRead + AsyncWrite + Connected + Unpin + Send + 'static,
+        IO::ConnectInfo: Clone + Send + Sync + 'static,
+        IE: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
         // Create rpc server.
-        let service = MessagePassingService { send_to };
-        let server = MessagePassingServer::new(service);
+        // Leave room for the addresses of sender and receiver.
+        let server = MessagePassingServer::new(service)
+            .max_decoding_message_size(MAX_MESSAGE_SIZE + (64 << 10));
 
-        // Start the server.
+        // Start the server, admin service is served on the same address if it is enabled.
         Server::builder()
             .add_service(server)
+            .add_optional_service(admin.map(AdminServer::new))
             .serve_with_incoming(incoming_stream)
             .await
-            .map_err(|e| "GRpc messenger server error: ".to_owned() + e.to_string().as_str())?;
-
-        Ok(())
+            .map_err(|e| "GRpc messenger server error: ".to_owned() + e.to_string().as_str())
     }
 }
+
+/// Allows IPv6 addresses in brackets, e.g. `[::1]`.
+pub(crate) fn strip_brackets(host: &str) -> &str {
+    host.strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host)
+}
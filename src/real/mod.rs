@@ -1,12 +1,18 @@
+pub mod admin;
+pub mod builder;
 pub mod context;
 pub mod io;
+pub mod launcher;
+pub mod network;
 pub mod node;
+pub mod shutdown;
 
 mod messenger;
 mod msg_waiters;
-mod network;
 mod process;
+mod tasks;
 mod timer;
+mod transport;
 
 #[cfg(test)]
 mod tests;
@@ -1,92 +1,359 @@
 //! Definition of asynchronous network manager.
 
+use std::{
+    collections::HashSet,
+    sync::{Arc, RwLock},
+};
+
 use log::{info, warn};
-use tokio::sync::mpsc::{Receiver, Sender};
+use rand::Rng;
+use tokio::{
+    sync::{
+        mpsc::{Receiver, Sender},
+        oneshot,
+    },
+    task::JoinSet,
+};
 
 use crate::common::{
     message::RoutedMessage,
     network::{SendError, SendResult},
+    process::Address,
+};
+
+use super::{
+    admin::AdminService,
+    messenger::{GRpcMessenger, Listener, ProcessSendRequest},
+    process::ProcessRegistry,
+    transport::TransportSettings,
 };
 
-use super::messenger::{GRpcMessenger, ProcessSendRequest};
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Default)]
+struct FaultConfig {
+    drop_rate: f64,
+    min_delay: f64,
+    max_delay: f64,
+    blocked: HashSet<Address>,
+    disconnected: bool,
+}
+
+/// Represents decision of the fault layer about the message.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum FaultDecision {
+    /// Message must be dropped.
+    Drop,
+    /// Message must be delivered after the specified delay in seconds.
+    Deliver(f64),
+}
+
+/// Handle which allows to inject network faults into the [real node][crate::RealNode].
+///
+/// Fault model matches the one of the [simulation][crate::Sim]: user can
+/// [drop][NetworkFaults::set_drop_rate] and [delay][NetworkFaults::set_delays] messages,
+/// [block][NetworkFaults::block_node] communication with other nodes and
+/// [disconnect][NetworkFaults::disconnect] node from the network.
+///
+/// Handle can be obtained by [`RealNode::network_faults`][crate::RealNode::network_faults]
+/// and settings can be changed at any time, including while the node is running.
+///
+/// Note that reliable sends are not affected by drop rate, but messages to and from blocked
+/// nodes are lost, so reliable sends will end with [`Timeout`][crate::SendError::Timeout].
+#[derive(Clone, Default)]
+pub struct NetworkFaults {
+    config: Arc<RwLock<FaultConfig>>,
+}
+
+impl NetworkFaults {
+    /// Set the fixed network delay.
+    pub fn set_delay(&self, delay: f64) {
+        self.set_delays(delay, delay);
+    }
+
+    /// Set the minimum and maximum network delays.
+    ///
+    /// Delay of every sent message is uniformly distributed between them.
+    pub fn set_delays(&self, min_delay: f64, max_delay: f64) {
+        assert!(
+            0.0 <= min_delay && min_delay <= max_delay,
+            "Incorrect network delays: {} and {}",
+            min_delay,
+            max_delay
+        );
+        let mut config = self.config.write().unwrap();
+        config.min_delay = min_delay;
+        config.max_delay = max_delay;
+    }
+
+    /// Set drop rate of the network.
+    pub fn set_drop_rate(&self, drop_rate: f64) {
+        assert!(
+            (0.0..=1.0).contains(&drop_rate),
+            "Incorrect drop rate: {}",
+            drop_rate
+        );
+        self.config.write().unwrap().drop_rate = drop_rate;
+    }
+
+    /// Block communication with the node with specified host and port.
+    ///
+    /// Messages sent to the node and received from it will be dropped.
+    pub fn block_node(&self, host: &str, port: u16) {
+        self.config
+            .write()
+            .unwrap()
+            .blocked
+            .insert(Address::new_node_address(host.to_owned(), port));
+    }
+
+    /// Unblock previously [blocked][NetworkFaults::block_node] node.
+    pub fn unblock_node(&self, host: &str, port: u16) {
+        self.config
+            .write()
+            .unwrap()
+            .blocked
+            .remove(&Address::new_node_address(host.to_owned(), port));
+    }
+
+    /// Disconnect node from the network.
+    ///
+    /// All sent and received messages will be dropped.
+    pub fn disconnect(&self) {
+        self.config.write().unwrap().disconnected = true;
+    }
+
+    /// Connect node to the network.
+    pub fn connect(&self) {
+        self.config.write().unwrap().disconnected = false;
+    }
+
+    /// Reset all fault settings, so network behaves normally.
+    pub fn reset(&self) {
+        *self.config.write().unwrap() = FaultConfig::default();
+    }
+
+    /// Returns decision about the message sent to the specified address.
+    pub(crate) fn outgoing(&self, to: &Address, reliable: bool) -> FaultDecision {
+        let config = self.config.read().unwrap();
+        if config.disconnected || config.blocked.contains(&node_address(to)) {
+            return FaultDecision::Drop;
+        }
+        let mut rng = rand::thread_rng();
+        if !reliable && config.drop_rate > 0.0 && rng.gen::<f64>() < config.drop_rate {
+            return FaultDecision::Drop;
+        }
+        let delay = if config.min_delay < config.max_delay {
+            rng.gen_range(config.min_delay..=config.max_delay)
+        } else {
+            config.min_delay
+        };
+        FaultDecision::Deliver(delay)
+    }
+
+    /// Checks if the message received from the specified address must be dropped.
+    pub(crate) fn drops_incoming(&self, from: &Address) -> bool {
+        let config = self.config.read().unwrap();
+        config.disconnected || config.blocked.contains(&node_address(from))
+    }
+}
+
+fn node_address(address: &Address) -> Address {
+    Address::new_node_address(address.host.clone(), address.port)
+}
+
+/// Message was lost, so acknowledgment will never be received.
+async fn lost<T>() -> T {
+    std::future::pending().await
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Maximum time in seconds network waits for in-flight messages on suspend.
+const DRAIN_TIMEOUT: f64 = 5.0;
+
+/// Services, which are served on the listen address of the node.
+pub struct ListenServices {
+    /// Processes, which can receive messages.
+    pub processes: ProcessRegistry,
+    /// Optional admin service.
+    pub admin: Option<AdminService>,
+}
 
 pub enum NetworkRequest {
     SendMessage(RoutedMessage),
-    #[allow(dead_code)]
-    Suspend(),
+    /// Send message reliably within the timeout in seconds and report the result.
+    /// Message is tracked as in-flight until the result is known.
+    SendMessageWithAck(RoutedMessage, f64, oneshot::Sender<SendResult<()>>),
+    /// Stop listening after in-flight messages are sent,
+    /// and notify the sender when it is done.
+    Suspend(oneshot::Sender<()>),
+}
+
+/// Binds socket to the listen address of the node.
+pub fn bind(host: &str, port: u16) -> Option<Listener> {
+    GRpcMessenger::bind(host, port)
+        .map_err(|info| log::error!("Can not start listen on {}:{};\n{}.", host, port, info))
+        .ok()
+}
+
+/// Binds socket to the listen address of the node without blocking of the runtime.
+pub async fn bind_async(host: &str, port: u16) -> Option<Listener> {
+    GRpcMessenger::bind_async(host, port)
+        .await
+        .map_err(|info| log::error!("Can not start listen on {}:{};\n{}.", host, port, info))
+        .ok()
 }
 
+/// Handles network requests and listens on the [bound][bind] socket, if there is one.
 pub async fn handle(
     msg_receiver: Sender<RoutedMessage>,
     mut listen_to: Receiver<NetworkRequest>,
-    host: String,
-    port: u16,
+    listener: Option<Listener>,
+    faults: NetworkFaults,
+    services: ListenServices,
+    settings: TransportSettings,
 ) {
+    let listen_faults = faults.clone();
+    let listen_settings = settings.clone();
     let listen_handler = tokio::spawn(async move {
-        let listen_result = GRpcMessenger::listen(host.clone(), port, msg_receiver).await;
+        let Some(listener) = listener else {
+            return;
+        };
+        let listen_result = GRpcMessenger::listen(
+            listener,
+            msg_receiver,
+            listen_faults,
+            services,
+            listen_settings,
+        )
+        .await;
 
         if let Err(info) = listen_result {
-            log::error!("Can not start listen on {}:{};\n{}.", host, port, info);
+            log::error!("Can not listen: {}.", info);
         }
     });
 
     tokio::spawn(async move {
-        while let Some(request) = listen_to.recv().await {
-            match request {
-                NetworkRequest::SendMessage(routed_msg) => {
-                    tokio::spawn(send_message(routed_msg));
-                }
-                NetworkRequest::Suspend() => {
-                    break;
-                }
+        let mut in_flight = JoinSet::new();
+        let mut suspended = None;
+
+        loop {
+            tokio::select! {
+                request = listen_to.recv() => match request {
+                    Some(NetworkRequest::SendMessage(routed_msg)) => {
+                        in_flight.spawn(send_message(routed_msg, faults.clone(), settings.clone()));
+                    }
+                    Some(NetworkRequest::SendMessageWithAck(routed_msg, timeout, result)) => {
+                        let faults = faults.clone();
+                        let settings = settings.clone();
+                        in_flight.spawn(async move {
+                            let send_result =
+                                send_message_with_ack_timeout(routed_msg, timeout, &faults, &settings)
+                                    .await;
+                            let _ = result.send(send_result);
+                        });
+                    }
+                    Some(NetworkRequest::Suspend(done)) => {
+                        suspended = Some(done);
+                        break;
+                    }
+                    None => break,
+                },
+                // Forget about already sent messages.
+                Some(_) = in_flight.join_next() => {}
             }
         }
+
+        // Drain in-flight messages.
+        let drained =
+            tokio::time::timeout(tokio::time::Duration::from_secs_f64(DRAIN_TIMEOUT), async {
+                while in_flight.join_next().await.is_some() {}
+            })
+            .await;
+        if drained.is_err() {
+            warn!("Not all in-flight messages were sent before network suspended");
+        }
+
         listen_handler.abort();
+        let _ = listen_handler.await;
 
         info!("Suspended network listening");
+
+        if let Some(done) = suspended {
+            let _ = done.send(());
+        }
     });
 }
 
-async fn send_message(msg: RoutedMessage) {
-    let result = GRpcMessenger::send(ProcessSendRequest {
-        sender_address: msg.from.clone(),
-        receiver_address: msg.to.clone(),
-        message: msg.msg,
-        tag: msg.tag,
-    })
+async fn send_message(msg: RoutedMessage, faults: NetworkFaults, settings: TransportSettings) {
+    match faults.outgoing(&msg.to, false) {
+        FaultDecision::Drop => return,
+        FaultDecision::Deliver(delay) => delay_message(delay).await,
+    }
+
+    let result = GRpcMessenger::send(
+        ProcessSendRequest {
+            sender_address: msg.from.clone(),
+            receiver_address: msg.to.clone(),
+            message: msg.msg,
+            tag: msg.tag,
+        },
+        &settings,
+    )
     .await;
 
-    if let Err(info) = result {
+    if let Err(err) = result {
         warn!(
-            "Can not send message from {:?} to {:?};\n{}",
-            msg.from, msg.to, info
+            "Can not send message from {:?} to {:?}: {}",
+            msg.from, msg.to, err
         );
     }
 }
 
-pub async fn send_message_with_ack(msg: RoutedMessage) -> SendResult<()> {
-    let result = GRpcMessenger::send(ProcessSendRequest {
-        sender_address: msg.from.clone(),
-        receiver_address: msg.to.clone(),
-        message: msg.msg,
-        tag: msg.tag,
-    })
+async fn send_message_with_ack(
+    msg: RoutedMessage,
+    faults: &NetworkFaults,
+    settings: &TransportSettings,
+) -> SendResult<()> {
+    match faults.outgoing(&msg.to, true) {
+        FaultDecision::Drop => return lost().await,
+        FaultDecision::Deliver(delay) => delay_message(delay).await,
+    }
+
+    let result = GRpcMessenger::send(
+        ProcessSendRequest {
+            sender_address: msg.from.clone(),
+            receiver_address: msg.to.clone(),
+            message: msg.msg,
+            tag: msg.tag,
+        },
+        settings,
+    )
     .await;
 
-    if let Ok(response) = result {
-        if response.status == "success" {
-            Ok(())
-        } else {
-            Err(SendError::NotSent)
-        }
-    } else {
-        Err(SendError::NotSent)
+    match result?.status.as_str() {
+        "success" => Ok(()),
+        "dropped" => lost().await,
+        "process_not_found" => Err(SendError::ProcessNotFound),
+        _ => Err(SendError::NotSent),
     }
 }
 
-pub async fn send_message_with_ack_timeout(msg: RoutedMessage, timeout: f64) -> SendResult<()> {
+async fn send_message_with_ack_timeout(
+    msg: RoutedMessage,
+    timeout: f64,
+    faults: &NetworkFaults,
+    settings: &TransportSettings,
+) -> SendResult<()> {
     tokio::select! {
         _ = tokio::time::sleep(tokio::time::Duration::from_secs_f64(timeout)) => Err(SendError::Timeout),
-        send_result = send_message_with_ack(msg) => send_result
+        send_result = send_message_with_ack(msg, faults, settings) => send_result
+    }
+}
+
+async fn delay_message(delay: f64) {
+    if delay > 0.0 {
+        tokio::time::sleep(tokio::time::Duration::from_secs_f64(delay)).await;
     }
 }
@@ -1,20 +1,42 @@
 //! Definition of node in real mode.
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     future::Future,
     pin::Pin,
     sync::{atomic::AtomicU32, Arc, RwLock},
 };
 
-use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::{
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        oneshot, watch,
+    },
+    task::{JoinHandle, JoinSet},
+};
 
-use crate::{common::message::RoutedMessage, Address, Process, ProcessWrapper};
+use crate::{
+    common::{
+        membership::{
+            MembershipConfig, MembershipProcess, MembershipView, MEMBERSHIP_PROCESS,
+            MEMBERSHIP_START,
+        },
+        message::RoutedMessage,
+    },
+    Address, Message, Process, ProcessWrapper,
+};
 
 use super::{
+    admin::{AdminService, NodeMonitor},
+    builder::{Builder, NodeConfig, RuntimeKind},
     io::IOProcessWrapper,
-    network::{self, NetworkRequest},
-    process::{FromSystemMessage, ProcessManager, ProcessManagerConfig, ToSystemMessage},
+    messenger::{GRpcMessenger, Listener},
+    network::{self, ListenServices, NetworkFaults, NetworkRequest},
+    process::{
+        FromSystemMessage, ProcessManager, ProcessManagerConfig, ProcessRegistry, ToSystemMessage,
+    },
+    shutdown::{shutdown_signal, ShutdownHandle},
+    tasks::TaskRegistry,
 };
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -33,17 +55,26 @@ use super::{
 /// together will user-defined processes.
 ///
 /// After all processes and activities are spawned, user can [run][Node::run] it.
+/// Node runs until all processes are stopped or node is [shut down][Node::shutdown_handle].
 pub struct Node {
     scheduled: Vec<Pin<Box<dyn Future<Output = ()> + Send + 'static>>>,
     process_senders: HashMap<String, Sender<FromSystemMessage>>,
+    running_processes: ProcessRegistry, // Names of processes, which can receive messages.
     from_process_receiver: Receiver<ToSystemMessage>,
     to_system_sender: Sender<ToSystemMessage>, // Just to clone it and pass to different process managers.
     network_sender: Sender<NetworkRequest>,
     network_receiver: Receiver<RoutedMessage>,
-    max_buffer_size: usize,
-    host: String,
-    port: u16,
-    mount_dir: String,
+    messages_sender: Sender<RoutedMessage>, // Passed to the network handler on run.
+    messages_receiver: Receiver<NetworkRequest>, // Passed to the network handler on run.
+    faults: NetworkFaults,
+    tasks: TaskRegistry, // Activities spawned by processes, aborted when node stops.
+    shutdown: ShutdownHandle,
+    shutdown_receiver: watch::Receiver<bool>,
+    membership: MembershipView,
+    services: HashSet<String>, // Processes, which do not keep node running.
+    monitor: NodeMonitor,
+    admin: bool,
+    config: NodeConfig,
 }
 
 impl Node {
@@ -51,34 +82,72 @@ impl Node {
     ///
     /// Every process spawned on the node will have the same host and port.
     ///
+    /// If `host` is `unix:`-prefixed or an absolute path, node will listen on the Unix domain socket
+    /// with such path instead of TCP. It allows to avoid loopback overhead for nodes
+    /// located on the same host.
+    ///
+    /// Here `host` can be IPv4 or IPv6 address or hostname, which will be resolved
+    /// when node starts listening and when other nodes send messages to it.
+    /// By default node listens on the same host and port it is reachable with,
+    /// which can be changed with [`Node::set_listen_address`].
+    ///
     /// Here `storage_mount` specifies dirrectory within which process can manipulate with files.
+    ///
+    /// Node is created with default settings, use [`RealNodeBuilder`][crate::RealNodeBuilder]
+    /// to configure it.
     pub fn new(host: &str, port: u16, storage_mount: &str) -> Self {
-        let max_buffer_size = 4 << 10;
-        let (messages_sender, network_receiver) = mpsc::channel(max_buffer_size);
+        Self::from_config(NodeConfig::new(host, port, storage_mount))
+    }
+
+    /// Returns [builder][crate::RealNodeBuilder] of the node with specified listen host, port
+    /// and dirrectory of file system mount.
+    pub fn builder(host: &str, port: u16, storage_mount: &str) -> Builder {
+        Builder::new(host, port, storage_mount)
+    }
+
+    pub(crate) fn from_config(config: NodeConfig) -> Self {
+        let (messages_sender, network_receiver) = mpsc::channel(config.network_channel_capacity);
+
+        let (network_sender, messages_receiver) = mpsc::channel(config.network_channel_capacity);
 
-        let (network_sender, messages_receiver) = mpsc::channel(max_buffer_size);
+        let faults = NetworkFaults::default();
 
-        let network_handler =
-            network::handle(messages_sender, messages_receiver, host.to_owned(), port);
+        let (shutdown, shutdown_receiver) = ShutdownHandle::new();
 
-        let (to_system_sender, from_process_receiver) = mpsc::channel(max_buffer_size);
+        let (to_system_sender, from_process_receiver) =
+            mpsc::channel(config.process_channel_capacity);
 
-        let mut system = Self {
+        Self {
             scheduled: Vec::new(),
             process_senders: HashMap::new(),
+            running_processes: ProcessRegistry::default(),
             from_process_receiver,
             to_system_sender,
             network_sender,
             network_receiver,
-            max_buffer_size,
-            host: host.to_owned(),
-            port,
-            mount_dir: storage_mount.to_owned(),
-        };
-
-        system.spawn(Box::pin(network_handler));
+            messages_sender,
+            messages_receiver,
+            faults,
+            tasks: TaskRegistry::default(),
+            shutdown,
+            shutdown_receiver,
+            membership: MembershipView::default(),
+            services: HashSet::new(),
+            monitor: NodeMonitor::default(),
+            admin: false,
+            config,
+        }
+    }
 
-        system
+    /// Allows to specify host and port, on which node will listen for incoming messages.
+    ///
+    /// It is useful when the address node binds to differs from the address other nodes
+    /// reach it with, e.g. node listens on `0.0.0.0` or `::` inside a container or behind NAT,
+    /// while processes are addressed with the host and port passed to [`Node::new`].
+    /// Addresses of the processes are not affected by this method.
+    pub fn set_listen_address(&mut self, host: &str, port: u16) {
+        self.config.listen_host = host.to_owned();
+        self.config.listen_port = port;
     }
 
     /// Allows to spawn asynchronous activity on the node.
@@ -89,6 +158,33 @@ impl Node {
         self.scheduled.push(Box::pin(future));
     }
 
+    /// Returns handle which allows to inject network faults into the node.
+    ///
+    /// See [`NetworkFaults`][crate::NetworkFaults] documentation for more details.
+    pub fn network_faults(&self) -> NetworkFaults {
+        self.faults.clone()
+    }
+
+    /// Returns handle which allows to gracefully shut down the node.
+    ///
+    /// See [`ShutdownHandle`][crate::ShutdownHandle] documentation for more details.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Allows to gracefully shut down the node when `SIGINT` (`Ctrl+C`)
+    /// or `SIGTERM` is received.
+    ///
+    /// See [`shutdown_signal`][crate::shutdown_signal] for more details.
+    pub fn shutdown_on_signal(&mut self) {
+        let shutdown = self.shutdown.clone();
+        self.spawn(async move {
+            shutdown_signal().await;
+            log::info!("Received shutdown signal");
+            shutdown.shutdown();
+        });
+    }
+
     /// Allows to add process with specified name.
     ///
     /// Refer to [`Process`][crate::Process] documentation
@@ -103,16 +199,22 @@ impl Node {
             process_ref: process_ref.clone(),
         };
 
-        let (local_proc_sender, local_user_receiver) = mpsc::channel(self.max_buffer_size);
-        let (local_user_sender, local_proc_receiver) = mpsc::channel(self.max_buffer_size);
+        let (local_proc_sender, local_user_receiver) =
+            mpsc::channel(self.config.local_channel_capacity);
+        let (local_user_sender, local_proc_receiver) =
+            mpsc::channel(self.config.local_channel_capacity);
 
         let address = Address {
-            host: self.host.clone(),
-            port: self.port,
+            host: self.config.host.clone(),
+            port: self.config.port,
             process_name: name.clone(),
         };
 
-        let (to_proc_sender, from_proc_receiver) = mpsc::channel(self.max_buffer_size);
+        let (to_proc_sender, from_proc_receiver) =
+            mpsc::channel(self.config.process_channel_capacity);
+
+        self.monitor
+            .register_process(&name, local_user_sender.clone());
 
         let io_process_wrapper = IOProcessWrapper {
             wrapper: process_wrapper,
@@ -130,8 +232,11 @@ impl Node {
             system_sender: self.to_system_sender.clone(),
             system_receiver: from_proc_receiver,
             network_sender: self.network_sender.clone(),
-            max_buffer_size: self.max_buffer_size,
-            mount_dir: self.mount_dir.clone(),
+            tasks: self.tasks.clone(),
+            membership: self.membership.clone(),
+            monitor: self.monitor.clone(),
+            max_buffer_size: self.config.process_channel_capacity,
+            mount_dir: self.config.mount_dir.clone(),
         };
 
         let proc_manager = ProcessManager::new(process_manager_config);
@@ -140,6 +245,7 @@ impl Node {
             panic!("Trying to add existing process with name '{}'", name);
         }
 
+        self.running_processes.write().unwrap().insert(name.clone());
         self.process_senders.insert(name, to_proc_sender);
 
         self.spawn(Box::pin(proc_manager.run()));
@@ -147,58 +253,234 @@ impl Node {
         io_process_wrapper
     }
 
+    /// Enables [membership service][crate::MembershipConfig] on the node.
+    ///
+    /// Method adds [membership process][crate::MEMBERSHIP_PROCESS] on the node,
+    /// which is started when node runs. Processes of the node can query cluster members using
+    /// [`Context::members`][crate::Context::members] and subscribe to membership changes.
+    /// Membership process does not prevent node from stopping when all other processes are stopped.
+    /// On graceful [shutdown][Node::shutdown_handle] node announces it left the cluster.
+    pub fn enable_membership(&mut self, config: MembershipConfig) {
+        let process = MembershipProcess::new(
+            config,
+            self.membership.clone(),
+            &self.config.host,
+            self.config.port,
+        );
+        let io = self.add_process(process, MEMBERSHIP_PROCESS.to_owned());
+        self.services.insert(MEMBERSHIP_PROCESS.to_owned());
+        self.spawn(async move {
+            let start = Message::new(MEMBERSHIP_START, &()).unwrap();
+            let _ = io.sender.send(start).await;
+        });
+    }
+
+    /// Enables admin service on the node.
+    ///
+    /// Admin service is served on the same address as the messages from other nodes and allows
+    /// to list running processes, send local messages to them, read local messages sent by them,
+    /// stop them and query statistics of the node using [`AdminClient`][crate::AdminClient]
+    /// or `dsbuild-ctl` command line tool. Local messages sent by processes are recorded
+    /// since this call and are still delivered to the [receivers][IOProcessWrapper::receiver]
+    /// of the processes.
+    ///
+    /// Note that everyone who can reach the node is able to control it,
+    /// so admin service must be enabled only in the trusted network.
+    pub fn enable_admin(&mut self) {
+        self.admin = true;
+        self.monitor.enable_recording();
+    }
+
     /// Run [spawned][crate::RealNode::spawn] asynchronous activities and [processes][crate::Process].
     ///
-    /// Method will be blocked until all processes are [stopped][crate::Context::stop].
-    pub fn run(mut self) {
-        let runtime = tokio::runtime::Builder::new_multi_thread()
-            .worker_threads(4)
+    /// Method creates runtime for the node and will be blocked until all processes are
+    /// [stopped][crate::Context::stop] or node is gracefully [shut down][Node::shutdown_handle].
+    ///
+    /// Method panics if it is called from the asynchronous context.
+    /// To run node within the existing `tokio` runtime use [run_async][Node::run_async]
+    /// or [start][Node::start] methods.
+    pub fn run(self) {
+        // Bind socket before the runtime is created, so node is reachable as soon as possible.
+        let listener = self.bind();
+        self.run_blocking(listener);
+    }
+
+    /// Binds socket to the listen address of the node without running it.
+    ///
+    /// Allows [launcher][crate::LocalCluster] to detect that the port of the node is taken.
+    pub(crate) fn try_bind(&self) -> Result<Listener, String> {
+        GRpcMessenger::bind(&self.config.listen_host, self.config.listen_port)
+    }
+
+    /// Run node on the bound socket within the runtime created for the node,
+    /// see [run][Node::run].
+    pub(crate) fn run_blocking(self, listener: Option<Listener>) {
+        let mut runtime_builder = match self.config.runtime {
+            RuntimeKind::MultiThread(threads) => {
+                let mut builder = tokio::runtime::Builder::new_multi_thread();
+                builder.worker_threads(threads);
+                builder
+            }
+            RuntimeKind::CurrentThread => tokio::runtime::Builder::new_current_thread(),
+        };
+        let runtime = runtime_builder
             .enable_time()
             .enable_io()
             .build()
             .expect("Can not create the runtime");
 
-        // Run event loop and all spawned activities.
-        runtime.block_on(async move {
-            let working_processes =
-                AtomicU32::new(self.process_senders.len().try_into().unwrap());
-            // Spawn scheduled activities.
-            for shed in self.scheduled {
-                tokio::spawn(shed);
-            }
+        runtime.block_on(self.run_bound(listener));
+    }
 
-            loop {
-                tokio::select! {
-                    Some(msg) = self.network_receiver.recv() => {
-                        let sender = self.process_senders.get(&msg.to.process_name);
+    /// Run [spawned][crate::RealNode::spawn] asynchronous activities and [processes][crate::Process]
+    /// within the current `tokio` runtime.
+    ///
+    /// It allows to embed node into the application, which already has runtime,
+    /// e.g. to run node together with the HTTP server. Runtime must have time and I/O drivers enabled.
+    /// Runtime settings of the node [builder][crate::RealNodeBuilder] are ignored in this case.
+    ///
+    /// Returned future completes when all processes are [stopped][crate::Context::stop]
+    /// or node is gracefully [shut down][Node::shutdown_handle]. After that, network of the node
+    /// is suspended, and spawned activities are aborted together with activities and timers
+    /// of the processes.
+    pub async fn run_async(self) {
+        let listener = network::bind_async(&self.config.listen_host, self.config.listen_port).await;
+        self.run_bound(listener).await;
+    }
 
-                        if let Some(sender) = sender {
-                            let _ = sender.send(FromSystemMessage::NetworkMessage(msg)).await;
-                        }
-                    },
-                    Some(msg) = self.from_process_receiver.recv() => {
-                        match msg {
-                            ToSystemMessage::ProcessStopped(proc_name) => {
-                                let sender = self.process_senders.remove(&proc_name);
-
-                                if let Some(sender) = sender {
-                                    let _ = sender
-                                     .send(FromSystemMessage::Suspend())
-                                     .await;
-
-                                    working_processes.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
-
-                                    // Then all processes are stopped and we are done.
-                                    if working_processes.load(std::sync::atomic::Ordering::Relaxed) == 0 {
-                                        break;
-                                    }
+    /// Spawns node on the current `tokio` runtime and returns handle,
+    /// which can be used to wait until node completes.
+    ///
+    /// If node listens on IP address or Unix socket, it is reachable by other nodes
+    /// right after method returns. Hostname is resolved within the spawned task,
+    /// so the runtime is not blocked by the resolver.
+    /// See [run_async][Node::run_async] for more details.
+    ///
+    /// Method panics if it is called outside of the `tokio` runtime.
+    pub fn start(self) -> JoinHandle<()> {
+        if GRpcMessenger::requires_lookup(&self.config.listen_host, self.config.listen_port) {
+            return tokio::spawn(self.run_async());
+        }
+        let listener = self.bind();
+        tokio::spawn(self.run_bound(listener))
+    }
+
+    /// Binds socket to the listen address of the node.
+    ///
+    /// Messages sent to the node are queued from this moment until node starts listening,
+    /// so they are not lost if they are sent before processes start.
+    fn bind(&self) -> Option<Listener> {
+        network::bind(&self.config.listen_host, self.config.listen_port)
+    }
+
+    async fn run_bound(mut self, listener: Option<Listener>) {
+        let admin = self.admin.then(|| {
+            AdminService::new(
+                self.monitor.clone(),
+                self.running_processes.clone(),
+                self.to_system_sender.clone(),
+            )
+        });
+        let network_handler = network::handle(
+            self.messages_sender,
+            self.messages_receiver,
+            listener,
+            self.faults,
+            ListenServices {
+                processes: self.running_processes.clone(),
+                admin,
+            },
+            self.config.transport,
+        );
+        tokio::spawn(network_handler);
+
+        let working_processes = AtomicU32::new(
+            (self.process_senders.len() - self.services.len())
+                .try_into()
+                .unwrap(),
+        );
+
+        // Spawn scheduled activities.
+        let mut activities = JoinSet::new();
+        for shed in self.scheduled {
+            activities.spawn(shed);
+        }
+
+        // Run event loop.
+        loop {
+            tokio::select! {
+                Ok(()) = self.shutdown_receiver.changed() => {
+                    if *self.shutdown_receiver.borrow() {
+                        Self::shutdown(self.process_senders).await;
+                        break;
+                    }
+                },
+                Some(msg) = self.network_receiver.recv() => {
+                    let sender = self.process_senders.get(&msg.to.process_name);
+
+                    if let Some(sender) = sender {
+                        let _ = sender.send(FromSystemMessage::NetworkMessage(msg)).await;
+                    }
+                },
+                Some(msg) = self.from_process_receiver.recv() => {
+                    match msg {
+                        ToSystemMessage::ProcessStopped(proc_name) => {
+                            let sender = self.process_senders.remove(&proc_name);
+                            self.running_processes.write().unwrap().remove(&proc_name);
+
+                            if let Some(sender) = sender {
+                                let _ = sender
+                                 .send(FromSystemMessage::Suspend())
+                                 .await;
+
+                                if self.services.contains(&proc_name) {
+                                    continue;
+                                }
+
+                                working_processes.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+
+                                // Then all processes are stopped and we are done.
+                                if working_processes.load(std::sync::atomic::Ordering::Relaxed) == 0 {
+                                    break;
                                 }
                             }
                         }
                     }
-                    else => break // All channels are closed.
                 }
+                else => break // All channels are closed.
             }
-        });
+        }
+
+        Self::suspend_network(self.network_sender).await;
+
+        // Activities would be dropped together with the runtime in the blocking mode.
+        activities.shutdown().await;
+        self.tasks.abort_all();
+    }
+
+    /// Stops running processes, which call their shutdown hooks.
+    async fn shutdown(process_senders: HashMap<String, Sender<FromSystemMessage>>) {
+        for sender in process_senders.into_values() {
+            let (done_sender, done_receiver) = oneshot::channel();
+            if sender
+                .send(FromSystemMessage::Shutdown(done_sender))
+                .await
+                .is_ok()
+            {
+                let _ = done_receiver.await;
+            }
+        }
+    }
+
+    /// Suspends network after in-flight messages are sent.
+    async fn suspend_network(network_sender: Sender<NetworkRequest>) {
+        let (done_sender, done_receiver) = oneshot::channel();
+        if network_sender
+            .send(NetworkRequest::Suspend(done_sender))
+            .await
+            .is_ok()
+        {
+            let _ = done_receiver.await;
+        }
     }
 }
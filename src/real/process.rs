@@ -1,22 +1,36 @@
 //! Definition of process management objects.
 
-use std::sync::{Arc, Mutex, RwLock};
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex, RwLock},
+};
 
-use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::{
+    mpsc::{channel, Receiver, Sender},
+    oneshot,
+};
 
 use crate::{
-    common::{context::Context, message::RoutedMessage},
+    common::{context::Context, membership::MembershipView, message::RoutedMessage},
     Address, Message, Process,
 };
 
 use super::{
-    context::RealContext, msg_waiters::MessageWaiters, network::NetworkRequest, timer::TimerManager,
+    admin::NodeMonitor, context::RealContext, msg_waiters::MessageWaiters, network::NetworkRequest,
+    tasks::TaskRegistry, timer::TimerManager,
 };
 
+/// Names of the processes, which are running on the node.
+///
+/// Used by the network to report delivery failure for unknown or stopped processes.
+pub type ProcessRegistry = Arc<RwLock<HashSet<String>>>;
+
 /// All messages which can be received from system.
 pub enum FromSystemMessage {
     NetworkMessage(RoutedMessage),
     Suspend(),
+    /// Node is shutting down, sender is notified when process is done.
+    Shutdown(oneshot::Sender<()>),
 }
 
 /// All messages which can be sent to system.
@@ -48,9 +62,12 @@ pub struct ProcessManager {
 pub struct InteractionBlock {
     pub local: Sender<Message>,
     pub network: Sender<NetworkRequest>,
+    pub tasks: TaskRegistry,
     pub system: Sender<ToSystemMessage>,
     pub timer_mngr: Arc<Mutex<TimerManager>>,
     pub message_waiters: Arc<Mutex<MessageWaiters>>,
+    pub membership: MembershipView,
+    pub monitor: NodeMonitor,
 }
 
 pub struct ProcessManagerConfig {
@@ -61,6 +78,9 @@ pub struct ProcessManagerConfig {
     pub system_sender: Sender<ToSystemMessage>,
     pub system_receiver: Receiver<FromSystemMessage>,
     pub network_sender: Sender<NetworkRequest>,
+    pub tasks: TaskRegistry,
+    pub membership: MembershipView,
+    pub monitor: NodeMonitor,
     pub max_buffer_size: usize,
     pub mount_dir: String,
 }
@@ -70,15 +90,18 @@ impl ProcessManager {
     pub fn new(config: ProcessManagerConfig) -> Self {
         let (timer_sender, timers_receiver) = channel(config.max_buffer_size);
 
-        let timer_manager = TimerManager::new(timer_sender);
+        let timer_manager = TimerManager::new(timer_sender, config.tasks.clone());
         let timer_manager_ref = Arc::new(Mutex::new(timer_manager));
 
         let output = InteractionBlock {
             local: config.local_sender,
             network: config.network_sender,
+            tasks: config.tasks,
             system: config.system_sender,
             timer_mngr: timer_manager_ref,
             message_waiters: Arc::new(Mutex::new(MessageWaiters::default())),
+            membership: config.membership,
+            monitor: config.monitor,
         };
 
         Self {
@@ -100,13 +123,21 @@ impl ProcessManager {
                 Some(msg) = self.system_receiver.recv() => {
                     match msg {
                         FromSystemMessage::NetworkMessage(msg) => self.handle_message(msg),
-                        FromSystemMessage::Suspend() => break
+                        FromSystemMessage::Suspend() => break,
+                        FromSystemMessage::Shutdown(done) => {
+                            self.handle_shutdown();
+                            let _ = done.send(());
+                            break;
+                        }
                     }
                 },
                 Some(timer_name) = self.timers_receiver.recv() => self.handle_timer_fired(timer_name),
                 else => break
             }
         }
+
+        // Timers of the stopped process must not fire.
+        self.output.timer_mngr.lock().unwrap().cancel_all_timers();
     }
 
     fn create_context(&self) -> Context {
@@ -119,6 +150,7 @@ impl ProcessManager {
     }
 
     fn handle_local_message(&mut self, msg: Message) {
+        self.output.monitor.on_local_message_received();
         self.process
             .write()
             .unwrap()
@@ -126,6 +158,8 @@ impl ProcessManager {
     }
 
     fn handle_message(&mut self, mut msg: RoutedMessage) {
+        self.output.monitor.on_network_message_received();
+
         if let Some(tag) = msg.tag {
             if let Some(waiting) = self.output.message_waiters.lock().unwrap().get_mut(&tag) {
                 if let Some(s) = waiting.pop() {
@@ -145,6 +179,14 @@ impl ProcessManager {
         );
     }
 
+    fn handle_shutdown(&mut self) {
+        self.output.timer_mngr.lock().unwrap().cancel_all_timers();
+        self.process
+            .write()
+            .unwrap()
+            .on_shutdown(self.create_context());
+    }
+
     fn handle_timer_fired(&mut self, timer_name: String) {
         self.process
             .write()
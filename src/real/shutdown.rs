@@ -0,0 +1,69 @@
+//! Definition of graceful shutdown primitives.
+
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Handle which allows to gracefully shut down the [real node][crate::RealNode] from outside.
+///
+/// Handle can be obtained by [`RealNode::shutdown_handle`][crate::RealNode::shutdown_handle]
+/// before the node is run, cloned and passed to other threads or asynchronous activities.
+///
+/// On shutdown node cancels pending timers of every running process, calls
+/// [`Process::on_shutdown`][crate::Process::on_shutdown] hooks, waits until
+/// in-flight network messages are sent, closes the listener and returns from
+/// [`RealNode::run`][crate::RealNode::run].
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl ShutdownHandle {
+    pub(crate) fn new() -> (Self, watch::Receiver<bool>) {
+        let (sender, receiver) = watch::channel(false);
+        let handle = Self {
+            sender: Arc::new(sender),
+        };
+        (handle, receiver)
+    }
+
+    /// Requests node to shut down.
+    ///
+    /// Method does not wait until the shutdown completes.
+    /// Shutdown can be requested before the node is run, in which case
+    /// node will shut down right after start.
+    pub fn shutdown(&self) {
+        self.sender.send_replace(true);
+    }
+
+    /// Returns if the shutdown was requested.
+    pub fn is_shutdown_requested(&self) -> bool {
+        *self.sender.borrow()
+    }
+}
+
+/// Waits until the process receives `SIGINT` (`Ctrl+C`) or `SIGTERM`.
+///
+/// On non-Unix platforms only `Ctrl+C` is awaited.
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate =
+            signal(SignalKind::terminate()).expect("Can not install SIGTERM handler");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {},
+            _ = terminate.recv() => {},
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Can not install Ctrl+C handler");
+    }
+}
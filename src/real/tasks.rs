@@ -0,0 +1,64 @@
+//! Definition of registry of asynchronous activities spawned by the processes.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use tokio::task::AbortHandle;
+
+#[derive(Default)]
+struct Tasks {
+    next_id: u64,
+    running: HashMap<u64, AbortHandle>,
+    closed: bool,
+}
+
+/// Tracks asynchronous activities spawned by the processes of the node
+/// (user activities, timers, pending sends), so they can be aborted when node stops.
+///
+/// Finished activities remove themselves from the registry.
+#[derive(Clone, Default)]
+pub(crate) struct TaskRegistry {
+    tasks: Arc<Mutex<Tasks>>,
+}
+
+impl TaskRegistry {
+    /// Spawn activity on the current runtime and track it.
+    ///
+    /// Returns handle to abort the activity or `None` if registry is closed,
+    /// in which case activity is not spawned.
+    pub fn spawn<F>(&self, future: F) -> Option<AbortHandle>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        // Lock is held until the activity is registered,
+        // so it can not remove itself before that.
+        let mut tasks = self.tasks.lock().unwrap();
+        if tasks.closed {
+            return None;
+        }
+        let id = tasks.next_id;
+        tasks.next_id += 1;
+
+        let registry = self.tasks.clone();
+        let handle = tokio::spawn(async move {
+            future.await;
+            registry.lock().unwrap().running.remove(&id);
+        })
+        .abort_handle();
+        tasks.running.insert(id, handle.clone());
+        Some(handle)
+    }
+
+    /// Abort all tracked activities and close the registry,
+    /// so activities spawned after that are ignored.
+    pub fn abort_all(&self) {
+        let mut tasks = self.tasks.lock().unwrap();
+        tasks.closed = true;
+        for (_, handle) in tasks.running.drain() {
+            handle.abort();
+        }
+    }
+}
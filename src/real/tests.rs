@@ -1,4 +1,12 @@
-use std::time::{Duration, SystemTime};
+use std::{
+    net::TcpListener,
+    path::Path,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
 
 use tokio::{
     sync::{mpsc, oneshot},
@@ -6,7 +14,16 @@ use tokio::{
 };
 
 use crate::{
-    common::context::Context, real::timer::TimerManager, Address, Message, Process, RealNode, Tag,
+    common::context::Context,
+    real::{
+        network::{FaultDecision, NetworkFaults},
+        tasks::TaskRegistry,
+        timer::TimerManager,
+        transport::Transport,
+    },
+    Address, AdminClient, AdminError, ClusterError, ClusterSpec, LocalCluster, Member, MemberState,
+    MembershipConfig, Message, Process, ProcessFactory, RealNode, RealNodeBuilder,
+    RealNodeConfigError, RetryPolicy, SendError, Tag, MEMBERSHIP_EVENT,
 };
 
 #[derive(Clone)]
@@ -109,7 +126,7 @@ fn local_messages_works() {
 async fn timer_manager_set_timer_works() {
     let (sender, mut receiver) = mpsc::channel(100);
 
-    let mut manager = TimerManager::new(sender);
+    let mut manager = TimerManager::new(sender, TaskRegistry::default());
 
     let time1 = SystemTime::now();
 
@@ -135,7 +152,7 @@ async fn timer_manager_set_timer_works() {
 async fn timer_manager_cancel_works() {
     let (sender, mut receiver) = mpsc::channel(100);
 
-    let mut manager = TimerManager::new(sender);
+    let mut manager = TimerManager::new(sender, TaskRegistry::default());
 
     // Check 'set_timer' works.
     let time1 = SystemTime::now();
@@ -231,3 +248,1033 @@ fn send_recv_works() {
     let got_message = flag_event_receiver.blocking_recv().unwrap();
     assert!(got_message);
 }
+
+#[test]
+fn transport_from_host_works() {
+    assert_eq!(
+        Transport::new("127.0.0.1", 10095),
+        Transport::Tcp {
+            host: "127.0.0.1".to_owned(),
+            port: 10095
+        }
+    );
+    assert_eq!(
+        Transport::new("unix:/tmp/node.sock", 10095),
+        Transport::Unix {
+            path: "/tmp/node.sock".into()
+        }
+    );
+    assert_eq!(
+        Transport::new("/tmp/node.sock", 0),
+        Transport::Unix {
+            path: "/tmp/node.sock".into()
+        }
+    );
+}
+
+struct PingProcess {}
+
+impl Process for PingProcess {
+    fn on_local_message(&mut self, msg: Message, ctx: Context) {
+        let to = msg.data::<Address>().unwrap();
+        ctx.clone().spawn(async move {
+            ctx.send_with_ack(Message::new("ping", &"ping").unwrap(), to, 5.0)
+                .await
+                .unwrap();
+        });
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {
+        unreachable!()
+    }
+
+    fn on_message(&mut self, msg: Message, _from: Address, ctx: Context) {
+        assert_eq!(msg.tip(), "pong");
+        ctx.send_local(msg);
+        ctx.stop();
+    }
+}
+
+struct PongProcess {}
+
+impl Process for PongProcess {
+    fn on_local_message(&mut self, _msg: Message, _ctx: Context) {
+        unreachable!()
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {
+        unreachable!()
+    }
+
+    fn on_message(&mut self, msg: Message, from: Address, ctx: Context) {
+        assert_eq!(msg.tip(), "ping");
+        ctx.clone().spawn(async move {
+            // Ping node can stop before the acknowledgement is sent.
+            let _ = ctx
+                .send_with_ack(Message::new("pong", &"pong").unwrap(), from, 5.0)
+                .await;
+            ctx.stop();
+        });
+    }
+}
+
+#[test]
+fn unix_socket_transport_works() {
+    let dir = std::env::temp_dir().join(format!("dsbuild-unix-{}", std::process::id()));
+    let ping_dir = dir.join("ping");
+    let pong_dir = dir.join("pong");
+    std::fs::create_dir_all(&ping_dir).unwrap();
+    std::fs::create_dir_all(&pong_dir).unwrap();
+
+    let ping_host = format!("unix:{}", ping_dir.join("node.sock").display());
+    let pong_host = format!("unix:{}", pong_dir.join("node.sock").display());
+
+    let mut ping_node = RealNode::new(&ping_host, 0, ping_dir.to_str().unwrap());
+    let mut ping_io = ping_node.add_process(PingProcess {}, "ping".to_owned());
+
+    let mut pong_node = RealNode::new(&pong_host, 0, pong_dir.to_str().unwrap());
+    pong_node.add_process(PongProcess {}, "pong".to_owned());
+
+    let pong_addr = Address::new_ref(&pong_host, 0, "pong");
+    ping_node.spawn(async move {
+        // Wait for the pong node starts listening.
+        sleep(Duration::from_millis(200)).await;
+
+        ping_io
+            .sender
+            .send(Message::new("addr", &pong_addr).unwrap())
+            .await
+            .unwrap();
+        let msg = ping_io.receiver.recv().await.unwrap();
+        assert_eq!(msg.data::<String>().unwrap(), "pong");
+    });
+
+    let pong_handle = std::thread::spawn(move || pong_node.run());
+    let ping_handle = std::thread::spawn(move || ping_node.run());
+
+    ping_handle.join().unwrap();
+    pong_handle.join().unwrap();
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn listen_address_and_hostnames_work() {
+    // Ping node is reachable with IPv6 loopback address, but listens on all IPv6 interfaces.
+    let mut ping_node = RealNode::new("::1", 10100, "/tmp/");
+    ping_node.set_listen_address("::", 10100);
+    let mut ping_io = ping_node.add_process(PingProcess {}, "ping".to_owned());
+
+    // Pong node is reachable with hostname and listens on the same hostname,
+    // so it is reachable whichever address family hostname is resolved into.
+    let mut pong_node = RealNode::new("localhost", 10101, "/tmp/");
+    pong_node.add_process(PongProcess {}, "pong".to_owned());
+
+    let pong_addr = Address::new_ref("localhost", 10101, "pong");
+    ping_node.spawn(async move {
+        // Wait for the pong node starts listening.
+        sleep(Duration::from_millis(200)).await;
+
+        ping_io
+            .sender
+            .send(Message::new("addr", &pong_addr).unwrap())
+            .await
+            .unwrap();
+        let msg = ping_io.receiver.recv().await.unwrap();
+        assert_eq!(msg.data::<String>().unwrap(), "pong");
+    });
+
+    let pong_handle = std::thread::spawn(move || pong_node.run());
+    let ping_handle = std::thread::spawn(move || ping_node.run());
+
+    ping_handle.join().unwrap();
+    pong_handle.join().unwrap();
+}
+
+#[test]
+fn network_faults_decisions() {
+    let faults = NetworkFaults::default();
+    let addr = Address::new_ref("127.0.0.1", 10100, "proc");
+    let other_addr = Address::new_ref("127.0.0.1", 10101, "proc");
+    assert_eq!(faults.outgoing(&addr, false), FaultDecision::Deliver(0.0));
+
+    faults.set_drop_rate(1.0);
+    assert_eq!(faults.outgoing(&addr, false), FaultDecision::Drop);
+    assert_eq!(faults.outgoing(&addr, true), FaultDecision::Deliver(0.0));
+
+    faults.set_drop_rate(0.0);
+    faults.set_delays(0.1, 0.2);
+    match faults.outgoing(&addr, false) {
+        FaultDecision::Deliver(delay) => assert!((0.1..=0.2).contains(&delay)),
+        FaultDecision::Drop => panic!("message must not be dropped"),
+    }
+
+    faults.block_node("127.0.0.1", 10100);
+    assert_eq!(faults.outgoing(&addr, true), FaultDecision::Drop);
+    assert!(faults.drops_incoming(&addr));
+    assert!(!faults.drops_incoming(&other_addr));
+
+    faults.unblock_node("127.0.0.1", 10100);
+    assert!(!faults.drops_incoming(&addr));
+
+    faults.disconnect();
+    assert!(faults.drops_incoming(&other_addr));
+
+    faults.reset();
+    assert_eq!(
+        faults.outgoing(&other_addr, false),
+        FaultDecision::Deliver(0.0)
+    );
+}
+
+struct AckReporter {}
+
+impl Process for AckReporter {
+    fn on_local_message(&mut self, msg: Message, ctx: Context) {
+        let to = msg.data::<Address>().unwrap();
+        ctx.clone().spawn(async move {
+            let result = ctx
+                .send_with_ack(Message::new("ping", &"ping").unwrap(), to, 0.3)
+                .await;
+            ctx.send_local(Message::new("result", &result.is_ok()).unwrap());
+        });
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {
+        unreachable!()
+    }
+
+    fn on_message(&mut self, _msg: Message, _from: Address, _ctx: Context) {}
+}
+
+#[test]
+fn network_faults_block_node() {
+    let mut node = RealNode::new("127.0.0.1", 10097, "/tmp/");
+    let faults = node.network_faults();
+    let mut sender_io = node.add_process(AckReporter {}, "sender".to_owned());
+    let mut receiver_io = node.add_process(AckReporter {}, "receiver".to_owned());
+    let receiver = Address::new_ref("127.0.0.1", 10097, "receiver");
+
+    node.spawn(async move {
+        // Messages to the blocked node are lost.
+        faults.block_node("127.0.0.1", 10097);
+        sender_io
+            .sender
+            .send(Message::new("addr", &receiver).unwrap())
+            .await
+            .unwrap();
+        let delivered = sender_io.receiver.recv().await.unwrap();
+        assert!(!delivered.data::<bool>().unwrap());
+
+        // Messages are delivered after node is unblocked.
+        faults.unblock_node("127.0.0.1", 10097);
+        sender_io
+            .sender
+            .send(Message::new("addr", &receiver).unwrap())
+            .await
+            .unwrap();
+        let delivered = sender_io.receiver.recv().await.unwrap();
+        assert!(delivered.data::<bool>().unwrap());
+
+        sender_io.stop_process().await;
+        receiver_io.stop_process().await;
+    });
+
+    node.run();
+}
+
+struct RetryingSender {
+    receiver: Address,
+}
+
+impl Process for RetryingSender {
+    fn on_local_message(&mut self, msg: Message, ctx: Context) {
+        let to = self.receiver.clone();
+        ctx.clone().spawn(async move {
+            // Report the result of the single attempt before retries start.
+            let result = ctx.send_with_ack(msg.clone(), to.clone(), 0.5).await;
+            ctx.send_local(Message::new("attempt", &result.is_ok()).unwrap());
+
+            let result = ctx
+                .with_retry_policy(RetryPolicy::new(20).with_backoff(0.1, 0.2))
+                .send_with_ack(msg, to, 0.5)
+                .await;
+            ctx.send_local(Message::new("result", &result.is_ok()).unwrap());
+        });
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {
+        unreachable!()
+    }
+
+    fn on_message(&mut self, _msg: Message, _from: Address, _ctx: Context) {
+        unreachable!()
+    }
+}
+
+struct StopOnMessage {}
+
+impl Process for StopOnMessage {
+    fn on_local_message(&mut self, _msg: Message, _ctx: Context) {
+        unreachable!()
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {
+        unreachable!()
+    }
+
+    fn on_message(&mut self, _msg: Message, _from: Address, ctx: Context) {
+        // Give the acknowledgement time to reach the sender.
+        ctx.clone().spawn(async move {
+            ctx.sleep(0.2).await;
+            ctx.stop();
+        });
+    }
+}
+
+#[test]
+fn send_with_ack_retries_works() {
+    // Port is reserved, but not listened, so connections to the receiver are refused
+    // until it starts.
+    let reserved = tokio::net::TcpSocket::new_v4().unwrap();
+    reserved.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let receiver_port = reserved.local_addr().unwrap().port();
+
+    let mut receiver_node = RealNode::new("127.0.0.1", receiver_port, "/tmp/");
+    receiver_node.add_process(AckReporter {}, "receiver".to_owned());
+    let receiver_shutdown = receiver_node.shutdown_handle();
+
+    let mut sender_node = RealNode::new("127.0.0.1", 0, "/tmp/");
+    let mut sender_io = sender_node.add_process(
+        RetryingSender {
+            receiver: Address::new_ref("127.0.0.1", receiver_port, "receiver"),
+        },
+        "sender".to_owned(),
+    );
+
+    let (refused_sender, refused_receiver) = std::sync::mpsc::channel();
+    sender_node.spawn(async move {
+        sender_io.sender.send("hello".into()).await.unwrap();
+        let attempt = sender_io.receiver.recv().await.unwrap();
+        assert!(!attempt.data::<bool>().unwrap());
+        refused_sender.send(()).unwrap();
+
+        let result = sender_io.receiver.recv().await.unwrap();
+        assert!(result.data::<bool>().unwrap());
+        receiver_shutdown.shutdown();
+        sender_io.stop_process().await;
+    });
+
+    let sender_handle = std::thread::spawn(move || sender_node.run());
+
+    // Receiver appears only after the first attempt failed.
+    refused_receiver.recv().unwrap();
+    drop(reserved);
+    receiver_node.run();
+
+    sender_handle.join().unwrap();
+}
+
+#[derive(Clone)]
+struct GoodbyeProcess {
+    to: Address,
+    shutdown_called: bool,
+}
+
+impl Process for GoodbyeProcess {
+    fn on_local_message(&mut self, _msg: Message, ctx: Context) {
+        // Timer must be cancelled on shutdown.
+        ctx.set_timer("timer", 0.5);
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {
+        unreachable!()
+    }
+
+    fn on_message(&mut self, _msg: Message, _from: Address, _ctx: Context) {
+        unreachable!()
+    }
+
+    fn on_shutdown(&mut self, ctx: Context) {
+        self.shutdown_called = true;
+        ctx.send(
+            Message::new("goodbye", &"goodbye").unwrap(),
+            self.to.clone(),
+        );
+    }
+}
+
+#[test]
+fn graceful_shutdown_works() {
+    let mut receiver_node = RealNode::new("127.0.0.1", 10103, "/tmp/");
+    receiver_node.add_process(StopOnMessage {}, "receiver".to_owned());
+    let receiver_handle = std::thread::spawn(move || receiver_node.run());
+
+    let mut node = RealNode::new("127.0.0.1", 10102, "/tmp/");
+    let io = node.add_process(
+        GoodbyeProcess {
+            to: Address::new_ref("127.0.0.1", 10103, "receiver"),
+            shutdown_called: false,
+        },
+        "goodbye".to_owned(),
+    );
+    let process = io.wrapper.clone();
+
+    let shutdown = node.shutdown_handle();
+    assert!(!shutdown.is_shutdown_requested());
+    node.spawn(async move {
+        io.sender.send("start".into()).await.unwrap();
+        sleep(Duration::from_millis(200)).await;
+        shutdown.shutdown();
+    });
+
+    // Returns after shutdown, while process is still running.
+    node.run();
+    assert!(process.read().shutdown_called);
+
+    // Receiver stops only after it receives goodbye message.
+    receiver_handle.join().unwrap();
+}
+
+struct ReliableGoodbyeProcess {
+    to: Address,
+}
+
+impl Process for ReliableGoodbyeProcess {
+    fn on_local_message(&mut self, _msg: Message, ctx: Context) {
+        let to = self.to.clone();
+        ctx.clone().spawn(async move {
+            let _ = ctx
+                .send_with_ack(Message::new("goodbye", &"goodbye").unwrap(), to, 3.0)
+                .await;
+        });
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {
+        unreachable!()
+    }
+
+    fn on_message(&mut self, _msg: Message, _from: Address, _ctx: Context) {
+        unreachable!()
+    }
+}
+
+#[test]
+fn graceful_shutdown_drains_reliable_sends() {
+    let mut receiver_node = RealNode::new("127.0.0.1", 10119, "/tmp/");
+    receiver_node.add_process(StopOnMessage {}, "receiver".to_owned());
+    let receiver_handle = std::thread::spawn(move || receiver_node.run());
+
+    let mut node = RealNode::new("127.0.0.1", 0, "/tmp/");
+    // Message is still in flight when the node shuts down.
+    node.network_faults().set_delay(0.5);
+    let io = node.add_process(
+        ReliableGoodbyeProcess {
+            to: Address::new_ref("127.0.0.1", 10119, "receiver"),
+        },
+        "goodbye".to_owned(),
+    );
+    let shutdown = node.shutdown_handle();
+    node.spawn(async move {
+        io.sender.send("start".into()).await.unwrap();
+        sleep(Duration::from_millis(200)).await;
+        shutdown.shutdown();
+    });
+    node.run();
+
+    // Receiver stops only after it receives goodbye message.
+    receiver_handle.join().unwrap();
+}
+
+struct TickingProcess {
+    ticks: Arc<AtomicU32>,
+}
+
+impl Process for TickingProcess {
+    fn on_local_message(&mut self, _msg: Message, ctx: Context) {
+        ctx.set_timer("timer", 0.2);
+        let ticks = self.ticks.clone();
+        ctx.clone().spawn(async move {
+            loop {
+                ticks.fetch_add(1, Ordering::SeqCst);
+                ctx.sleep(0.01).await;
+            }
+        });
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {
+        self.ticks.fetch_add(1000, Ordering::SeqCst);
+    }
+
+    fn on_message(&mut self, _msg: Message, _from: Address, _ctx: Context) {
+        unreachable!()
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn process_activities_are_aborted_on_shutdown() {
+    let ticks = Arc::new(AtomicU32::new(0));
+    let mut node = RealNode::new("127.0.0.1", 0, "/tmp/");
+    let io = node.add_process(
+        TickingProcess {
+            ticks: ticks.clone(),
+        },
+        "ticking".to_owned(),
+    );
+    let shutdown = node.shutdown_handle();
+    let handle = node.start();
+
+    io.sender.send("start".into()).await.unwrap();
+    sleep(Duration::from_millis(100)).await;
+    shutdown.shutdown();
+    handle.await.unwrap();
+
+    // Runtime is still alive, but the activity and the timer of the process are aborted.
+    let stopped_at = ticks.load(Ordering::SeqCst);
+    assert!(stopped_at > 0);
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(ticks.load(Ordering::SeqCst), stopped_at);
+}
+
+#[test]
+fn shutdown_before_run_works() {
+    let mut node = RealNode::new("127.0.0.1", 10104, "/tmp/");
+    node.add_process(StopOnMessage {}, "process".to_owned());
+    let shutdown = node.shutdown_handle();
+    shutdown.shutdown();
+    assert!(shutdown.is_shutdown_requested());
+    node.run();
+}
+
+#[test]
+fn builder_validates_config() {
+    let err = |builder: RealNodeBuilder| builder.build().err().unwrap();
+
+    assert_eq!(
+        err(RealNodeBuilder::new("", 10105, "/tmp/")),
+        RealNodeConfigError::EmptyHost
+    );
+    assert_eq!(
+        err(RealNodeBuilder::new("127.0.0.1", 10105, "/tmp/").listen_address("", 10105)),
+        RealNodeConfigError::EmptyHost
+    );
+    assert_eq!(
+        err(RealNodeBuilder::new("127.0.0.1", 10105, "/tmp/").process_channel_capacity(0)),
+        RealNodeConfigError::ZeroChannelCapacity("process")
+    );
+    assert_eq!(
+        err(RealNodeBuilder::new("127.0.0.1", 10105, "/tmp/").worker_threads(0)),
+        RealNodeConfigError::ZeroWorkerThreads
+    );
+    assert_eq!(
+        err(RealNodeBuilder::new("127.0.0.1", 10105, "/tmp/").send_timeout(-1.0)),
+        RealNodeConfigError::InvalidDuration("send timeout", -1.0)
+    );
+
+    let dir = std::env::temp_dir().join(format!("dsbuild-builder-{}", std::process::id()));
+    let mount = dir.join("mount");
+    let mount = mount.to_str().unwrap();
+    assert_eq!(
+        err(RealNodeBuilder::new("127.0.0.1", 10105, mount)),
+        RealNodeConfigError::StorageMountNotFound(mount.to_owned())
+    );
+    assert!(RealNodeBuilder::new("127.0.0.1", 10105, mount)
+        .create_storage_mount(true)
+        .build()
+        .is_ok());
+    assert!(std::path::Path::new(mount).is_dir());
+
+    let file = dir.join("file");
+    std::fs::write(&file, "").unwrap();
+    let file = file.to_str().unwrap();
+    assert_eq!(
+        err(RealNodeBuilder::new("127.0.0.1", 10105, file)),
+        RealNodeConfigError::StorageMountNotDirectory(file.to_owned())
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn builder_current_thread_works() {
+    let mut ping_node = RealNode::builder("127.0.0.1", 10106, "/tmp/")
+        .current_thread()
+        .network_channel_capacity(16)
+        .process_channel_capacity(16)
+        .local_channel_capacity(1)
+        .connect_timeout(1.0)
+        .send_timeout(1.0)
+        .tcp_keepalive(10.0)
+        .build()
+        .unwrap();
+    let mut ping_io = ping_node.add_process(PingProcess {}, "ping".to_owned());
+
+    let mut pong_node = RealNode::builder("127.0.0.1", 10107, "/tmp/")
+        .worker_threads(1)
+        .tcp_nodelay(false)
+        .build()
+        .unwrap();
+    pong_node.add_process(PongProcess {}, "pong".to_owned());
+
+    let pong_addr = Address::new_ref("127.0.0.1", 10107, "pong");
+    ping_node.spawn(async move {
+        // Wait for the pong node starts listening.
+        sleep(Duration::from_millis(200)).await;
+
+        ping_io
+            .sender
+            .send(Message::new("addr", &pong_addr).unwrap())
+            .await
+            .unwrap();
+        let msg = ping_io.receiver.recv().await.unwrap();
+        assert_eq!(msg.data::<String>().unwrap(), "pong");
+    });
+
+    let pong_handle = std::thread::spawn(move || pong_node.run());
+    let ping_handle = std::thread::spawn(move || ping_node.run());
+
+    ping_handle.join().unwrap();
+    pong_handle.join().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn run_async_works() {
+    let mut ping_node = RealNode::new("127.0.0.1", 10108, "/tmp/");
+    let mut ping_io = ping_node.add_process(PingProcess {}, "ping".to_owned());
+
+    // Hostname of the node is resolved without blocking of the runtime.
+    let mut pong_node = RealNode::new("localhost", 10109, "/tmp/");
+    pong_node.add_process(PongProcess {}, "pong".to_owned());
+
+    // Node can be spawned on the current runtime.
+    let pong_handle = pong_node.start();
+
+    // Activity can be executed outside of the node.
+    let pong_addr = Address::new_ref("localhost", 10109, "pong");
+    let activity = async move {
+        // Wait for the pong node starts listening.
+        sleep(Duration::from_millis(200)).await;
+
+        ping_io
+            .sender
+            .send(Message::new("addr", &pong_addr).unwrap())
+            .await
+            .unwrap();
+        let msg = ping_io.receiver.recv().await.unwrap();
+        assert_eq!(msg.data::<String>().unwrap(), "pong");
+    };
+
+    tokio::join!(ping_node.run_async(), activity);
+    pong_handle.await.unwrap();
+}
+
+struct MembershipWatcher {}
+
+impl Process for MembershipWatcher {
+    fn on_local_message(&mut self, _msg: Message, ctx: Context) {
+        ctx.subscribe_members();
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {
+        unreachable!()
+    }
+
+    fn on_message(&mut self, msg: Message, _from: Address, ctx: Context) {
+        assert_eq!(msg.tip(), MEMBERSHIP_EVENT);
+        let member = msg.data::<Member>().unwrap();
+        ctx.send_local(msg);
+        if member.state == MemberState::Left {
+            ctx.stop();
+        }
+    }
+}
+
+#[test]
+fn membership_works() {
+    let config = MembershipConfig::new(vec![Address::new_ref("127.0.0.1", 10110, "")])
+        .with_protocol_period(0.1, 0.05);
+
+    let mut watcher_node = RealNode::new("127.0.0.1", 10110, "/tmp/");
+    watcher_node.enable_membership(config.clone());
+    let mut io = watcher_node.add_process(MembershipWatcher {}, "watcher".to_owned());
+    io.sender.try_send("subscribe".into()).unwrap();
+    let watcher_handle = std::thread::spawn(move || watcher_node.run());
+
+    let mut node = RealNode::new("127.0.0.1", 10111, "/tmp/");
+    node.enable_membership(config);
+    node.add_process(StopOnMessage {}, "process".to_owned());
+    let shutdown = node.shutdown_handle();
+    node.spawn(async move {
+        sleep(Duration::from_millis(500)).await;
+        shutdown.shutdown();
+    });
+
+    // Node announces it left the cluster on shutdown.
+    node.run();
+
+    // Watcher node stops after watcher receives the announcement.
+    watcher_handle.join().unwrap();
+    let events = std::iter::from_fn(|| io.receiver.try_recv().ok())
+        .map(|msg| msg.data::<Member>().unwrap())
+        .map(|member| (member.address.port, member.state))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        vec![(10111, MemberState::Alive), (10111, MemberState::Left)]
+    );
+}
+
+struct AckSender {
+    receivers: Vec<Address>,
+}
+
+impl Process for AckSender {
+    fn on_local_message(&mut self, msg: Message, ctx: Context) {
+        let receivers = self.receivers.clone();
+        ctx.clone().spawn(async move {
+            let mut results = Vec::new();
+            for to in receivers {
+                let result = ctx.send_with_ack(msg.clone(), to, 1.0).await;
+                results.push(result);
+            }
+            ctx.send_local(Message::new("results", &format!("{:?}", results)).unwrap());
+            ctx.stop();
+        });
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {
+        unreachable!()
+    }
+
+    fn on_message(&mut self, _msg: Message, _from: Address, _ctx: Context) {
+        unreachable!()
+    }
+}
+
+#[test]
+fn send_to_unknown_process_fails() {
+    let mut node = RealNode::new("127.0.0.1", 10112, "/tmp/");
+    node.add_process(StopOnMessage {}, "receiver".to_owned());
+    let mut io = node.add_process(
+        AckSender {
+            receivers: vec![
+                Address::new_ref("127.0.0.1", 10112, "missing"),
+                Address::new_ref("127.0.0.1", 10112, "receiver"),
+            ],
+        },
+        "sender".to_owned(),
+    );
+    let sender = io.sender.clone();
+    node.spawn(async move {
+        // Wait until node starts listening.
+        sleep(Duration::from_millis(100)).await;
+        sender.send("hello".into()).await.unwrap();
+    });
+    node.run();
+
+    let results = io.receiver.try_recv().unwrap();
+    assert_eq!(
+        results.data::<String>().unwrap(),
+        format!("{:?}", vec![Err(SendError::ProcessNotFound), Ok(())])
+    );
+}
+
+#[test]
+fn send_errors_are_classified() {
+    let mut node = RealNode::new("127.0.0.1", 10113, "/tmp/");
+    let mut io = node.add_process(
+        AckSender {
+            receivers: vec![
+                Address::new_ref("127.0.0.1", 10114, "process"),
+                Address::new_ref("nonexistent.invalid", 10114, "process"),
+            ],
+        },
+        "sender".to_owned(),
+    );
+    io.sender.try_send("hello".into()).unwrap();
+    node.run();
+
+    let results = io.receiver.try_recv().unwrap();
+    assert_eq!(
+        results.data::<String>().unwrap(),
+        format!(
+            "{:?}",
+            vec![
+                Err::<(), _>(SendError::ConnectionRefused),
+                Err(SendError::NodeUnreachable)
+            ]
+        )
+    );
+}
+
+#[derive(Clone)]
+struct EchoProcess {}
+
+impl Process for EchoProcess {
+    fn on_local_message(&mut self, msg: Message, ctx: Context) {
+        ctx.send_local(msg);
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {
+        unreachable!()
+    }
+
+    fn on_message(&mut self, _msg: Message, _from: Address, _ctx: Context) {
+        unreachable!()
+    }
+}
+
+#[test]
+fn admin_service_works() {
+    let mut node = RealNode::new("127.0.0.1", 10115, "/tmp/");
+    node.enable_admin();
+    let mut io = node.add_process(EchoProcess {}, "echo".to_owned());
+    let (result_sender, mut result_receiver) = oneshot::channel();
+    node.spawn(async move {
+        // Wait until node starts listening.
+        sleep(Duration::from_millis(100)).await;
+        let mut client = AdminClient::connect("127.0.0.1", 10115).await.unwrap();
+
+        let processes = client.list_processes().await.unwrap();
+        client.send_local("echo", "hello".into()).await.unwrap();
+        let mut outputs = Vec::new();
+        while outputs.is_empty() {
+            sleep(Duration::from_millis(10)).await;
+            outputs = client.read_local("echo").await.unwrap();
+        }
+        let missing = client.send_local("missing", "hello".into()).await;
+        let stats = client.stats().await.unwrap();
+        let _ = result_sender.send((processes, outputs, missing, stats));
+
+        // Node stops when process is stopped, so task can be aborted before response.
+        let _ = client.stop_process("echo").await;
+    });
+    node.run();
+
+    let (processes, outputs, missing, stats) = result_receiver.try_recv().unwrap();
+    assert_eq!(processes, vec!["echo".to_owned()]);
+    assert_eq!(outputs, vec![Message::from("hello")]);
+    assert_eq!(
+        missing,
+        Err(AdminError::ProcessNotFound("missing".to_owned()))
+    );
+    assert_eq!(stats.processes, 1);
+    assert_eq!(stats.local_messages_received, 1);
+    assert_eq!(stats.local_messages_sent, 1);
+
+    // Local messages are still delivered to the user.
+    assert_eq!(io.receiver.try_recv().unwrap(), Message::from("hello"));
+}
+
+#[test]
+fn admin_service_is_disabled_by_default() {
+    let mut node = RealNode::new("127.0.0.1", 10116, "/tmp/");
+    node.add_process(EchoProcess {}, "echo".to_owned());
+    let shutdown = node.shutdown_handle();
+    let (result_sender, mut result_receiver) = oneshot::channel();
+    node.spawn(async move {
+        // Wait until node starts listening.
+        sleep(Duration::from_millis(100)).await;
+        let mut client = AdminClient::connect("127.0.0.1", 10116).await.unwrap();
+        let _ = result_sender.send(client.list_processes().await);
+        shutdown.shutdown();
+    });
+    node.run();
+
+    assert_eq!(
+        result_receiver.try_recv().unwrap(),
+        Err(AdminError::Disabled)
+    );
+}
+
+struct Broadcaster {
+    receivers: Vec<Address>,
+}
+
+impl Process for Broadcaster {
+    fn on_local_message(&mut self, msg: Message, ctx: Context) {
+        for receiver in self.receivers.iter() {
+            ctx.send(msg.clone(), receiver.clone());
+        }
+        ctx.stop();
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {
+        unreachable!()
+    }
+
+    fn on_message(&mut self, _msg: Message, _from: Address, _ctx: Context) {
+        unreachable!()
+    }
+}
+
+#[test]
+fn cluster_spec_builds_real_nodes() {
+    let spec = ClusterSpec::from_json(
+        r#"{
+            "nodes": [
+                {
+                    "name": "sender",
+                    "host": "127.0.0.1",
+                    "port": 10117,
+                    "storage_mount": "/tmp/dsbuild-cluster/sender",
+                    "processes": [{ "name": "broadcaster", "kind": "broadcaster" }]
+                },
+                {
+                    "name": "receiver",
+                    "host": "127.0.0.1",
+                    "port": 10118,
+                    "storage_mount": "/tmp/dsbuild-cluster/receiver",
+                    "processes": [{ "name": "process", "kind": "receiver" }]
+                }
+            ]
+        }"#,
+    )
+    .unwrap();
+
+    let mut factory = ProcessFactory::new();
+    factory
+        .register("broadcaster", |_: (), _, spec: &ClusterSpec| Broadcaster {
+            receivers: spec.addresses("receiver"),
+        })
+        .register("receiver", |_: (), _, _| LocalProcess {});
+
+    let mut nodes = spec.build_real_nodes(&factory).unwrap();
+    let mut receiver = nodes.pop().unwrap();
+    let mut sender = nodes.pop().unwrap();
+    assert_eq!(sender.name, "sender");
+    assert_eq!(receiver.name, "receiver");
+
+    let mut io = receiver.processes.remove("process").unwrap();
+    let receiver_handle = std::thread::spawn(move || receiver.node.run());
+
+    let sender_io = sender.processes.remove("broadcaster").unwrap();
+    sender.node.spawn(async move {
+        // Wait until receiver starts listening.
+        sleep(Duration::from_millis(100)).await;
+        sender_io.sender.send("hello".into()).await.unwrap();
+    });
+    sender.node.run();
+
+    receiver_handle.join().unwrap();
+    assert_eq!(io.receiver.try_recv().unwrap(), Message::from("hello"));
+}
+
+fn local_cluster_factory() -> ProcessFactory {
+    let mut factory = ProcessFactory::new();
+    factory.register("echo", |_: (), _, _| EchoProcess {});
+    factory
+}
+
+/// Entry point of the nodes launched by [`local_cluster_works`] test.
+#[test]
+#[ignore = "launched by local_cluster_works"]
+fn local_cluster_node() {
+    if std::env::var("DSBUILD_CLUSTER_NODE").is_err() {
+        return;
+    }
+    LocalCluster::run_node(&local_cluster_factory()).unwrap();
+}
+
+fn echo_with_admin(cluster: &LocalCluster, node: &str) -> Result<Vec<Message>, AdminError> {
+    let node = cluster.spec().node(node).unwrap();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let mut client = AdminClient::connect(&node.host, node.port).await?;
+        client.send_local("echo", "hello".into()).await?;
+        for _ in 0..100 {
+            let messages = client.read_local("echo").await?;
+            if !messages.is_empty() {
+                return Ok(messages);
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        Ok(Vec::new())
+    })
+}
+
+#[test]
+fn local_cluster_works() {
+    let socket = std::env::temp_dir().join(format!("dsbuild-cluster-{}.sock", std::process::id()));
+    let spec = ClusterSpec::from_json(&format!(
+        r#"{{
+            "nodes": [
+                {{
+                    "name": "a",
+                    "host": "127.0.0.1",
+                    "port": 1,
+                    "processes": [{{ "name": "echo", "kind": "echo" }}]
+                }},
+                {{
+                    "name": "b",
+                    "host": "127.0.0.1",
+                    "port": 2,
+                    "processes": [{{ "name": "echo", "kind": "echo" }}]
+                }},
+                {{
+                    "name": "u",
+                    "host": "unix:{}",
+                    "port": 3,
+                    "processes": [{{ "name": "echo", "kind": "echo" }}]
+                }}
+            ]
+        }}"#,
+        socket.display()
+    ))
+    .unwrap();
+
+    let program = std::env::current_exe().unwrap();
+    let args = [
+        "--exact",
+        "real::tests::local_cluster_node",
+        "--ignored",
+        "--nocapture",
+    ];
+    let mut cluster = LocalCluster::start(&spec, &program, &args).unwrap();
+    assert!(cluster.wait_until_ready(10.0));
+    for node in cluster.spec().nodes.iter() {
+        assert!(node.port > 2);
+        assert!(Path::new(node.storage_mount.as_ref().unwrap()).is_dir());
+    }
+
+    let hello = vec![Message::from("hello")];
+    assert_eq!(echo_with_admin(&cluster, "a"), Ok(hello.clone()));
+    assert_eq!(echo_with_admin(&cluster, "u"), Ok(hello.clone()));
+
+    // Killed node is unavailable, while other nodes are still running.
+    cluster.kill_node("a").unwrap();
+    assert!(!cluster.is_running("a"));
+    assert!(matches!(
+        echo_with_admin(&cluster, "a"),
+        Err(AdminError::Unavailable(_))
+    ));
+    assert_eq!(echo_with_admin(&cluster, "b"), Ok(hello.clone()));
+
+    // Port taken while node is down is replaced on restart.
+    let port = cluster.spec().node("a").unwrap().port;
+    let taken = TcpListener::bind(("127.0.0.1", port)).unwrap();
+    cluster.restart_node("a").unwrap();
+    assert!(cluster.wait_until_ready(10.0));
+    assert!(cluster.is_running("a"));
+    assert_ne!(cluster.spec().node("a").unwrap().port, port);
+    assert_eq!(echo_with_admin(&cluster, "a"), Ok(hello.clone()));
+    drop(taken);
+
+    // Node keeps its port, if it is free.
+    let port = cluster.spec().node("a").unwrap().port;
+    cluster.restart_node("a").unwrap();
+    assert_eq!(cluster.spec().node("a").unwrap().port, port);
+    assert_eq!(echo_with_admin(&cluster, "a"), Ok(hello));
+    assert_eq!(cluster.spec().node("u").unwrap().port, 3);
+
+    let b = cluster.spec().node("b").unwrap();
+    let bound = format!("dsbuild: node is bound to 127.0.0.1:{}", b.port);
+    assert!(cluster.logs("b").iter().any(|line| line.ends_with(&bound)));
+    assert_eq!(
+        cluster.kill_node("c"),
+        Err(ClusterError::UnknownNode("c".to_owned()))
+    );
+}
@@ -3,22 +3,26 @@
 use std::collections::HashMap;
 
 use tokio::sync::mpsc::Sender;
-use tokio::task::JoinHandle;
+use tokio::task::AbortHandle;
 use tokio::time::{sleep, Duration};
 
+use super::tasks::TaskRegistry;
+
 /// Responsible for settings and cancelling timers.
 /// Not thread-safe.
 pub(crate) struct TimerManager {
-    pending_timers: HashMap<String, JoinHandle<()>>,
+    pending_timers: HashMap<String, AbortHandle>,
     sender: Sender<String>,
+    tasks: TaskRegistry,
 }
 
 impl TimerManager {
     // Create new timer manager.
-    pub fn new(sender: Sender<String>) -> Self {
+    pub fn new(sender: Sender<String>, tasks: TaskRegistry) -> Self {
         Self {
             pending_timers: HashMap::default(),
             sender,
+            tasks,
         }
     }
 
@@ -33,11 +37,14 @@ impl TimerManager {
         }
         let name_clone = name.clone();
         let sender = self.sender.clone();
-        let handler = tokio::spawn(async move {
+        let handler = self.tasks.spawn(async move {
             sleep(Duration::from_secs_f64(delay)).await;
-            sender.send(name).await.unwrap();
+            // Process could be stopped while timer is firing.
+            let _ = sender.send(name).await;
         });
-        self.pending_timers.insert(name_clone, handler);
+        if let Some(handler) = handler {
+            self.pending_timers.insert(name_clone, handler);
+        }
     }
 
     /// Cancel timer with specified name.
@@ -47,7 +54,6 @@ impl TimerManager {
         }
     }
 
-    #[allow(dead_code)]
     /// Cancel all pending timers.
     pub fn cancel_all_timers(&mut self) {
         for task in self.pending_timers.values_mut() {
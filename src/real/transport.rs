@@ -0,0 +1,72 @@
+//! Definition of transports, which are used by nodes to deliver messages.
+
+use std::path::PathBuf;
+
+/// Prefix of the [host][crate::Address::host], which specifies
+/// path to the Unix domain socket of the node.
+pub const UNIX_SCHEME: &str = "unix:";
+
+/// Represents transport endpoint of the node.
+///
+/// Endpoint is determined by the node [host][crate::Address::host] and
+/// [port][crate::Address::port]. If host starts with `unix:` prefix or
+/// is an absolute path, then node is reachable with the Unix domain socket located
+/// at the specified path, and port is not used to locate the node.
+/// Else, node is reachable over TCP with the specified host and port.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Transport {
+    /// TCP transport.
+    Tcp {
+        /// Host of the node.
+        host: String,
+        /// Port of the node.
+        port: u16,
+    },
+    /// Unix domain socket transport.
+    Unix {
+        /// Path to the socket file.
+        path: PathBuf,
+    },
+}
+
+impl Transport {
+    /// Returns transport, which corresponds to the specified host and port.
+    pub fn new(host: &str, port: u16) -> Self {
+        if let Some(path) = host.strip_prefix(UNIX_SCHEME) {
+            Self::Unix { path: path.into() }
+        } else if host.starts_with('/') {
+            Self::Unix { path: host.into() }
+        } else {
+            Self::Tcp {
+                host: host.to_owned(),
+                port,
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Settings of the transport, which are used by node to send and receive messages.
+#[derive(Clone, Debug)]
+pub(crate) struct TransportSettings {
+    /// Timeout in seconds for establishing connection with other node.
+    pub connect_timeout: Option<f64>,
+    /// Timeout in seconds for sending message to other node.
+    pub send_timeout: Option<f64>,
+    /// Specifies if `TCP_NODELAY` option must be set on TCP connections.
+    pub tcp_nodelay: bool,
+    /// Interval in seconds of TCP keepalive probes.
+    pub tcp_keepalive: Option<f64>,
+}
+
+impl Default for TransportSettings {
+    fn default() -> Self {
+        Self {
+            connect_timeout: None,
+            send_timeout: None,
+            tcp_nodelay: true,
+            tcp_keepalive: None,
+        }
+    }
+}
@@ -1,19 +1,28 @@
 //! Definition of virtual mode context.
 
-use std::{cell::RefCell, future::Future, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::hash_map::DefaultHasher,
+    future::Future,
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
 
 use crate::common::{
     fs::{File, FsResult},
+    membership::MembershipView,
     message::{Message, Tag},
     network::{SendError, SendResult},
     process::Address,
 };
-use dslab_async_mp::process::context::Context as DSLabContext;
+use dslab_async_mp::{process::context::Context as DSLabContext, storage::file::File as DSLabFile};
 
 use super::{
     fs::FileWrapper,
+    network::MessageAction,
     node::NodeManager,
     send_future::{SendFuture, Sf},
+    trace::TraceEventKind,
 };
 
 /// Represents context in virtual mode.
@@ -25,61 +34,303 @@ use super::{
 pub(crate) struct VirtualContext {
     pub dslab_ctx: DSLabContext,
     pub node_manager: Rc<RefCell<NodeManager>>,
+    pub membership: MembershipView,
+    pub address: Address,
 }
 
 impl VirtualContext {
+    /// Record event of the process in the trace of the simulation, if trace is enabled.
+    pub fn trace(&self, kind: impl FnOnce() -> TraceEventKind) {
+        self.node_manager
+            .borrow_mut()
+            .record_event(&self.address, self.time(), kind);
+    }
+
     /// Send local message.
     pub fn send_local(&self, message: Message) {
+        self.trace(|| TraceEventKind::local_message_sent(&message));
         self.dslab_ctx.send_local(message.into());
     }
 
     /// Set timer with specified name and delay.
     /// If such timer already exists, delay will be override.
     pub fn set_timer(&self, name: &str, delay: f64) {
-        self.dslab_ctx.set_timer(name, delay);
+        self.trace(|| TraceEventKind::TimerSet {
+            name: name.to_owned(),
+            delay,
+            once: false,
+        });
+        let delay = self.timer_delay(delay);
+        if !self.hold_timer(name, delay, false) {
+            self.dslab_ctx.set_timer(name, delay);
+        }
     }
 
     /// Set timer with specified name and delay.
     /// If such timer already exists, nothing happens.
     pub fn set_timer_once(&self, name: &str, delay: f64) {
-        self.dslab_ctx.set_timer_once(name, delay);
+        self.trace(|| TraceEventKind::TimerSet {
+            name: name.to_owned(),
+            delay,
+            once: true,
+        });
+        let delay = self.timer_delay(delay);
+        if !self.hold_timer(name, delay, true) {
+            self.dslab_ctx.set_timer_once(name, delay);
+        }
     }
 
     /// Cancel timer with specified name.
     pub fn cancel_timer(&self, name: &str) {
-        self.dslab_ctx.cancel_timer(name);
+        self.trace(|| TraceEventKind::TimerCancelled {
+            name: name.to_owned(),
+        });
+        match self.node_manager.borrow_mut().controller_mut() {
+            Some(controller) => controller.cancel_timer(&self.address, name),
+            None => self.dslab_ctx.cancel_timer(name),
+        }
+    }
+
+    /// Returns delay of virtual time, which corresponds to `delay` of the clock of the node.
+    fn timer_delay(&self, delay: f64) -> f64 {
+        self.node_manager.borrow().timer_delay(&self.address, delay)
+    }
+
+    /// Holds timer in the model checking mode, so it fires when model checker decides.
+    ///
+    /// Returns `false` if model checking mode is not enabled.
+    fn hold_timer(&self, name: &str, delay: f64, once: bool) -> bool {
+        let mut node_manager = self.node_manager.borrow_mut();
+        let Some(controller) = node_manager.controller_mut() else {
+            return false;
+        };
+        let ctx = self.dslab_ctx.clone();
+        let timer = name.to_owned();
+        controller.hold_timer(
+            &self.address,
+            name,
+            delay,
+            once,
+            Box::new(move || ctx.set_timer(&timer, 0.0)),
+        );
+        true
     }
 
     /// Send message to specified address.
-    pub fn send(&self, msg: Message, dst: Address) {
-        match self.node_manager.borrow().get_full_process_name(&dst) {
-            Ok(full_process_name) => {
-                self.dslab_ctx.send(msg.into(), &full_process_name);
-            }
+    pub fn send(&self, mut msg: Message, dst: Address) {
+        let full_process_name = self.node_manager.borrow().get_full_process_name(&dst);
+        let full_process_name = match full_process_name {
+            Ok(full_process_name) => full_process_name,
             Err(err) => {
                 log::warn!("Message not sent: {}", err);
+                self.trace(|| TraceEventKind::message_dropped(&dst, &msg));
+                return;
+            }
+        };
+        self.trace(|| TraceEventKind::message_sent(&dst, &msg, false));
+
+        let Some((copies, delay)) = self.intercept(&mut msg, &dst) else {
+            self.trace(|| TraceEventKind::message_dropped(&dst, &msg));
+            return;
+        };
+        if self.hold_message(&msg, &dst, &full_process_name, copies) {
+            return;
+        }
+        for _ in 1..copies {
+            self.send_over_link(msg.clone(), &dst, full_process_name.clone(), delay);
+        }
+        self.send_over_link(msg, &dst, full_process_name, delay);
+    }
+
+    /// Holds copies of the message in the model checking mode,
+    /// so they are delivered when model checker decides.
+    ///
+    /// Returns `false` if model checking mode is not enabled.
+    fn hold_message(
+        &self,
+        msg: &Message,
+        dst: &Address,
+        full_process_name: &str,
+        copies: usize,
+    ) -> bool {
+        let mut node_manager = self.node_manager.borrow_mut();
+        let Some(controller) = node_manager.controller_mut() else {
+            return false;
+        };
+        for _ in 0..copies {
+            let ctx = self.dslab_ctx.clone();
+            let copy = msg.clone();
+            let process_name = full_process_name.to_owned();
+            controller.hold_message(
+                &self.address,
+                dst,
+                msg,
+                Box::new(move || ctx.send(copy.into(), &process_name)),
+            );
+        }
+        true
+    }
+
+    /// Applies [message filter][crate::Sim::set_message_filter] and network duplication
+    /// to the message sent to `dst`.
+    ///
+    /// Returns number of copies of the message to send and additional delay of them,
+    /// or `None` if message is dropped by the filter.
+    fn intercept(&self, msg: &mut Message, dst: &Address) -> Option<(usize, f64)> {
+        // Filter is called without borrow of the node manager,
+        // so it can use the simulation, e.g. send messages.
+        let filter = self.node_manager.borrow_mut().take_message_filter();
+        let action = match filter {
+            Some(mut filter) => {
+                let action = filter(&self.address, dst, msg);
+                self.node_manager.borrow_mut().return_message_filter(filter);
+                action
+            }
+            None => MessageAction::Deliver,
+        };
+
+        let mut node_manager = self.node_manager.borrow_mut();
+        let (mut copies, delay) = match action {
+            MessageAction::Deliver => (1, 0.0),
+            MessageAction::Drop => return None,
+            MessageAction::Delay(delay) => (1, delay),
+            MessageAction::Duplicate => (2, 0.0),
+        };
+        if node_manager.duplicate_message(&self.address, dst) {
+            copies += 1;
+        }
+        Some((copies, delay))
+    }
+
+    /// Send unreliable message over the link to `dst` after the additional `delay`.
+    fn send_over_link(&self, msg: Message, dst: &Address, full_process_name: String, delay: f64) {
+        let (msg, link_delay) = {
+            let mut node_manager = self.node_manager.borrow_mut();
+            let msg = node_manager.corrupt_message(&self.address, dst, msg);
+            let link_delay = node_manager.link_delay(&self.address, dst, false, self.time());
+            (msg, link_delay)
+        };
+        match link_delay.map(|link_delay| link_delay + delay) {
+            // Message is lost on the link.
+            None => self.trace(|| TraceEventKind::message_dropped(dst, &msg)),
+            Some(delay) if delay > 0.0 => {
+                let ctx = self.dslab_ctx.clone();
+                let node_manager = self.node_manager.clone();
+                let src = self.address.clone();
+                let dst = dst.clone();
+                self.dslab_ctx.spawn(async move {
+                    ctx.sleep(delay).await;
+                    // Link can be blocked while message is held.
+                    let mut node_manager = node_manager.borrow_mut();
+                    if node_manager.is_link_blocked(&src, &dst, ctx.time()) {
+                        node_manager.record_event(&src, ctx.time(), || {
+                            TraceEventKind::message_dropped(&dst, &msg)
+                        });
+                        return;
+                    }
+                    drop(node_manager);
+                    ctx.send(msg.into(), &full_process_name);
+                });
             }
+            Some(_) => self.dslab_ctx.send(msg.into(), &full_process_name),
+        }
+    }
+
+    /// Returns delay of the reliable message sent over the link to `dst` after the
+    /// additional `delay`, or `None` if message is not delivered because of isolation.
+    fn reliable_link_delay(&self, dst: &Address, delay: f64) -> Option<f64> {
+        self.node_manager
+            .borrow_mut()
+            .link_delay(&self.address, dst, true, self.time())
+            .map(|link_delay| link_delay + delay)
+    }
+
+    /// Prepares reliable message sent to `dst`: applies message filter,
+    /// sends duplicates of the message and records it in the trace.
+    ///
+    /// Returns full name of the receiver process and delay of the message on the link,
+    /// which is `None` if message is not delivered.
+    fn prepare_reliable_send(
+        &self,
+        msg: &mut Message,
+        tag: Option<Tag>,
+        dst: &Address,
+        timeout: f64,
+    ) -> (Result<String, String>, Option<f64>) {
+        let process_name = self.node_manager.borrow().get_full_process_name(dst);
+        self.trace(|| TraceEventKind::message_sent(dst, msg, true));
+
+        let link_delay = match &process_name {
+            Ok(process_name) => self.intercept(msg, dst).and_then(|(copies, delay)| {
+                for _ in 1..copies {
+                    self.send_duplicate(msg, tag, dst, process_name, delay, timeout);
+                }
+                self.reliable_link_delay(dst, delay)
+            }),
+            Err(_) => None,
+        };
+        if link_delay.is_none() {
+            self.trace(|| TraceEventKind::message_dropped(dst, msg));
         }
+
+        (process_name, link_delay)
+    }
+
+    /// Send duplicate of the reliable message to `dst` after the additional `delay`.
+    ///
+    /// Result of the duplicate delivery is ignored, so the sender is not affected.
+    fn send_duplicate(
+        &self,
+        msg: &Message,
+        tag: Option<Tag>,
+        dst: &Address,
+        process_name: &str,
+        delay: f64,
+        timeout: f64,
+    ) {
+        let link_delay = self.reliable_link_delay(dst, delay);
+        let msg = msg.clone();
+        let process_name = process_name.to_owned();
+        let ctx = self.dslab_ctx.clone();
+        self.dslab_ctx.spawn(async move {
+            if pass_link(&ctx, link_delay, timeout).await.is_err() {
+                return;
+            }
+            let _ = match tag {
+                Some(tag) => {
+                    ctx.send_with_tag(msg.into(), tag, &process_name, timeout)
+                        .await
+                }
+                None => ctx.send_with_ack(msg.into(), &process_name, timeout).await,
+            };
+        });
     }
 
     /// Send network message reliable with specified timeout.
-    /// It is guaranteed that message will be delivered exactly once and without corruption.
+    /// It is guaranteed that message will be delivered without corruption,
+    /// but it can be delivered more than once if network duplicates messages.
     ///
     /// # Returns
     ///
     /// - Error if message was not delivered with specified timeout.
     /// - Ok if message was delivered
-    pub fn send_with_ack(&self, msg: Message, dst: Address, timeout: f64) -> Sf<SendResult<()>> {
-        let process_name = self.node_manager.borrow().get_full_process_name(&dst);
+    pub fn send_with_ack(
+        &self,
+        mut msg: Message,
+        dst: Address,
+        timeout: f64,
+    ) -> Sf<SendResult<()>> {
+        let (process_name, link_delay) = self.prepare_reliable_send(&mut msg, None, &dst, timeout);
 
         let ctx = self.dslab_ctx.clone();
         SendFuture::from_future(async move {
             if let Ok(process_name) = process_name {
+                let timeout = pass_link(&ctx, link_delay, timeout).await?;
                 Ok(ctx
                     .send_with_ack(msg.into(), &process_name, timeout)
                     .await?)
             } else {
-                Err(SendError::NotSent)
+                Err(SendError::ProcessNotFound)
             }
         })
     }
@@ -87,21 +338,23 @@ impl VirtualContext {
     /// See [`crate::common::context::Context::send_with_tag`].
     pub fn send_with_tag(
         &self,
-        msg: Message,
+        mut msg: Message,
         tag: Tag,
         to: Address,
         timeout: f64,
     ) -> Sf<SendResult<()>> {
-        let process_name = self.node_manager.borrow().get_full_process_name(&to);
+        let (process_name, link_delay) =
+            self.prepare_reliable_send(&mut msg, Some(tag), &to, timeout);
 
         let ctx = self.dslab_ctx.clone();
         SendFuture::from_future(async move {
             if let Ok(process_name) = process_name {
+                let timeout = pass_link(&ctx, link_delay, timeout).await?;
                 Ok(ctx
                     .send_with_tag(msg.into(), tag, &process_name, timeout)
                     .await?)
             } else {
-                Err(SendError::NotSent)
+                Err(SendError::ProcessNotFound)
             }
         })
     }
@@ -109,22 +362,24 @@ impl VirtualContext {
     /// See [`crate::common::context::Context::send_recv_with_tag`].
     pub fn send_recv_with_tag(
         &self,
-        msg: Message,
+        mut msg: Message,
         tag: Tag,
         to: Address,
         timeout: f64,
     ) -> Sf<SendResult<Message>> {
-        let process_name = self.node_manager.borrow().get_full_process_name(&to);
+        let (process_name, link_delay) =
+            self.prepare_reliable_send(&mut msg, Some(tag), &to, timeout);
 
         let ctx = self.dslab_ctx.clone();
         SendFuture::from_future(async move {
             if let Ok(process_name) = process_name {
+                let timeout = pass_link(&ctx, link_delay, timeout).await?;
                 Ok(ctx
                     .send_recv_with_tag(msg.into(), tag, &process_name, timeout)
                     .await
                     .map(|msg| msg.into())?)
             } else {
-                Err(SendError::NotSent)
+                Err(SendError::ProcessNotFound)
             }
         })
     }
@@ -142,10 +397,11 @@ impl VirtualContext {
     /// Create file with specified name.
     pub fn create_file<'a>(&'a self, name: &'a str) -> Sf<'a, FsResult<File>> {
         let future = async move {
-            Ok(self
-                .dslab_ctx
-                .create_file(name)
-                .map(|file| File::from_sim(FileWrapper { file }))?)
+            let file = self.dslab_ctx.create_file(name)?;
+            self.trace(|| TraceEventKind::FileCreated {
+                file: name.to_owned(),
+            });
+            Ok(File::from_sim(self.wrap_file(file, name)))
         };
 
         SendFuture::from_future(future)
@@ -153,7 +409,13 @@ impl VirtualContext {
 
     /// Delete file with specified name.
     pub fn delete_file<'a>(&'a self, name: &'a str) -> Sf<'a, FsResult<()>> {
-        let future = async move { Ok(self.dslab_ctx.delete_file(name)?) };
+        let future = async move {
+            self.dslab_ctx.delete_file(name)?;
+            self.trace(|| TraceEventKind::FileDeleted {
+                file: name.to_owned(),
+            });
+            Ok(())
+        };
 
         SendFuture::from_future(future)
     }
@@ -166,17 +428,66 @@ impl VirtualContext {
     /// Open file.
     pub fn open_file<'a>(&'a self, name: &'a str) -> Sf<'a, FsResult<File>> {
         SendFuture::from_future(async move {
-            Ok(self
-                .dslab_ctx
-                .open_file(name)
-                .map(|file| File::from_sim(FileWrapper { file }))?)
+            let file = self.dslab_ctx.open_file(name)?;
+            self.trace(|| TraceEventKind::FileOpened {
+                file: name.to_owned(),
+            });
+            Ok(File::from_sim(self.wrap_file(file, name)))
         })
     }
 
+    /// Wrap opened or created file, so operations with it are recorded in the trace.
+    fn wrap_file(&self, file: DSLabFile, name: &str) -> FileWrapper {
+        FileWrapper {
+            file,
+            name: name.to_owned(),
+            ctx: self.clone(),
+        }
+    }
+
     /// Get current simulation time.
     pub fn time(&self) -> f64 {
         self.dslab_ctx.time()
     }
+
+    /// Sleep for the specified number of seconds of virtual time.
+    ///
+    /// Duration is measured by the clock of the node.
+    pub fn sleep(&self, duration: f64) -> Sf<'_, ()> {
+        SendFuture::from_future(self.dslab_ctx.sleep(self.timer_delay(duration)))
+    }
+
+    /// Returns random seed, which is determined by the seed of the simulation,
+    /// current simulation time, address of the process and specified address
+    /// to keep simulation deterministic.
+    pub fn random_seed(&self, address: &Address) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.node_manager.borrow().seed().hash(&mut hasher);
+        self.address.hash(&mut hasher);
+        address.hash(&mut hasher);
+        self.time().to_bits().hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Holds reliable message on the link for the specified delay.
+///
+/// Returns the rest of the timeout or error if timeout expires while message is on the link
+/// or message is not delivered by the link.
+async fn pass_link(ctx: &DSLabContext, delay: Option<f64>, timeout: f64) -> SendResult<f64> {
+    let Some(delay) = delay else {
+        ctx.sleep(timeout).await;
+        return Err(SendError::Timeout);
+    };
+    if delay <= 0.0 {
+        return Ok(timeout);
+    }
+    if delay >= timeout {
+        ctx.sleep(timeout).await;
+        return Err(SendError::Timeout);
+    }
+    ctx.sleep(delay).await;
+    Ok(timeout - delay)
 }
 
 /// [`VirtualContext`] wont be shared between threads,
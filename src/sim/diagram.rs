@@ -0,0 +1,521 @@
+//! Definition of sequence diagrams, which are built from the [trace][crate::Sim::enable_trace]
+//! of the simulation.
+
+use std::{collections::HashSet, fmt::Write};
+
+use crate::common::process::Address;
+
+use super::trace::{TraceEvent, TraceEventKind};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Represents format of the [sequence diagram][crate::Sim::sequence_diagram].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagramFormat {
+    /// [Mermaid](https://mermaid.js.org/syntax/sequenceDiagram.html) sequence diagram.
+    Mermaid,
+    /// [PlantUML](https://plantuml.com/sequence-diagram) sequence diagram.
+    PlantUml,
+    /// Self-contained HTML page with the timeline of the message flows.
+    Html,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Allows to select events, which are shown on the
+/// [sequence diagram][crate::Sim::sequence_diagram].
+///
+/// By default all events are shown.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiagramFilter {
+    /// Nodes to show, `None` means all nodes.
+    pub nodes: Option<HashSet<String>>,
+    /// Processes to show, `None` means all processes.
+    pub processes: Option<HashSet<String>>,
+    /// Tips of messages to show, `None` means all tips.
+    pub tips: Option<HashSet<String>>,
+    /// Events which happened before this time are not shown.
+    pub from_time: Option<f64>,
+    /// Events which happened after this time are not shown.
+    pub to_time: Option<f64>,
+}
+
+impl DiagramFilter {
+    /// Creates filter which shows all events.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set nodes to show.
+    ///
+    /// Message is shown only if both its sender and receiver are located on these nodes.
+    pub fn with_nodes(mut self, nodes: &[&str]) -> Self {
+        self.nodes = Some(nodes.iter().map(|node| node.to_string()).collect());
+        self
+    }
+
+    /// Set processes to show.
+    ///
+    /// Message is shown only if both its sender and receiver are among these processes.
+    pub fn with_processes(mut self, processes: &[&str]) -> Self {
+        self.processes = Some(processes.iter().map(|proc| proc.to_string()).collect());
+        self
+    }
+
+    /// Set tips of messages to show.
+    pub fn with_tips(mut self, tips: &[&str]) -> Self {
+        self.tips = Some(tips.iter().map(|tip| tip.to_string()).collect());
+        self
+    }
+
+    /// Set time window of events to show.
+    pub fn with_time_window(mut self, from_time: f64, to_time: f64) -> Self {
+        assert!(from_time <= to_time, "Time window must not be empty.");
+        self.from_time = Some(from_time);
+        self.to_time = Some(to_time);
+        self
+    }
+
+    fn accepts_time(&self, time: f64) -> bool {
+        self.from_time.is_none_or(|from| time >= from) && self.to_time.is_none_or(|to| time <= to)
+    }
+
+    fn accepts_tip(&self, tip: &str) -> bool {
+        self.tips.as_ref().is_none_or(|tips| tips.contains(tip))
+    }
+
+    fn accepts_message(&self, tip: &str, from: &Participant, to: &Participant) -> bool {
+        self.accepts_tip(tip) && self.accepts_participant(from) && self.accepts_participant(to)
+    }
+
+    fn accepts_local_message(&self, tip: &str, process: &Participant) -> bool {
+        self.accepts_tip(tip) && self.accepts_participant(process)
+    }
+
+    fn accepts_participant(&self, participant: &Participant) -> bool {
+        self.nodes
+            .as_ref()
+            .is_none_or(|nodes| nodes.contains(&participant.node))
+            && self
+                .processes
+                .as_ref()
+                .is_none_or(|procs| procs.contains(&participant.process))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, PartialEq)]
+struct Participant {
+    node: String,
+    process: String,
+}
+
+impl Participant {
+    fn label(&self) -> String {
+        format!("{}/{}", self.node, self.process)
+    }
+}
+
+enum Item {
+    Message {
+        time: f64,
+        from: usize,
+        to: usize,
+        tip: String,
+        data: String,
+        dropped: bool,
+    },
+    Note {
+        time: f64,
+        first: usize,
+        last: usize,
+        text: String,
+    },
+}
+
+/// Sequence diagram, which is built from the trace events.
+pub(crate) struct Diagram {
+    participants: Vec<Participant>,
+    items: Vec<Item>,
+}
+
+impl Diagram {
+    /// Builds diagram from the trace events.
+    ///
+    /// Messages, drops of messages, local messages and events of nodes are shown,
+    /// while timers and file operations are not. Function `node_of` returns name
+    /// of the node for the process address.
+    pub fn build(
+        events: &[TraceEvent],
+        filter: &DiagramFilter,
+        node_of: impl Fn(&Address) -> Option<String>,
+    ) -> Self {
+        let mut diagram = Self {
+            participants: Vec::new(),
+            items: Vec::new(),
+        };
+
+        let remote = |address: &Address| Participant {
+            node: node_of(address).unwrap_or_else(|| format!("{}:{}", address.host, address.port)),
+            process: address.process_name.clone(),
+        };
+
+        for event in events
+            .iter()
+            .filter(|event| filter.accepts_time(event.time))
+        {
+            let local = event.process.as_ref().map(|process| Participant {
+                node: event.node.clone(),
+                process: process.clone(),
+            });
+            match (&event.kind, local) {
+                (TraceEventKind::MessageReceived { from, tip, data }, Some(receiver))
+                    if filter.accepts_message(tip, &remote(from), &receiver) =>
+                {
+                    diagram.add_message(event.time, remote(from), receiver, tip, data, false)
+                }
+                (TraceEventKind::MessageDropped { to, tip, data }, Some(sender))
+                    if filter.accepts_message(tip, &sender, &remote(to)) =>
+                {
+                    diagram.add_message(event.time, sender, remote(to), tip, data, true)
+                }
+                (TraceEventKind::LocalMessageReceived { tip, .. }, Some(process))
+                    if filter.accepts_local_message(tip, &process) =>
+                {
+                    diagram.add_local_note(event.time, process, tip, "local in")
+                }
+                (TraceEventKind::LocalMessageSent { tip, .. }, Some(process))
+                    if filter.accepts_local_message(tip, &process) =>
+                {
+                    diagram.add_local_note(event.time, process, tip, "local out")
+                }
+                (TraceEventKind::NodeCrashed, None) => {
+                    diagram.add_node_note(event.time, &event.node, "crashed")
+                }
+                (TraceEventKind::NodeRecovered, None) => {
+                    diagram.add_node_note(event.time, &event.node, "recovered")
+                }
+                (TraceEventKind::NodeShutdown, None) => {
+                    diagram.add_node_note(event.time, &event.node, "shut down")
+                }
+                (TraceEventKind::NodeRerun, None) => {
+                    diagram.add_node_note(event.time, &event.node, "rerun")
+                }
+                _ => {}
+            }
+        }
+
+        diagram
+    }
+
+    fn add_message(
+        &mut self,
+        time: f64,
+        from: Participant,
+        to: Participant,
+        tip: &str,
+        data: &str,
+        dropped: bool,
+    ) {
+        let from = self.participant(from);
+        let to = self.participant(to);
+        self.items.push(Item::Message {
+            time,
+            from,
+            to,
+            tip: tip.to_owned(),
+            data: data.to_owned(),
+            dropped,
+        });
+    }
+
+    fn add_local_note(&mut self, time: f64, process: Participant, tip: &str, direction: &str) {
+        let process = self.participant(process);
+        self.items.push(Item::Note {
+            time,
+            first: process,
+            last: process,
+            text: format!("{}: {}", direction, tip),
+        });
+    }
+
+    /// Node events are shown over the processes of the node,
+    /// which already appeared on the diagram.
+    fn add_node_note(&mut self, time: f64, node: &str, what: &str) {
+        let mut processes = self
+            .participants
+            .iter()
+            .enumerate()
+            .filter(|(_, participant)| participant.node == node)
+            .map(|(id, _)| id);
+        if let Some(first) = processes.next() {
+            let last = processes.next_back().unwrap_or(first);
+            self.items.push(Item::Note {
+                time,
+                first,
+                last,
+                text: format!("{} {}", node, what),
+            });
+        }
+    }
+
+    fn participant(&mut self, participant: Participant) -> usize {
+        self.participants
+            .iter()
+            .position(|p| *p == participant)
+            .unwrap_or_else(|| {
+                self.participants.push(participant);
+                self.participants.len() - 1
+            })
+    }
+
+    /// Renders diagram in the specified format.
+    pub fn render(&self, format: DiagramFormat) -> String {
+        match format {
+            DiagramFormat::Mermaid => self.render_mermaid(),
+            DiagramFormat::PlantUml => self.render_plantuml(),
+            DiagramFormat::Html => self.render_html(),
+        }
+    }
+
+    fn render_mermaid(&self) -> String {
+        let mut out = String::from("sequenceDiagram\n");
+        for (id, participant) in self.participants.iter().enumerate() {
+            let label = escape_mermaid(&participant.label()).replace(':', "#58;");
+            writeln!(out, "    participant p{} as {}", id, label).unwrap();
+        }
+        for item in self.items.iter() {
+            match item {
+                Item::Message {
+                    time,
+                    from,
+                    to,
+                    tip,
+                    dropped,
+                    ..
+                } => {
+                    let arrow = if *dropped { "-x" } else { "->>" };
+                    let label = escape_mermaid(&message_label(tip, *time, *dropped));
+                    writeln!(out, "    p{}{}p{}: {}", from, arrow, to, label).unwrap();
+                }
+                Item::Note {
+                    time,
+                    first,
+                    last,
+                    text,
+                } => {
+                    let label = escape_mermaid(&format!("{} @ {:.3}", text, time));
+                    writeln!(out, "    Note over p{},p{}: {}", first, last, label).unwrap();
+                }
+            }
+        }
+        out
+    }
+
+    fn render_plantuml(&self) -> String {
+        let mut out = String::from("@startuml\n");
+        for (id, participant) in self.participants.iter().enumerate() {
+            let label = escape_plantuml(&participant.label());
+            writeln!(out, "participant \"{}\" as p{}", label, id).unwrap();
+        }
+        for item in self.items.iter() {
+            match item {
+                Item::Message {
+                    time,
+                    from,
+                    to,
+                    tip,
+                    dropped,
+                    ..
+                } => {
+                    let arrow = if *dropped { "->x" } else { "->" };
+                    let label = escape_plantuml(&message_label(tip, *time, *dropped));
+                    writeln!(out, "p{} {} p{} : {}", from, arrow, to, label).unwrap();
+                }
+                Item::Note {
+                    time,
+                    first,
+                    last,
+                    text,
+                } => {
+                    let label = escape_plantuml(&format!("{} @ {:.3}", text, time));
+                    writeln!(out, "note over p{}, p{} : {}", first, last, label).unwrap();
+                }
+            }
+        }
+        out.push_str("@enduml\n");
+        out
+    }
+
+    fn render_html(&self) -> String {
+        const LEFT: usize = 100;
+        const COLUMN: usize = 180;
+        const TOP: usize = 50;
+        const ROW: usize = 30;
+
+        let x = |id: usize| LEFT + COLUMN * id + COLUMN / 2;
+        let y = |row: usize| TOP + ROW * (row + 1);
+        let width = LEFT + COLUMN * self.participants.len().max(1);
+        let height = y(self.items.len()) + ROW;
+
+        let mut svg = String::new();
+        for (id, participant) in self.participants.iter().enumerate() {
+            writeln!(
+                svg,
+                "<text class=\"participant\" x=\"{}\" y=\"{}\">{}</text>\
+                 <line class=\"lifeline\" x1=\"{0}\" y1=\"{}\" x2=\"{0}\" y2=\"{}\"/>",
+                x(id),
+                TOP / 2,
+                escape_html(&participant.label()),
+                TOP,
+                height,
+            )
+            .unwrap();
+        }
+        for (row, item) in self.items.iter().enumerate() {
+            let y = y(row);
+            match item {
+                Item::Message {
+                    time,
+                    from,
+                    to,
+                    tip,
+                    data,
+                    dropped,
+                } => {
+                    let class = if *dropped { "dropped" } else { "message" };
+                    let (x1, x2) = (x(*from), x(*to));
+                    let path = if from == to {
+                        format!("M{} {} h40 v{} h-40", x1, y - ROW / 3, ROW / 3)
+                    } else {
+                        format!("M{} {} H{}", x1, y, x2)
+                    };
+                    writeln!(
+                        svg,
+                        "<g class=\"{}\"><title>{}</title>\
+                         <text class=\"time\" x=\"{}\" y=\"{}\">{:.3}</text>\
+                         <path d=\"{}\" marker-end=\"url(#{0})\"/>\
+                         <text class=\"label\" x=\"{}\" y=\"{}\">{}</text></g>",
+                        class,
+                        escape_html(data),
+                        LEFT - 10,
+                        y,
+                        time,
+                        path,
+                        (x1 + x2) / 2,
+                        y - 5,
+                        escape_html(tip),
+                    )
+                    .unwrap();
+                }
+                Item::Note {
+                    time,
+                    first,
+                    last,
+                    text,
+                } => {
+                    let (x1, x2) = (x(*first) - COLUMN / 2 + 10, x(*last) + COLUMN / 2 - 10);
+                    writeln!(
+                        svg,
+                        "<g class=\"note\">\
+                         <text class=\"time\" x=\"{}\" y=\"{}\">{:.3}</text>\
+                         <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>\
+                         <text class=\"label\" x=\"{}\" y=\"{}\">{}</text></g>",
+                        LEFT - 10,
+                        y,
+                        time,
+                        x1,
+                        y - ROW / 2 - 2,
+                        x2 - x1,
+                        ROW - 6,
+                        (x1 + x2) / 2,
+                        y + 2,
+                        escape_html(text),
+                    )
+                    .unwrap();
+                }
+            }
+        }
+
+        format!(
+            "<!DOCTYPE html>\n\
+             <html>\n<head>\n<meta charset=\"utf-8\">\n<title>Simulation timeline</title>\n\
+             <style>\n{}</style>\n</head>\n<body>\n\
+             <svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n\
+             <defs>\n{}{}</defs>\n{}</svg>\n</body>\n</html>\n",
+            HTML_STYLE,
+            width,
+            height,
+            html_marker("message", "#333"),
+            html_marker("dropped", "#c00"),
+            svg,
+        )
+    }
+}
+
+const HTML_STYLE: &str = "\
+body { font-family: sans-serif; font-size: 12px; }
+text { text-anchor: middle; }
+.participant { font-weight: bold; }
+.lifeline { stroke: #999; stroke-dasharray: 4 4; }
+.time { text-anchor: end; fill: #666; }
+.message path { stroke: #333; fill: none; }
+.dropped path { stroke: #c00; fill: none; stroke-dasharray: 6 3; }
+.dropped .label { fill: #c00; }
+.note rect { fill: #ffd; stroke: #cc9; }
+";
+
+fn html_marker(id: &str, color: &str) -> String {
+    format!(
+        "<marker id=\"{}\" markerWidth=\"10\" markerHeight=\"10\" refX=\"9\" refY=\"5\" \
+         orient=\"auto\"><path d=\"M0 0 L10 5 L0 10 z\" fill=\"{}\"/></marker>\n",
+        id, color
+    )
+}
+
+fn message_label(tip: &str, time: f64, dropped: bool) -> String {
+    if dropped {
+        format!("{} @ {:.3} (dropped)", tip, time)
+    } else {
+        format!("{} @ {:.3}", tip, time)
+    }
+}
+
+/// Replaces symbols, which separate statements or start entity codes in Mermaid,
+/// with entity codes. Line breaks are kept with `<br>`.
+fn escape_mermaid(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '#' => escaped.push_str("#35;"),
+            ';' => escaped.push_str("#59;"),
+            '\n' => escaped.push_str("<br>"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Replaces quotes, which end names of the participants in PlantUML,
+/// with unicode codes. Line breaks are kept with `\n`.
+fn escape_plantuml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("<U+0022>"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
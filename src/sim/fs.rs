@@ -2,19 +2,40 @@ use dslab_async_mp::storage::file::File as DSLabFile;
 
 use crate::FsResult;
 
-use super::send_future::{SendFuture, Sf};
+use super::{
+    context::VirtualContext,
+    send_future::{SendFuture, Sf},
+    trace::TraceEventKind,
+};
 
 pub struct FileWrapper {
     pub file: DSLabFile,
+    pub name: String,
+    pub ctx: VirtualContext,
 }
 
 impl FileWrapper {
     pub fn append<'a>(&'a mut self, data: &'a [u8]) -> Sf<'a, FsResult<u64>> {
-        SendFuture::from_future(async move { Ok(self.file.append(data).await?) })
+        SendFuture::from_future(async move {
+            let size = self.file.append(data).await?;
+            self.ctx.trace(|| TraceEventKind::FileAppended {
+                file: self.name.clone(),
+                size,
+            });
+            Ok(size)
+        })
     }
 
     pub fn read<'a>(&'a mut self, offset: u64, buf: &'a mut [u8]) -> Sf<'a, FsResult<u64>> {
-        SendFuture::from_future(async move { Ok(self.file.read(offset, buf).await?) })
+        SendFuture::from_future(async move {
+            let size = self.file.read(offset, buf).await?;
+            self.ctx.trace(|| TraceEventKind::FileRead {
+                file: self.name.clone(),
+                offset,
+                size,
+            });
+            Ok(size)
+        })
     }
 }
 
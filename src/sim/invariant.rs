@@ -0,0 +1,69 @@
+//! Definition of the view of the simulation, which is used by the [invariants][Sim::add_invariant].
+
+use std::any::Any;
+
+use crate::common::process::{Process, ProcessGuard, ProcessWrapper};
+
+use super::system::Sim;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Invariant of the simulation, which returns description of the violation in case of error.
+pub(crate) type Invariant = Box<dyn Fn(&SimView) -> Result<(), String>>;
+
+/// Provides read access to the state of the [simulation][Sim],
+/// which is passed to the [invariants][Sim::add_invariant].
+///
+/// Processes of the crashed nodes keep their last state until the node is recovered.
+pub struct SimView<'a> {
+    sim: &'a Sim,
+}
+
+impl<'a> SimView<'a> {
+    pub(crate) fn new(sim: &'a Sim) -> Self {
+        Self { sim }
+    }
+
+    /// Returns current time of the simulation in seconds.
+    pub fn time(&self) -> f64 {
+        self.sim.time()
+    }
+
+    /// Checks if the node is crashed.
+    pub fn is_node_crashed(&self, node: &str) -> bool {
+        self.sim.is_node_crashed(node)
+    }
+
+    /// Returns [guard][ProcessGuard] for read access to the process with name `process`
+    /// on the node with name `node`.
+    ///
+    /// Returns `None` if there is no such process or it has other type.
+    pub fn process<P: Process + 'static>(
+        &self,
+        node: &str,
+        process: &str,
+    ) -> Option<ProcessGuard<'a, P>> {
+        self.sim
+            .registered_processes()
+            .find(|(n, p, _)| *n == node && *p == process)
+            .and_then(|(_, _, wrapper)| read_process(wrapper))
+    }
+
+    /// Returns names of the nodes and processes together with [guards][ProcessGuard]
+    /// for read access to all processes of type `P` in the simulation.
+    pub fn processes<P: Process + 'static>(
+        &self,
+    ) -> impl Iterator<Item = (&'a str, &'a str, ProcessGuard<'a, P>)> {
+        self.sim
+            .registered_processes()
+            .filter_map(|(node, process, wrapper)| {
+                read_process(wrapper).map(|guard| (node, process, guard))
+            })
+    }
+}
+
+fn read_process<P: Process + 'static>(wrapper: &dyn Any) -> Option<ProcessGuard<'_, P>> {
+    wrapper
+        .downcast_ref::<ProcessWrapper<P>>()
+        .map(|wrapper| wrapper.read())
+}
@@ -0,0 +1,266 @@
+//! Definition of history of client operations and linearizability checker.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    hash::Hash,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Represents sequential specification of the object, which is used
+/// to [check][History::check] linearizability of the history.
+pub trait Model {
+    /// State of the object. States are compared to avoid exploring
+    /// the same linearizations twice.
+    type State: Clone + Eq + Hash;
+    /// Input of the operation.
+    type Input;
+    /// Output of the operation.
+    type Output;
+
+    /// Returns initial state of the object.
+    fn init(&self) -> Self::State;
+
+    /// Applies operation to the state.
+    ///
+    /// # Returns
+    ///
+    /// - New state if operation with the `input` can return `output` in the `state`.
+    /// - `None` otherwise.
+    ///
+    /// Output is `None` for operations, which were not completed,
+    /// in this case the effect of the operation must be applied.
+    fn step(
+        &self,
+        state: &Self::State,
+        input: &Self::Input,
+        output: Option<&Self::Output>,
+    ) -> Option<Self::State>;
+
+    /// Returns partition of the operation.
+    ///
+    /// Operations from different partitions must not affect each other
+    /// (e.g. operations on different keys of the key-value storage),
+    /// so every partition is checked independently, which is much faster.
+    /// By default all operations are placed into one partition.
+    fn partition(&self, _input: &Self::Input) -> String {
+        String::new()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Represents operation of the client recorded in the [history][History].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Operation<I, O> {
+    /// Identifier of the operation in the history.
+    pub id: usize,
+    /// Name of the client, which invoked operation.
+    pub client: String,
+    /// Input of the operation.
+    pub input: I,
+    /// Output of the operation or `None` if operation is not completed.
+    pub output: Option<O>,
+    /// Virtual time of the invocation.
+    pub invoke_time: f64,
+    /// Virtual time of the completion or `None` if operation is not completed.
+    pub complete_time: Option<f64>,
+}
+
+/// Represents history of the operations invoked by the clients.
+///
+/// Operations, which were not completed (e.g. because client did not receive response),
+/// could take effect at any time after invocation or not take effect at all.
+pub struct History<I, O> {
+    operations: Vec<Operation<I, O>>,
+}
+
+impl<I, O> Default for History<I, O> {
+    fn default() -> Self {
+        Self {
+            operations: Vec::new(),
+        }
+    }
+}
+
+impl<I, O> History<I, O> {
+    /// Creates empty history.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records invocation of the operation by the client at virtual `time`.
+    ///
+    /// Returns identifier of the operation, which is used to [complete][History::complete] it.
+    pub fn invoke(&mut self, client: &str, input: I, time: f64) -> usize {
+        let id = self.operations.len();
+        self.operations.push(Operation {
+            id,
+            client: client.to_owned(),
+            input,
+            output: None,
+            invoke_time: time,
+            complete_time: None,
+        });
+        id
+    }
+
+    /// Records completion of the operation at virtual `time`.
+    ///
+    /// Repeated completions of the operation are ignored.
+    ///
+    /// # Panics
+    ///
+    /// - If operation with such identifier was not invoked.
+    /// - If operation is completed before invocation.
+    pub fn complete(&mut self, id: usize, output: O, time: f64) {
+        let operation = &mut self.operations[id];
+        if operation.complete_time.is_some() {
+            return;
+        }
+        assert!(
+            time >= operation.invoke_time,
+            "Operation can not be completed before invocation"
+        );
+        operation.output = Some(output);
+        operation.complete_time = Some(time);
+    }
+
+    /// Returns recorded operations in order of invocation.
+    pub fn operations(&self) -> &[Operation<I, O>] {
+        &self.operations
+    }
+
+    /// Checks if history is linearizable according to the `model`.
+    ///
+    /// Operations are considered concurrent if invocation of one operation
+    /// happened at the same time or before completion of another one.
+    ///
+    /// # Returns
+    ///
+    /// - Ok if there is linearization of the history.
+    /// - Error with the longest found linearization of the partition otherwise.
+    pub fn check<M>(&self, model: &M) -> Result<(), LinearizabilityError>
+    where
+        M: Model<Input = I, Output = O>,
+    {
+        let mut partitions = BTreeMap::<String, Vec<&Operation<I, O>>>::new();
+        for operation in self.operations.iter() {
+            partitions
+                .entry(model.partition(&operation.input))
+                .or_default()
+                .push(operation);
+        }
+
+        partitions
+            .into_iter()
+            .try_for_each(|(partition, operations)| {
+                check_partition(model, &operations).map_err(|linearized| {
+                    let not_linearized = operations
+                        .iter()
+                        .filter(|operation| {
+                            operation.complete_time.is_some() && !linearized.contains(&operation.id)
+                        })
+                        .map(|operation| operation.id)
+                        .collect();
+                    LinearizabilityError {
+                        partition,
+                        linearized,
+                        not_linearized,
+                    }
+                })
+            })
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Represents error returned if [history][History] is not linearizable.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinearizabilityError {
+    /// Partition of the operations, which is not linearizable.
+    pub partition: String,
+    /// Identifiers of the operations from the longest found linearization in its order.
+    pub linearized: Vec<usize>,
+    /// Identifiers of the completed operations, which are not included into the linearization.
+    pub not_linearized: Vec<usize>,
+}
+
+impl fmt::Display for LinearizabilityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.partition.is_empty() {
+            write!(f, "Partition '{}': ", self.partition)?;
+        }
+        write!(
+            f,
+            "history is not linearizable, longest linearization is {:?}, operations {:?} can not be linearized after it",
+            self.linearized, self.not_linearized
+        )
+    }
+}
+
+impl std::error::Error for LinearizabilityError {}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Searches linearization of the operations with the depth-first search (Wing & Gong algorithm).
+/// Pairs of the linearized operations and the state of the model are memoized (Lowe's improvement).
+///
+/// Returns the longest found linearization in case of error.
+fn check_partition<M: Model>(
+    model: &M,
+    operations: &[&Operation<M::Input, M::Output>],
+) -> Result<(), Vec<usize>> {
+    let completed = operations
+        .iter()
+        .filter(|operation| operation.complete_time.is_some())
+        .count();
+
+    let mut longest = Vec::new();
+    let mut visited = HashSet::new();
+    // Linearized operations (indices in the partition), number of linearized completed operations
+    // and state of the model.
+    let mut stack = vec![(Vec::<usize>::new(), 0, model.init())];
+
+    while let Some((linearized, linearized_completed, state)) = stack.pop() {
+        if linearized_completed == completed {
+            return Ok(());
+        }
+
+        let mut is_linearized = vec![false; operations.len()];
+        linearized.iter().for_each(|i| is_linearized[*i] = true);
+        if !visited.insert((is_linearized.clone(), state.clone())) {
+            continue;
+        }
+        if linearized.len() > longest.len() {
+            longest.clone_from(&linearized);
+        }
+
+        // Operation can be linearized next if it was invoked before all
+        // not linearized operations were completed.
+        let deadline = operations
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !is_linearized[*i])
+            .filter_map(|(_, operation)| operation.complete_time)
+            .fold(f64::INFINITY, f64::min);
+
+        for (i, operation) in operations.iter().enumerate().rev() {
+            if is_linearized[i] || operation.invoke_time > deadline {
+                continue;
+            }
+            if let Some(next_state) =
+                model.step(&state, &operation.input, operation.output.as_ref())
+            {
+                let mut next = linearized.clone();
+                next.push(i);
+                let next_completed =
+                    linearized_completed + usize::from(operation.complete_time.is_some());
+                stack.push((next, next_completed, next_state));
+            }
+        }
+    }
+
+    Err(longest.into_iter().map(|i| operations[i].id).collect())
+}
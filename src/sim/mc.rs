@@ -0,0 +1,510 @@
+//! Definition of model checker, which explores orderings of the events of the simulation.
+
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap, VecDeque},
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use crate::common::{message::Message, process::Address};
+
+use super::system::Sim;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Represents event of the simulation, which is ordered by the [model checker][ModelChecker].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum McEvent {
+    /// Message is delivered to the receiver.
+    MessageDelivered {
+        /// Address of the sender.
+        from: Address,
+        /// Address of the receiver.
+        to: Address,
+        /// Tip of the message.
+        tip: String,
+        /// Data of the message.
+        data: String,
+    },
+    /// Message is dropped by the network.
+    MessageDropped {
+        /// Address of the sender.
+        from: Address,
+        /// Address of the receiver.
+        to: Address,
+        /// Tip of the message.
+        tip: String,
+        /// Data of the message.
+        data: String,
+    },
+    /// Timer of the process fired.
+    TimerFired {
+        /// Address of the process.
+        process: Address,
+        /// Name of the timer.
+        name: String,
+    },
+}
+
+impl fmt::Display for McEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            McEvent::MessageDelivered {
+                from,
+                to,
+                tip,
+                data,
+            } => write!(
+                f,
+                "{} -> {}: {} {}",
+                display_address(from),
+                display_address(to),
+                tip,
+                data
+            ),
+            McEvent::MessageDropped {
+                from,
+                to,
+                tip,
+                data,
+            } => write!(
+                f,
+                "{} -x {}: {} {}",
+                display_address(from),
+                display_address(to),
+                tip,
+                data
+            ),
+            McEvent::TimerFired { process, name } => {
+                write!(f, "{}: timer {} fired", display_address(process), name)
+            }
+        }
+    }
+}
+
+fn display_address(address: &Address) -> String {
+    format!("{}:{}/{}", address.host, address.port, address.process_name)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Specifies order in which the [model checker][ModelChecker] explores states.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SearchStrategy {
+    /// Breadth-first search, which stops on the first violation.
+    #[default]
+    Bfs,
+    /// Depth-first search, which continues after the violation
+    /// to find shorter sequence of events, but does not guarantee it is minimal.
+    Dfs,
+}
+
+/// Represents statistics of the [model checking][ModelChecker::run].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct McStats {
+    /// Number of distinct explored states.
+    pub explored_states: usize,
+    /// Maximum number of events applied to reach the explored state.
+    pub max_depth: usize,
+}
+
+/// Represents violation of the invariant found by the [model checker][ModelChecker].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct McViolation {
+    /// Description of the violation returned by the invariant.
+    pub reason: String,
+    /// Sequence of events, which leads to the violation.
+    ///
+    /// Sequence is minimal only for [`SearchStrategy::Bfs`].
+    pub events: Vec<McEvent>,
+}
+
+impl fmt::Display for McViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invariant violated after {} events: {}",
+            self.events.len(),
+            self.reason
+        )?;
+        for (i, event) in self.events.iter().enumerate() {
+            write!(f, "\n{}. {}", i + 1, event)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for McViolation {}
+
+////////////////////////////////////////////////////////////////////////////////
+
+type Invariant = Box<dyn Fn(&mut Sim) -> Result<(), String>>;
+
+/// Allows to explore all orderings of the message deliveries and timers of the
+/// [simulation][Sim] for small configurations.
+///
+/// Every explored sequence of events is replayed on the new simulation,
+/// which is created with the specified seed and configured by the `setup` function.
+/// Messages sent with [`send`][crate::Context::send] and timers are held
+/// until the model checker decides to deliver or fire them.
+/// Reliable sends, local messages and sleeps are not controlled and are processed immediately,
+/// network settings are ignored for controlled messages.
+///
+/// By default delays of the timers are ignored, so any pending timer can fire before
+/// any other timer or message. This over-approximates the possible behaviors of the system,
+/// e.g. election timeout may fire before the heartbeat timer with the shorter delay.
+/// Use [`with_timer_delays`][ModelChecker::with_timer_delays] to fire timers
+/// in the order of their deadlines.
+///
+/// After every sequence of events invariants are checked. Processes are assumed to be
+/// deterministic, so state of the system is identified by the sequences of events delivered
+/// to every process and the set of pending events, and already visited states are not explored.
+pub struct ModelChecker {
+    setup: Box<dyn Fn(&mut Sim)>,
+    invariants: Vec<Invariant>,
+    strategy: SearchStrategy,
+    max_depth: usize,
+    drops: bool,
+    timer_delays: bool,
+    seed: u64,
+}
+
+impl ModelChecker {
+    /// Creates model checker, which uses `setup` to add nodes and processes to the simulation
+    /// and send initial local messages.
+    pub fn new(setup: impl Fn(&mut Sim) + 'static) -> Self {
+        Self {
+            setup: Box::new(setup),
+            invariants: Vec::new(),
+            strategy: SearchStrategy::default(),
+            max_depth: 10,
+            drops: false,
+            timer_delays: false,
+            seed: 12345,
+        }
+    }
+
+    /// Set search strategy.
+    pub fn with_strategy(mut self, strategy: SearchStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Set maximum number of events in the explored sequences.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Set if drops of the messages are explored in addition to deliveries.
+    pub fn with_drops(mut self, drops: bool) -> Self {
+        self.drops = drops;
+        self
+    }
+
+    /// Set if timers fire in the order of their deadlines.
+    ///
+    /// Model checker maintains logical time, which is advanced to the deadline of the fired timer.
+    /// Deadline of the timer is the logical time at which it was set plus its delay,
+    /// and only timers with the earliest deadline can fire. Messages are still delivered
+    /// in any order and do not advance the logical time, so any message can be delivered
+    /// before the next timer fires.
+    pub fn with_timer_delays(mut self, timer_delays: bool) -> Self {
+        self.timer_delays = timer_delays;
+        self
+    }
+
+    /// Set seed of the simulations.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Add invariant, which is checked in every explored state.
+    ///
+    /// Invariant returns description of the violation in case of error.
+    pub fn add_invariant(&mut self, invariant: impl Fn(&mut Sim) -> Result<(), String> + 'static) {
+        self.invariants.push(Box::new(invariant));
+    }
+
+    /// Explores orderings of the events.
+    ///
+    /// # Returns
+    ///
+    /// - Error with the sequence of events, which leads to violation of the invariant.
+    ///   It is minimal for [`SearchStrategy::Bfs`] and the shortest found one for
+    ///   [`SearchStrategy::Dfs`].
+    /// - Ok with the statistics if no violations found.
+    pub fn run(&self) -> Result<McStats, McViolation> {
+        let mut stats = McStats::default();
+        let mut violation: Option<McViolation> = None;
+        let mut bound = self.max_depth;
+
+        // Minimal depth on which state was visited.
+        let mut visited = HashMap::<u64, usize>::new();
+        let mut frontier = VecDeque::from([Vec::<Choice>::new()]);
+
+        while let Some(path) = match self.strategy {
+            SearchStrategy::Bfs => frontier.pop_front(),
+            SearchStrategy::Dfs => frontier.pop_back(),
+        } {
+            if path.len() > bound {
+                continue;
+            }
+            let (mut sim, events) = self.replay(&path);
+
+            let state = sim.with_controller(|controller| controller.state_hash());
+            if visited
+                .get(&state)
+                .is_some_and(|depth| *depth <= path.len())
+            {
+                continue;
+            }
+            visited.insert(state, path.len());
+            stats.explored_states += 1;
+            stats.max_depth = stats.max_depth.max(path.len());
+
+            if let Err(reason) = self.check(&mut sim) {
+                violation = Some(McViolation { reason, events });
+                if self.strategy == SearchStrategy::Bfs || path.is_empty() {
+                    break;
+                }
+                bound = path.len() - 1;
+                continue;
+            }
+
+            if path.len() < bound {
+                let mut choices = sim.with_controller(|controller| controller.choices(self.drops));
+                if self.strategy == SearchStrategy::Dfs {
+                    choices.reverse();
+                }
+                for choice in choices {
+                    let mut next = path.clone();
+                    next.push(choice);
+                    frontier.push_back(next);
+                }
+            }
+        }
+
+        match violation {
+            Some(violation) => Err(violation),
+            None => Ok(stats),
+        }
+    }
+
+    /// Creates new simulation and applies the sequence of choices to it.
+    fn replay(&self, path: &[Choice]) -> (Sim, Vec<McEvent>) {
+        let mut sim = Sim::new(self.seed);
+        sim.enable_controlled_delivery();
+        sim.with_controller(|controller| controller.set_timer_delays(self.timer_delays));
+        (self.setup)(&mut sim);
+        sim.step_until_no_events();
+
+        let events = path
+            .iter()
+            .map(|choice| {
+                let (event, fire) = sim.with_controller(|controller| controller.take(choice));
+                if let Some(fire) = fire {
+                    fire();
+                }
+                sim.step_until_no_events();
+                event
+            })
+            .collect();
+
+        (sim, events)
+    }
+
+    fn check(&self, sim: &mut Sim) -> Result<(), String> {
+        self.invariants
+            .iter()
+            .try_for_each(|invariant| invariant(sim))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Action which delivers held message or fires held timer.
+pub(crate) type Fire = Box<dyn FnOnce()>;
+
+/// Choice of the model checker: index of the pending event and if message is dropped.
+#[derive(Clone, Copy)]
+pub(crate) struct Choice {
+    index: usize,
+    drop: bool,
+}
+
+struct PendingEvent {
+    event: McEvent,
+    /// Logical time at which timer fires, `None` for messages.
+    deadline: Option<f64>,
+    fire: Fire,
+}
+
+/// Holds messages and timers of the simulation in the model checking mode.
+#[derive(Default)]
+pub(crate) struct Controller {
+    pending: Vec<PendingEvent>,
+    /// Hashes of the sequences of events delivered to the processes.
+    histories: BTreeMap<Address, u64>,
+    /// If timers fire in the order of their deadlines.
+    timer_delays: bool,
+    /// Logical time, which is the deadline of the last fired timer.
+    now: f64,
+}
+
+impl Controller {
+    /// Set if timers fire in the order of their deadlines.
+    pub fn set_timer_delays(&mut self, timer_delays: bool) {
+        self.timer_delays = timer_delays;
+    }
+
+    /// Holds message sent from `from` to `to`.
+    pub fn hold_message(&mut self, from: &Address, to: &Address, msg: &Message, fire: Fire) {
+        let event = McEvent::MessageDelivered {
+            from: from.clone(),
+            to: to.clone(),
+            tip: msg.tip().clone(),
+            data: String::from_utf8_lossy(msg.raw_data()).into_owned(),
+        };
+        self.pending.push(PendingEvent {
+            event,
+            deadline: None,
+            fire,
+        });
+    }
+
+    /// Holds timer of the process, which fires after `delay` of the logical time.
+    /// Timer overrides existing one with the same name, unless it is set `once`.
+    pub fn hold_timer(
+        &mut self,
+        process: &Address,
+        name: &str,
+        delay: f64,
+        once: bool,
+        fire: Fire,
+    ) {
+        let event = McEvent::TimerFired {
+            process: process.clone(),
+            name: name.to_owned(),
+        };
+        if let Some(pos) = self
+            .pending
+            .iter()
+            .position(|pending| pending.event == event)
+        {
+            if once {
+                return;
+            }
+            self.pending.remove(pos);
+        }
+        self.pending.push(PendingEvent {
+            event,
+            deadline: Some(self.now + delay),
+            fire,
+        });
+    }
+
+    /// Cancels held timer of the process.
+    pub fn cancel_timer(&mut self, process: &Address, name: &str) {
+        self.pending.retain(|pending| {
+            !matches!(&pending.event, McEvent::TimerFired { process: p, name: n } if p == process && n == name)
+        });
+    }
+
+    /// Returns possible choices in the current state, equal pending events are considered once.
+    ///
+    /// If timer delays are respected, only timers with the earliest deadline can fire.
+    fn choices(&self, drops: bool) -> Vec<Choice> {
+        let earliest = self
+            .pending
+            .iter()
+            .filter_map(|pending| pending.deadline)
+            .reduce(f64::min);
+        let mut choices = Vec::new();
+        for (index, pending) in self.pending.iter().enumerate() {
+            if self.timer_delays && pending.deadline.is_some() && pending.deadline != earliest {
+                continue;
+            }
+            if self.pending[..index]
+                .iter()
+                .any(|other| other.event == pending.event)
+            {
+                continue;
+            }
+            choices.push(Choice { index, drop: false });
+            if drops && matches!(pending.event, McEvent::MessageDelivered { .. }) {
+                choices.push(Choice { index, drop: true });
+            }
+        }
+        choices
+    }
+
+    /// Removes chosen event and returns it together with the action,
+    /// which must be performed to apply it.
+    fn take(&mut self, choice: &Choice) -> (McEvent, Option<Fire>) {
+        let PendingEvent {
+            event,
+            deadline,
+            fire,
+        } = self.pending.remove(choice.index);
+        if let Some(deadline) = deadline {
+            self.now = self.now.max(deadline);
+        }
+        if choice.drop {
+            let McEvent::MessageDelivered {
+                from,
+                to,
+                tip,
+                data,
+            } = event
+            else {
+                unreachable!("Only messages can be dropped.");
+            };
+            return (
+                McEvent::MessageDropped {
+                    from,
+                    to,
+                    tip,
+                    data,
+                },
+                None,
+            );
+        }
+
+        let receiver = match &event {
+            McEvent::MessageDelivered { to, .. } => to,
+            McEvent::TimerFired { process, .. } => process,
+            McEvent::MessageDropped { .. } => unreachable!(),
+        };
+        let history = self.histories.entry(receiver.clone()).or_default();
+        let mut hasher = DefaultHasher::new();
+        (*history, &event).hash(&mut hasher);
+        *history = hasher.finish();
+
+        (event, Some(fire))
+    }
+
+    /// Returns hash of the state of the system.
+    ///
+    /// If timer delays are respected, times left until the deadlines of the timers
+    /// are part of the state.
+    fn state_hash(&self) -> u64 {
+        let mut pending = self
+            .pending
+            .iter()
+            .map(|pending| {
+                let left = pending
+                    .deadline
+                    .filter(|_| self.timer_delays)
+                    .map(|deadline| (deadline - self.now).to_bits());
+                (&pending.event, left)
+            })
+            .collect::<Vec<_>>();
+        pending.sort_by_key(|(event, left)| (event.to_string(), *left));
+
+        let mut hasher = DefaultHasher::new();
+        (&self.histories, pending).hash(&mut hasher);
+        hasher.finish()
+    }
+}
@@ -1,13 +1,21 @@
 //! Definition of structures and functions, which are used in [`virtual mode`][`crate::Sim`].
 
 pub mod context;
+pub mod diagram;
 pub mod fs;
+pub mod invariant;
+pub mod linearizability;
+pub mod mc;
+pub mod nemesis;
+pub mod network;
 mod node;
 mod process;
+pub mod runner;
 
 mod send_future;
 
 pub mod system;
+pub mod trace;
 
 #[cfg(test)]
 mod tests;
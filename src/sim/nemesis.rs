@@ -0,0 +1,497 @@
+//! Definition of nemesis, which injects faults into the simulation according to the plan.
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+
+use super::{network::LinkConfig, system::Sim};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Represents fault, which is injected into the simulation by the [nemesis][Nemesis].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "fault", rename_all = "snake_case")]
+pub enum Fault {
+    /// Node is [crashed][Sim::crash_node].
+    CrashNode {
+        /// Name of the node.
+        node: String,
+    },
+    /// Node is [recovered][Sim::recover_node] after crash
+    /// and [restart hook][Nemesis::with_restart_hook] is called.
+    RecoverNode {
+        /// Name of the node.
+        node: String,
+    },
+    /// Network is [split][Sim::split_network] into two groups.
+    ///
+    /// Only links, which are not partitioned yet, are remembered by the nemesis.
+    Partition {
+        /// First group of nodes.
+        group1: Vec<String>,
+        /// Second group of nodes.
+        group2: Vec<String>,
+    },
+    /// Links partitioned by the nemesis are connected again. Partitions, isolations
+    /// and [blocks][Sim::block_link] of the links made by the user are kept.
+    HealNetwork,
+    /// Link from node `from` to node `to` gets additional delays and drops.
+    ///
+    /// Previous settings of the link are remembered by the nemesis.
+    DegradeLink {
+        /// Name of the sender node.
+        from: String,
+        /// Name of the receiver node.
+        to: String,
+        /// Minimum additional delay.
+        min_delay: f64,
+        /// Maximum additional delay.
+        max_delay: f64,
+        /// Drop rate of the link.
+        drop_rate: f64,
+    },
+    /// Settings of the link from node `from` to node `to`, which were changed by the nemesis,
+    /// are restored to the values before degradation. [Blocks][Sim::block_link] of the link are kept.
+    RestoreLink {
+        /// Name of the sender node.
+        from: String,
+        /// Name of the receiver node.
+        to: String,
+    },
+    /// Node is [isolated][Sim::isolate_node] for the `duration` of virtual time,
+    /// as during a long pause of the process: it does not exchange messages,
+    /// while its timers still fire. Clock of the node is not affected, see [`Fault::SkewClock`].
+    PauseNode {
+        /// Name of the node.
+        node: String,
+        /// Duration of the pause.
+        duration: f64,
+    },
+    /// Clock of the node runs with the specified [rate][Sim::set_clock_rate],
+    /// so timers of its processes fire earlier or later than expected.
+    SkewClock {
+        /// Name of the node.
+        node: String,
+        /// Rate of the clock.
+        rate: f64,
+    },
+    /// Clock of the node runs with the normal rate again.
+    RestoreClock {
+        /// Name of the node.
+        node: String,
+    },
+}
+
+impl Fault {
+    fn is_repair(&self) -> bool {
+        matches!(
+            self,
+            Fault::RecoverNode { .. }
+                | Fault::HealNetwork
+                | Fault::RestoreLink { .. }
+                | Fault::RestoreClock { .. }
+        )
+    }
+}
+
+/// Represents [fault][Fault] scheduled at the specified virtual time.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledFault {
+    /// Virtual time of the fault.
+    pub time: f64,
+    /// Injected fault.
+    #[serde(flatten)]
+    pub fault: Fault,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Debug, PartialEq)]
+enum FaultKind {
+    Crash,
+    Partition,
+    DegradeLink {
+        min_delay: f64,
+        max_delay: f64,
+        drop_rate: f64,
+    },
+    Pause,
+    ClockSkew {
+        min_rate: f64,
+        max_rate: f64,
+    },
+}
+
+/// Declarative description of the faults, which are injected by the [nemesis][Nemesis].
+///
+/// Faults are injected one by one: after random interval the fault of the random enabled kind
+/// starts and lasts for the random duration, after which it is repaired.
+/// By default no kinds of faults are enabled.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FaultPlan {
+    nodes: Vec<String>,
+    kinds: Vec<FaultKind>,
+    min_interval: f64,
+    max_interval: f64,
+    min_duration: f64,
+    max_duration: f64,
+}
+
+impl FaultPlan {
+    /// Creates plan of the faults of the specified nodes.
+    pub fn new(nodes: &[&str]) -> Self {
+        Self {
+            nodes: nodes.iter().map(|node| node.to_string()).collect(),
+            kinds: Vec::new(),
+            min_interval: 5.0,
+            max_interval: 10.0,
+            min_duration: 1.0,
+            max_duration: 5.0,
+        }
+    }
+
+    /// Set minimum and maximum interval between the repair of the fault and the next fault.
+    pub fn with_interval(mut self, min_interval: f64, max_interval: f64) -> Self {
+        assert!(
+            0.0 < min_interval && min_interval <= max_interval,
+            "Incorrect fault interval: [{}, {}]",
+            min_interval,
+            max_interval
+        );
+        self.min_interval = min_interval;
+        self.max_interval = max_interval;
+        self
+    }
+
+    /// Set minimum and maximum duration of the fault.
+    pub fn with_duration(mut self, min_duration: f64, max_duration: f64) -> Self {
+        assert!(
+            0.0 <= min_duration && min_duration <= max_duration,
+            "Incorrect fault duration: [{}, {}]",
+            min_duration,
+            max_duration
+        );
+        self.min_duration = min_duration;
+        self.max_duration = max_duration;
+        self
+    }
+
+    /// Enable crashes of the nodes, which are followed by the recovery.
+    pub fn with_crashes(mut self) -> Self {
+        self.kinds.push(FaultKind::Crash);
+        self
+    }
+
+    /// Enable partitions of the nodes into two groups, which are followed by the heal of the network.
+    pub fn with_partitions(mut self) -> Self {
+        assert!(
+            self.nodes.len() >= 2,
+            "Partition requires at least two nodes."
+        );
+        self.kinds.push(FaultKind::Partition);
+        self
+    }
+
+    /// Enable degradation of the links between nodes, which is followed by the reset of the link.
+    pub fn with_link_degradation(mut self, min_delay: f64, max_delay: f64, drop_rate: f64) -> Self {
+        assert!(
+            self.nodes.len() >= 2,
+            "Link degradation requires at least two nodes."
+        );
+        self.kinds.push(FaultKind::DegradeLink {
+            min_delay,
+            max_delay,
+            drop_rate,
+        });
+        self
+    }
+
+    /// Enable pauses of the nodes.
+    pub fn with_pauses(mut self) -> Self {
+        self.kinds.push(FaultKind::Pause);
+        self
+    }
+
+    /// Enable skews of the clocks of the nodes with rate from `min_rate` to `max_rate`,
+    /// which are followed by the restore of the normal rate.
+    pub fn with_clock_skew(mut self, min_rate: f64, max_rate: f64) -> Self {
+        assert!(
+            0.0 < min_rate && min_rate <= max_rate,
+            "Incorrect clock rates: [{}, {}]",
+            min_rate,
+            max_rate
+        );
+        self.kinds.push(FaultKind::ClockSkew { min_rate, max_rate });
+        self
+    }
+
+    /// Returns schedule of the faults, which start in the time interval `[from, until)`.
+    ///
+    /// Schedule is determined by the `seed`. Repair of the last fault can be scheduled after `until`.
+    pub fn schedule(&self, seed: u64, from: f64, until: f64) -> Vec<ScheduledFault> {
+        let mut rng = Pcg64::seed_from_u64(seed);
+        let mut schedule = Vec::new();
+        if self.nodes.is_empty() || self.kinds.is_empty() {
+            return schedule;
+        }
+
+        let mut time = from;
+        loop {
+            time += rng.gen_range(self.min_interval..=self.max_interval);
+            if time >= until {
+                break;
+            }
+            let duration = rng.gen_range(self.min_duration..=self.max_duration);
+            let (fault, repair) = match self.kinds.choose(&mut rng).unwrap() {
+                FaultKind::Crash => {
+                    let node = self.nodes.choose(&mut rng).unwrap().clone();
+                    (
+                        Fault::CrashNode { node: node.clone() },
+                        Some(Fault::RecoverNode { node }),
+                    )
+                }
+                FaultKind::Partition => {
+                    let mut nodes = self.nodes.clone();
+                    nodes.shuffle(&mut rng);
+                    let group2 = nodes.split_off(rng.gen_range(1..nodes.len()));
+                    (
+                        Fault::Partition {
+                            group1: nodes,
+                            group2,
+                        },
+                        Some(Fault::HealNetwork),
+                    )
+                }
+                FaultKind::DegradeLink {
+                    min_delay,
+                    max_delay,
+                    drop_rate,
+                } => {
+                    let mut nodes = self.nodes.choose_multiple(&mut rng, 2).cloned();
+                    let from = nodes.next().unwrap();
+                    let to = nodes.next().unwrap();
+                    (
+                        Fault::DegradeLink {
+                            from: from.clone(),
+                            to: to.clone(),
+                            min_delay: *min_delay,
+                            max_delay: *max_delay,
+                            drop_rate: *drop_rate,
+                        },
+                        Some(Fault::RestoreLink { from, to }),
+                    )
+                }
+                FaultKind::Pause => {
+                    let node = self.nodes.choose(&mut rng).unwrap().clone();
+                    (Fault::PauseNode { node, duration }, None)
+                }
+                FaultKind::ClockSkew { min_rate, max_rate } => {
+                    let node = self.nodes.choose(&mut rng).unwrap().clone();
+                    let rate = rng.gen_range(*min_rate..=*max_rate);
+                    (
+                        Fault::SkewClock {
+                            node: node.clone(),
+                            rate,
+                        },
+                        Some(Fault::RestoreClock { node }),
+                    )
+                }
+            };
+            schedule.push(ScheduledFault { time, fault });
+            time += duration;
+            if let Some(fault) = repair {
+                schedule.push(ScheduledFault { time, fault });
+            }
+        }
+        schedule
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+type RestartHook = Box<dyn FnMut(&mut Sim, &str)>;
+
+/// Injects [faults][Fault] into the [simulation][Sim] according to the schedule,
+/// which is generated from the [plan][FaultPlan] or recorded before.
+///
+/// Simulation must be stepped through the nemesis, which applies the fault before the first
+/// step made at or after the scheduled time of the fault. So replay of the recorded schedule
+/// in the same simulation reproduces the run exactly.
+pub struct Nemesis {
+    schedule: Vec<ScheduledFault>,
+    applied: usize,
+    restart_hook: Option<RestartHook>,
+    /// Settings of the degraded links before degradation.
+    degraded: HashMap<(String, String), Option<LinkConfig>>,
+    /// Links partitioned by the nemesis.
+    partitioned: Vec<(String, String)>,
+}
+
+impl Nemesis {
+    /// Creates nemesis, which injects faults according to the `plan` during the `duration`
+    /// of virtual time starting from the current time of the simulation.
+    ///
+    /// Schedule of the faults is determined by the [seed][Sim::seed] of the simulation.
+    pub fn new(plan: &FaultPlan, sim: &Sim, duration: f64) -> Self {
+        let schedule = plan.schedule(sim.seed(), sim.time(), sim.time() + duration);
+        Self::replay(schedule)
+    }
+
+    /// Creates nemesis, which injects faults according to the recorded schedule.
+    pub fn replay(mut schedule: Vec<ScheduledFault>) -> Self {
+        schedule.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self {
+            schedule,
+            applied: 0,
+            restart_hook: None,
+            degraded: HashMap::new(),
+            partitioned: Vec::new(),
+        }
+    }
+
+    /// Creates nemesis, which injects faults according to the schedule
+    /// [exported][Nemesis::export_schedule] to the file.
+    pub fn replay_file(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let mut schedule = Vec::new();
+        for line in BufReader::new(std::fs::File::open(path)?).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                schedule.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(Self::replay(schedule))
+    }
+
+    /// Set hook, which is called after node is recovered,
+    /// so processes of the node can be added again.
+    pub fn with_restart_hook(mut self, hook: impl FnMut(&mut Sim, &str) + 'static) -> Self {
+        self.restart_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Returns the whole schedule of the faults.
+    pub fn schedule(&self) -> &[ScheduledFault] {
+        &self.schedule
+    }
+
+    /// Returns faults, which are already applied.
+    pub fn applied(&self) -> &[ScheduledFault] {
+        &self.schedule[..self.applied]
+    }
+
+    /// Writes [applied][Nemesis::applied] faults to the file in JSON lines format,
+    /// so they can be [replayed][Nemesis::replay_file].
+    pub fn export_schedule(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut output = BufWriter::new(std::fs::File::create(path)?);
+        for fault in self.applied() {
+            serde_json::to_writer(&mut output, fault)?;
+            output.write_all(b"\n")?;
+        }
+        output.flush()
+    }
+
+    /// Applies faults, which time has come.
+    pub fn apply_due(&mut self, sim: &mut Sim) {
+        while self
+            .schedule
+            .get(self.applied)
+            .is_some_and(|scheduled| scheduled.time <= sim.time())
+        {
+            self.apply_next(sim);
+        }
+    }
+
+    /// Repairs the fault, which is still active, immediately
+    /// and removes the rest of the faults from the schedule.
+    pub fn finish(&mut self, sim: &mut Sim) {
+        if let Some(scheduled) = self.schedule.get_mut(self.applied) {
+            if scheduled.fault.is_repair() {
+                scheduled.time = sim.time();
+                self.apply_next(sim);
+            }
+        }
+        self.schedule.truncate(self.applied);
+    }
+
+    fn apply_next(&mut self, sim: &mut Sim) {
+        let fault = self.schedule[self.applied].fault.clone();
+        self.apply(&fault, sim);
+        if let (Fault::RecoverNode { node }, Some(hook)) = (&fault, self.restart_hook.as_mut()) {
+            hook(sim, node);
+        }
+        self.applied += 1;
+    }
+
+    fn apply(&mut self, fault: &Fault, sim: &mut Sim) {
+        match fault {
+            Fault::CrashNode { node } => sim.crash_node(node),
+            Fault::RecoverNode { node } => sim.recover_node(node),
+            Fault::Partition { group1, group2 } => {
+                for from in group1.iter() {
+                    for to in group2.iter() {
+                        for (from, to) in [(from, to), (to, from)] {
+                            if !sim.is_partitioned(from, to) {
+                                self.partitioned.push((from.clone(), to.clone()));
+                            }
+                        }
+                    }
+                }
+                let group1 = group1.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+                let group2 = group2.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+                sim.split_network(&group1, &group2);
+            }
+            Fault::HealNetwork => {
+                for (from, to) in self.partitioned.drain(..) {
+                    sim.remove_partition(&from, &to);
+                }
+            }
+            Fault::DegradeLink {
+                from,
+                to,
+                min_delay,
+                max_delay,
+                drop_rate,
+            } => {
+                self.degraded
+                    .entry((from.clone(), to.clone()))
+                    .or_insert_with(|| sim.link_config(from, to));
+                sim.set_link_delays(from, to, *min_delay, *max_delay);
+                sim.set_link_drop_rate(from, to, *drop_rate);
+            }
+            Fault::RestoreLink { from, to } => {
+                if let Some(config) = self.degraded.remove(&(from.clone(), to.clone())) {
+                    sim.restore_link_config(from, to, config);
+                }
+            }
+            Fault::PauseNode { node, duration } => sim.isolate_node(node, *duration),
+            Fault::SkewClock { node, rate } => sim.set_clock_rate(node, *rate),
+            Fault::RestoreClock { node } => sim.set_clock_rate(node, 1.0),
+        }
+    }
+
+    /// Performs single step through the simulation after applying faults, which time has come.
+    pub fn step(&mut self, sim: &mut Sim) -> bool {
+        self.apply_due(sim);
+        sim.step()
+    }
+
+    /// Steps through the simulation until there are no pending events left.
+    ///
+    /// Faults scheduled after the last event are not applied.
+    pub fn step_until_no_events(&mut self, sim: &mut Sim) {
+        while self.step(sim) {}
+    }
+
+    /// Steps through the simulation until the specified duration in seconds passes
+    /// or there are no pending events left.
+    pub fn step_for_duration(&mut self, sim: &mut Sim, duration: f64) {
+        let end_time = sim.time() + duration;
+        while sim.time() < end_time && self.step(sim) {}
+    }
+}
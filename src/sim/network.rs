@@ -0,0 +1,338 @@
+//! Definition of per-link network configuration and message filters of the simulation.
+
+use std::collections::{HashMap, HashSet};
+
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64;
+
+use crate::common::{message::Message, process::Address};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Represents effective settings of the directed link between two nodes of the
+/// [simulation][crate::Sim], which are returned by [`Sim::link_settings`][crate::Sim::link_settings].
+///
+/// Settings combine the network settings and the settings of the link.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkSettings {
+    /// Minimum delay of the messages sent over the link.
+    pub min_delay: f64,
+    /// Maximum delay of the messages sent over the link.
+    pub max_delay: f64,
+    /// Probability the message sent over the link is dropped.
+    pub drop_rate: f64,
+    /// Specifies if messages sent over the link are not delivered,
+    /// because link is blocked, network is partitioned,
+    /// or one of the nodes is disconnected from the network or isolated.
+    pub blocked: bool,
+}
+
+/// Specifies how messages sent over the network of the [simulation][crate::Sim] are reordered,
+/// see [`Sim::set_reorder_mode`][crate::Sim::set_reorder_mode].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ReorderMode {
+    /// Messages are reordered only because of the random network and link delays.
+    #[default]
+    Network,
+    /// Every message is held for the additional random delay from `[0, window]` before it is sent,
+    /// so messages sent within the window can be delivered in any order.
+    Window(f64),
+}
+
+/// Specifies what happens with the message intercepted by the
+/// [message filter][crate::Sim::set_message_filter] of the simulation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MessageAction {
+    /// Message is passed to the network, possibly modified by the filter.
+    Deliver,
+    /// Message is dropped.
+    Drop,
+    /// Message is held for the specified additional delay before it is passed to the network.
+    Delay(f64),
+    /// Message is passed to the network twice.
+    Duplicate,
+}
+
+/// Filter of the messages sent between processes, which receives addresses
+/// of the sender and the receiver and the message, which can be modified.
+pub(crate) type MessageFilter = Box<dyn FnMut(&Address, &Address, &mut Message) -> MessageAction>;
+
+/// Additional delays and drop rate of the link.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct LinkConfig {
+    min_delay: f64,
+    max_delay: f64,
+    drop_rate: f64,
+}
+
+/// Stores network settings of the simulation and settings of the links between nodes.
+///
+/// Network delays, partitions and blocked links are applied by the underlying simulation
+/// and tracked here, while drops, additional delays of the links and isolation of the nodes
+/// are decided by the nodes on send, so dropped messages can be recorded in the trace.
+pub(crate) struct Links {
+    network: LinkConfig,
+    links: HashMap<(String, String), LinkConfig>,
+    blocked: HashSet<(String, String)>,
+    partitioned: HashSet<(String, String)>,
+    disconnected: HashSet<String>,
+    /// Time until which node is isolated.
+    isolated: HashMap<String, f64>,
+    duplicate_rate: f64,
+    corrupt_rate: f64,
+    reorder_mode: ReorderMode,
+    rng: Pcg64,
+}
+
+impl Links {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            network: LinkConfig::default(),
+            links: HashMap::new(),
+            blocked: HashSet::new(),
+            partitioned: HashSet::new(),
+            disconnected: HashSet::new(),
+            isolated: HashMap::new(),
+            duplicate_rate: 0.0,
+            corrupt_rate: 0.0,
+            reorder_mode: ReorderMode::Network,
+            rng: Pcg64::seed_from_u64(seed),
+        }
+    }
+
+    pub fn set_network_delays(&mut self, min_delay: f64, max_delay: f64) {
+        self.network.min_delay = min_delay;
+        self.network.max_delay = max_delay;
+    }
+
+    pub fn set_network_drop_rate(&mut self, drop_rate: f64) {
+        self.network.drop_rate = drop_rate;
+    }
+
+    pub fn set_duplicate_rate(&mut self, duplicate_rate: f64) {
+        assert!(
+            (0.0..=1.0).contains(&duplicate_rate),
+            "Incorrect duplicate rate: {}",
+            duplicate_rate
+        );
+        self.duplicate_rate = duplicate_rate;
+    }
+
+    pub fn set_corrupt_rate(&mut self, corrupt_rate: f64) {
+        assert!(
+            (0.0..=1.0).contains(&corrupt_rate),
+            "Incorrect corrupt rate: {}",
+            corrupt_rate
+        );
+        self.corrupt_rate = corrupt_rate;
+    }
+
+    pub fn set_reorder_mode(&mut self, mode: ReorderMode) {
+        if let ReorderMode::Window(window) = mode {
+            assert!(window >= 0.0, "Incorrect reorder window: {}", window);
+        }
+        self.reorder_mode = mode;
+    }
+
+    pub fn set_delays(&mut self, from: &str, to: &str, min_delay: f64, max_delay: f64) {
+        assert!(
+            0.0 <= min_delay && min_delay <= max_delay,
+            "Incorrect link delays: {} and {}",
+            min_delay,
+            max_delay
+        );
+        let link = self.link_mut(from, to);
+        link.min_delay = min_delay;
+        link.max_delay = max_delay;
+    }
+
+    pub fn set_drop_rate(&mut self, from: &str, to: &str, drop_rate: f64) {
+        assert!(
+            (0.0..=1.0).contains(&drop_rate),
+            "Incorrect drop rate: {}",
+            drop_rate
+        );
+        self.link_mut(from, to).drop_rate = drop_rate;
+    }
+
+    pub fn block(&mut self, from: &str, to: &str) {
+        self.blocked.insert((from.to_owned(), to.to_owned()));
+    }
+
+    /// Returns `true` if link is not blocked by partition, so it can be enabled.
+    pub fn unblock(&mut self, from: &str, to: &str) -> bool {
+        let link = (from.to_owned(), to.to_owned());
+        self.blocked.remove(&link);
+        !self.partitioned.contains(&link)
+    }
+
+    /// Removes settings of the link, so it behaves as the rest of the network.
+    ///
+    /// Returns `true` if link is not blocked by partition, so it can be enabled.
+    pub fn reset(&mut self, from: &str, to: &str) -> bool {
+        self.links.remove(&(from.to_owned(), to.to_owned()));
+        self.unblock(from, to)
+    }
+
+    /// Returns settings of the link, or `None` if link behaves as the rest of the network.
+    pub fn config(&self, from: &str, to: &str) -> Option<LinkConfig> {
+        self.links.get(&(from.to_owned(), to.to_owned())).cloned()
+    }
+
+    /// Replaces settings of the link, `None` removes them.
+    pub fn set_config(&mut self, from: &str, to: &str, config: Option<LinkConfig>) {
+        let link = (from.to_owned(), to.to_owned());
+        match config {
+            Some(config) => self.links.insert(link, config),
+            None => self.links.remove(&link),
+        };
+    }
+
+    pub fn partition(&mut self, from: &str, to: &str) {
+        self.partitioned.insert((from.to_owned(), to.to_owned()));
+    }
+
+    pub fn is_partitioned(&self, from: &str, to: &str) -> bool {
+        self.partitioned.contains(&(from.to_owned(), to.to_owned()))
+    }
+
+    /// Removes partition of the link.
+    ///
+    /// Returns `true` if link is not blocked, so it can be enabled.
+    pub fn unpartition(&mut self, from: &str, to: &str) -> bool {
+        let link = (from.to_owned(), to.to_owned());
+        self.partitioned.remove(&link);
+        !self.blocked.contains(&link)
+    }
+
+    /// Removes all partitions.
+    ///
+    /// Returns links, which were blocked by partitions and can be enabled.
+    pub fn heal_partitions(&mut self) -> Vec<(String, String)> {
+        let mut links = self
+            .partitioned
+            .drain()
+            .filter(|link| !self.blocked.contains(link))
+            .collect::<Vec<_>>();
+        links.sort();
+        links
+    }
+
+    /// Isolates node from other nodes until the specified time.
+    pub fn isolate(&mut self, node: &str, until: f64) {
+        let current = self.isolated.entry(node.to_owned()).or_insert(until);
+        *current = current.max(until);
+    }
+
+    pub fn cancel_isolation(&mut self) {
+        self.isolated.clear();
+    }
+
+    fn is_isolated(&self, node: &str, time: f64) -> bool {
+        self.isolated.get(node).is_some_and(|until| time < *until)
+    }
+
+    /// Checks if messages sent from node `from` to node `to` at the specified time are not delivered.
+    pub fn is_blocked(&self, from: &str, to: &str, time: f64) -> bool {
+        if from == to {
+            return false;
+        }
+        let link = (from.to_owned(), to.to_owned());
+        self.blocked.contains(&link)
+            || self.partitioned.contains(&link)
+            || self.disconnected.contains(from)
+            || self.disconnected.contains(to)
+            || self.is_isolated(from, time)
+            || self.is_isolated(to, time)
+    }
+
+    pub fn disconnect(&mut self, node: &str) {
+        self.disconnected.insert(node.to_owned());
+    }
+
+    pub fn connect(&mut self, node: &str) {
+        self.disconnected.remove(node);
+    }
+
+    /// Returns effective settings of the link at the specified time.
+    pub fn settings(&self, from: &str, to: &str, time: f64) -> LinkSettings {
+        let link = self
+            .links
+            .get(&(from.to_owned(), to.to_owned()))
+            .cloned()
+            .unwrap_or_default();
+        LinkSettings {
+            min_delay: self.network.min_delay + link.min_delay,
+            max_delay: self.network.max_delay + link.max_delay,
+            drop_rate: 1.0 - (1.0 - self.network.drop_rate) * (1.0 - link.drop_rate),
+            blocked: self.is_blocked(from, to, time),
+        }
+    }
+
+    /// Returns additional delay of the message sent over the link at the specified time,
+    /// or `None` if message is dropped by the network or the link.
+    ///
+    /// Reliable messages are dropped only if the link is [blocked][Links::is_blocked].
+    /// Messages sent within the node are not reordered.
+    pub fn decide(&mut self, from: &str, to: &str, reliable: bool, time: f64) -> Option<f64> {
+        if self.is_blocked(from, to, time) {
+            return None;
+        }
+        if !reliable
+            && self.network.drop_rate > 0.0
+            && self.rng.gen::<f64>() < self.network.drop_rate
+        {
+            return None;
+        }
+        let reorder_delay = match self.reorder_mode {
+            ReorderMode::Window(window) if window > 0.0 && from != to => {
+                self.rng.gen_range(0.0..=window)
+            }
+            _ => 0.0,
+        };
+        let Some(link) = self.links.get(&(from.to_owned(), to.to_owned())) else {
+            return Some(reorder_delay);
+        };
+        if !reliable && link.drop_rate > 0.0 && self.rng.gen::<f64>() < link.drop_rate {
+            return None;
+        }
+        if link.min_delay < link.max_delay {
+            Some(reorder_delay + self.rng.gen_range(link.min_delay..=link.max_delay))
+        } else {
+            Some(reorder_delay + link.min_delay)
+        }
+    }
+
+    /// Decides if the message sent over the link is duplicated.
+    ///
+    /// Messages sent within the node are not duplicated.
+    pub fn duplicate(&mut self, from: &str, to: &str) -> bool {
+        from != to && self.duplicate_rate > 0.0 && self.rng.gen::<f64>() < self.duplicate_rate
+    }
+
+    /// Corrupts the message sent over the link with probability of the corrupt rate.
+    ///
+    /// One ASCII symbol of the data is replaced with another one, so data stays valid UTF-8.
+    /// Messages sent within the node are not corrupted.
+    pub fn corrupt(&mut self, from: &str, to: &str, msg: Message) -> Message {
+        if from == to || self.corrupt_rate == 0.0 || self.rng.gen::<f64>() >= self.corrupt_rate {
+            return msg;
+        }
+        let mut data = msg.raw_data().to_vec();
+        let positions = (0..data.len())
+            .filter(|i| data[*i].is_ascii())
+            .collect::<Vec<_>>();
+        if positions.is_empty() {
+            return msg;
+        }
+        let i = positions[self.rng.gen_range(0..positions.len())];
+        data[i] ^= self.rng.gen_range(1..0x80);
+        Message::new_raw(msg.tip(), &data).unwrap()
+    }
+
+    fn link_mut(&mut self, from: &str, to: &str) -> &mut LinkConfig {
+        self.links
+            .entry((from.to_owned(), to.to_owned()))
+            .or_default()
+    }
+}
@@ -6,19 +6,57 @@
 
 use std::collections::{HashMap, HashSet};
 
-use crate::common::process::Address;
+use crate::common::{membership::MembershipView, message::Message, process::Address};
+
+use super::{
+    mc::Controller,
+    network::{Links, MessageFilter},
+    trace::{Trace, TraceEvent, TraceEventKind},
+};
 
 /// Represents node manager.
 ///
 /// WARNING: Node manager does not permit nodes and processes with names, contains `/`.
-#[derive(Default)]
 pub struct NodeManager {
     name_to_address: HashMap<String, Address>,
     address_to_name: HashMap<Address, String>,
     node_processes: HashMap<String, HashSet<String>>,
+    node_membership: HashMap<String, MembershipView>,
+    links: Links,
+    message_filter: Option<MessageFilter>,
+    /// Specifies if message filter is taken to be called.
+    message_filter_taken: bool,
+    controller: Option<Controller>,
+    /// Rates of the clocks of the nodes, which differ from `1.0`.
+    clock_rates: HashMap<String, f64>,
+    trace: Trace,
+    seed: u64,
 }
 
 impl NodeManager {
+    /// Creates node manager, which uses specified seed to make decisions about messages
+    /// sent over the configured links.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            name_to_address: HashMap::new(),
+            address_to_name: HashMap::new(),
+            node_processes: HashMap::new(),
+            node_membership: HashMap::new(),
+            links: Links::new(seed),
+            message_filter: None,
+            message_filter_taken: false,
+            controller: None,
+            clock_rates: HashMap::new(),
+            trace: Trace::default(),
+            seed,
+        }
+    }
+
+    /// Returns seed of the simulation.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
     /// Add new node to the manager.
     ///
     /// # Returns
@@ -48,6 +86,10 @@ impl NodeManager {
                 );
             }
 
+            // Membership view is shared by all processes of the node.
+            self.node_membership
+                .insert(name.clone(), MembershipView::default());
+
             // Add node to the node process mapping.
             let node_processes_opt = self.node_processes.insert(name, HashSet::new());
 
@@ -184,6 +226,175 @@ impl NodeManager {
         }
     }
 
+    /// Returns address of the node with such `node_name`.
+    pub fn get_node_address(&self, node_name: &str) -> Result<Address, String> {
+        self.name_to_address
+            .get(node_name)
+            .cloned()
+            .ok_or(format!("Node with name {} does not exist.", node_name))
+    }
+
+    /// Returns membership view of the node with such `node_name`.
+    pub fn membership_view(&self, node_name: &str) -> Result<MembershipView, String> {
+        self.node_membership
+            .get(node_name)
+            .cloned()
+            .ok_or(format!("Node with name {} does not exist.", node_name))
+    }
+
+    /// Returns settings of the links between nodes.
+    pub fn links(&self) -> &Links {
+        &self.links
+    }
+
+    /// Returns mutable settings of the links between nodes.
+    pub fn links_mut(&mut self) -> &mut Links {
+        &mut self.links
+    }
+
+    /// Returns additional delay of the message sent between processes with specified addresses
+    /// at the specified time or `None` if message is dropped by the link between their nodes.
+    pub fn link_delay(
+        &mut self,
+        from: &Address,
+        to: &Address,
+        reliable: bool,
+        time: f64,
+    ) -> Option<f64> {
+        let from = self.node_name(from);
+        let to = self.node_name(to);
+        match (from, to) {
+            (Some(from), Some(to)) => self.links.decide(&from, &to, reliable, time),
+            _ => Some(0.0),
+        }
+    }
+
+    /// Checks if messages sent between processes with specified addresses
+    /// at the specified time are not delivered, because the link between their nodes is blocked.
+    pub fn is_link_blocked(&self, from: &Address, to: &Address, time: f64) -> bool {
+        let from = self.node_name(from);
+        let to = self.node_name(to);
+        match (from, to) {
+            (Some(from), Some(to)) => self.links.is_blocked(&from, &to, time),
+            _ => false,
+        }
+    }
+
+    /// Decides if the message sent between processes with specified addresses is duplicated.
+    pub fn duplicate_message(&mut self, from: &Address, to: &Address) -> bool {
+        let from = self.node_name(from);
+        let to = self.node_name(to);
+        match (from, to) {
+            (Some(from), Some(to)) => self.links.duplicate(&from, &to),
+            _ => false,
+        }
+    }
+
+    /// Corrupts the message sent between processes with specified addresses
+    /// according to the corrupt rate of the network.
+    pub fn corrupt_message(&mut self, from: &Address, to: &Address, msg: Message) -> Message {
+        let from = self.node_name(from);
+        let to = self.node_name(to);
+        match (from, to) {
+            (Some(from), Some(to)) => self.links.corrupt(&from, &to, msg),
+            _ => msg,
+        }
+    }
+
+    /// Set rate of the clock of the node with such `node_name`.
+    pub fn set_clock_rate(&mut self, node_name: &str, rate: f64) {
+        if rate == 1.0 {
+            self.clock_rates.remove(node_name);
+        } else {
+            self.clock_rates.insert(node_name.to_owned(), rate);
+        }
+    }
+
+    /// Returns delay of virtual time, after which timer set with `delay` by the process
+    /// with such `address` fires according to the clock of its node.
+    pub fn timer_delay(&self, address: &Address, delay: f64) -> f64 {
+        self.node_name(address)
+            .and_then(|node| self.clock_rates.get(&node))
+            .map_or(delay, |rate| delay / rate)
+    }
+
+    /// Set filter of the messages sent between processes, `None` removes the filter.
+    pub fn set_message_filter(&mut self, filter: Option<MessageFilter>) {
+        self.message_filter = filter;
+        self.message_filter_taken = false;
+    }
+
+    /// Takes message filter, so it can be called without borrow of the node manager.
+    ///
+    /// Filter must be [returned][NodeManager::return_message_filter] after the call.
+    /// Messages sent by the filter itself are not filtered.
+    pub fn take_message_filter(&mut self) -> Option<MessageFilter> {
+        let filter = self.message_filter.take();
+        self.message_filter_taken = filter.is_some();
+        filter
+    }
+
+    /// Returns message filter taken with [`NodeManager::take_message_filter`],
+    /// unless filter was replaced or removed after that.
+    pub fn return_message_filter(&mut self, filter: MessageFilter) {
+        if self.message_filter_taken {
+            self.message_filter = Some(filter);
+            self.message_filter_taken = false;
+        }
+    }
+
+    /// Enable model checking mode, in which messages and timers are held by the controller.
+    pub fn enable_controller(&mut self) {
+        self.controller.get_or_insert_with(Controller::default);
+    }
+
+    /// Returns controller of the messages and timers, if model checking mode is enabled.
+    pub fn controller_mut(&mut self) -> Option<&mut Controller> {
+        self.controller.as_mut()
+    }
+
+    /// Returns trace of the simulation.
+    pub fn trace(&self) -> &Trace {
+        &self.trace
+    }
+
+    /// Returns mutable trace of the simulation.
+    pub fn trace_mut(&mut self) -> &mut Trace {
+        &mut self.trace
+    }
+
+    /// Records event of the process with such `address` in the trace, if trace is enabled.
+    pub fn record_event(
+        &mut self,
+        address: &Address,
+        time: f64,
+        kind: impl FnOnce() -> TraceEventKind,
+    ) {
+        if !self.trace.is_enabled() {
+            return;
+        }
+        let event = TraceEvent {
+            time,
+            node: self.node_name(address).unwrap_or_default(),
+            process: Some(address.process_name.clone()),
+            kind: kind(),
+        };
+        self.trace.record(event);
+    }
+
+    /// Returns name of the node, on which process with such `address` is located.
+    pub fn node_name(&self, address: &Address) -> Option<String> {
+        let node_address = Address::new_node_address(address.host.clone(), address.port);
+        self.address_to_name.get(&node_address).cloned()
+    }
+
+    /// Returns sorted names of all nodes.
+    pub fn node_names(&self) -> Vec<String> {
+        let mut names = self.node_processes.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
     /// Check if node with such `node_name` exists.
     pub fn check_node_exists(&self, node_name: &str) -> bool {
         self.node_processes.contains_key(node_name)
@@ -12,11 +12,14 @@ use dslab_async_mp::{
 };
 
 use crate::{
-    common::process::{Process, ProcessState},
-    Context,
+    common::{
+        membership::MembershipView,
+        process::{Process, ProcessState},
+    },
+    Address, Context,
 };
 
-use super::{context::VirtualContext, node::NodeManager};
+use super::{context::VirtualContext, node::NodeManager, trace::TraceEventKind};
 
 /// Represents virtual process wrapper,
 /// which is to be passed to the [`DSLab MP`](https://osukhoroslov.github.io/dslab/docs/dslab_mp/index.html).
@@ -24,15 +27,24 @@ pub struct VirtualProcessWrapper<P: Process + 'static> {
     user_process: Arc<RwLock<P>>,
     process_state: ProcessState,
     node_manager: Rc<RefCell<NodeManager>>,
+    membership: MembershipView,
+    address: Address,
 }
 
 impl<P: Process + 'static> VirtualProcessWrapper<P> {
     /// Create new virtual process wrapper.
-    pub fn new(process_impl: Arc<RwLock<P>>, node_manager: Rc<RefCell<NodeManager>>) -> Self {
+    pub fn new(
+        process_impl: Arc<RwLock<P>>,
+        node_manager: Rc<RefCell<NodeManager>>,
+        membership: MembershipView,
+        address: Address,
+    ) -> Self {
         Self {
             user_process: process_impl,
             process_state: ProcessState::Running,
             node_manager,
+            membership,
+            address,
         }
     }
 
@@ -41,6 +53,8 @@ impl<P: Process + 'static> VirtualProcessWrapper<P> {
         VirtualContext {
             dslab_ctx,
             node_manager: self.node_manager.clone(),
+            membership: self.membership.clone(),
+            address: self.address.clone(),
         }
     }
 }
@@ -66,23 +80,27 @@ impl<P: Process + 'static> DSLabProcess for VirtualProcessWrapper<P> {
 
         // Create virtual context to pass it into dslab process.
         let virt_ctx = self.create_context(ctx);
+        let msg = msg.into();
+        virt_ctx.trace(|| TraceEventKind::message_received(&from_address, &msg));
 
         // Callback dslab process on message method.
         self.user_process
             .write()
             .expect("Can not write in process, probably datarace appeared")
-            .on_message(msg.into(), from_address, Context::new_virt(virt_ctx));
+            .on_message(msg, from_address, Context::new_virt(virt_ctx));
 
         Ok(())
     }
 
     fn on_local_message(&mut self, msg: DSLabMessage, ctx: DSLabContext) -> Result<(), String> {
         let virt_ctx = self.create_context(ctx);
+        let msg = msg.into();
+        virt_ctx.trace(|| TraceEventKind::local_message_received(&msg));
 
         self.user_process
             .write()
             .expect("Can not write in process, probably datarace appeared")
-            .on_local_message(msg.into(), Context::new_virt(virt_ctx));
+            .on_local_message(msg, Context::new_virt(virt_ctx));
 
         Ok(())
     }
@@ -93,6 +111,9 @@ impl<P: Process + 'static> DSLabProcess for VirtualProcessWrapper<P> {
         }
 
         let virt_ctx = self.create_context(ctx);
+        virt_ctx.trace(|| TraceEventKind::TimerFired {
+            name: timer.clone(),
+        });
 
         self.user_process
             .write()
@@ -0,0 +1,262 @@
+//! Definition of runner, which executes scenario of the simulation over many seeds in parallel.
+
+use std::{
+    any::Any,
+    fmt,
+    num::NonZeroUsize,
+    ops::Range,
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Environment variable, which restricts [runner][SeedRunner] to the single seed.
+const SEED_ENV_VAR: &str = "DSBUILD_SEED";
+
+/// Represents failure of the scenario for one seed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SeedFailure {
+    /// Seed of the failed run.
+    pub seed: u64,
+    /// Error returned by the scenario or message of the panic.
+    pub reason: String,
+    /// Command, which runs the scenario only for this seed.
+    pub reproduce: String,
+}
+
+/// Represents aggregated results of the [runner][SeedRunner].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SeedReport {
+    /// Number of the executed seeds.
+    pub total: usize,
+    /// Failures ordered by seed.
+    pub failures: Vec<SeedFailure>,
+}
+
+impl SeedReport {
+    /// Returns if scenario succeeded for all seeds.
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Returns seeds of the failed runs.
+    pub fn failed_seeds(&self) -> Vec<u64> {
+        self.failures.iter().map(|failure| failure.seed).collect()
+    }
+}
+
+impl fmt::Display for SeedReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_success() {
+            return write!(f, "All {} seeds passed", self.total);
+        }
+        write!(
+            f,
+            "{} of {} seeds failed: {:?}",
+            self.failures.len(),
+            self.total,
+            self.failed_seeds()
+        )?;
+        for failure in self.failures.iter() {
+            write!(
+                f,
+                "\n\nSeed {}: {}\nReproduce with: {}",
+                failure.seed, failure.reason, failure.reproduce
+            )?;
+        }
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Allows to execute scenario of the [simulation][crate::Sim] over the range of seeds
+/// across several threads.
+///
+/// Simulation is not [`Send`], so scenario receives the seed and creates simulation
+/// in the thread of the run. Threads of the runs are named as the thread, which calls
+/// [`run`][SeedRunner::run]. Run fails if scenario returns error or panics.
+/// Failed seeds are printed to stderr together with the command, which reproduces them
+/// with the runner created by [`SeedRunner::from_env`].
+///
+/// # Example
+///
+/// ```
+/// use dsbuild::{SeedRunner, Sim};
+///
+/// let report = SeedRunner::from_env(0..16).with_threads(4).run(|seed| {
+///     let mut sim = Sim::new(seed);
+///     sim.add_node("node", "10.0.0.1", 10024);
+///     sim.step_until_no_events();
+///     Ok(())
+/// });
+/// assert!(report.is_success());
+/// ```
+pub struct SeedRunner {
+    seeds: Range<u64>,
+    /// Seed, which is executed instead of the range.
+    only_seed: Option<u64>,
+    threads: usize,
+    reproduce: Option<String>,
+}
+
+impl SeedRunner {
+    /// Creates runner over the range of seeds, which uses all available cores.
+    pub fn new(seeds: Range<u64>) -> Self {
+        Self {
+            seeds,
+            only_seed: None,
+            threads: std::thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            reproduce: None,
+        }
+    }
+
+    /// Creates runner over the range of seeds, unless `DSBUILD_SEED` environment variable
+    /// is set, in which case only this seed is executed.
+    ///
+    /// # Panics
+    ///
+    /// - If `DSBUILD_SEED` environment variable is set, but it is not a number.
+    pub fn from_env(seeds: Range<u64>) -> Self {
+        let runner = Self::new(seeds);
+        match std::env::var(SEED_ENV_VAR) {
+            Ok(seed) => runner.with_only_seed(
+                seed.parse::<u64>()
+                    .unwrap_or_else(|_| panic!("{} must be a number", SEED_ENV_VAR)),
+            ),
+            Err(_) => runner,
+        }
+    }
+
+    /// Set the only seed, which is executed instead of the range.
+    pub fn with_only_seed(mut self, seed: u64) -> Self {
+        self.only_seed = Some(seed);
+        self
+    }
+
+    /// Set number of threads.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Set command, which reproduces failed run, `{seed}` is replaced with the seed.
+    ///
+    /// By default command runs the current test of the current package
+    /// with `DSBUILD_SEED` environment variable.
+    pub fn with_reproduce_command(mut self, command: &str) -> Self {
+        self.reproduce = Some(command.to_owned());
+        self
+    }
+
+    /// Executes scenario for every seed and returns aggregated results.
+    pub fn run(&self, scenario: impl Fn(u64) -> Result<(), String> + Sync) -> SeedReport {
+        // Seeds are counted from the first one, so the range can end at `u64::MAX`.
+        let (first_seed, total) = match self.only_seed {
+            Some(seed) => (seed, 1),
+            None => (
+                self.seeds.start,
+                self.seeds.end.saturating_sub(self.seeds.start),
+            ),
+        };
+        let reproduce = self
+            .reproduce
+            .clone()
+            .unwrap_or_else(default_reproduce_command);
+        let thread_name = std::thread::current().name().map(str::to_owned);
+
+        let next_index = AtomicU64::new(0);
+        let failures = Mutex::new(Vec::new());
+        std::thread::scope(|scope| {
+            for _ in 0..self.threads {
+                let mut builder = std::thread::Builder::new();
+                if let Some(name) = thread_name.clone() {
+                    builder = builder.name(name);
+                }
+                let run = || loop {
+                    let index = next_index.fetch_add(1, Ordering::Relaxed);
+                    if index >= total {
+                        break;
+                    }
+                    let seed = first_seed + index;
+                    let result = catch_unwind(AssertUnwindSafe(|| scenario(seed)))
+                        .unwrap_or_else(|panic| Err(panic_message(panic)));
+                    if let Err(reason) = result {
+                        failures.lock().unwrap().push(SeedFailure {
+                            seed,
+                            reason,
+                            reproduce: reproduce.replace("{seed}", &seed.to_string()),
+                        });
+                    }
+                };
+                builder
+                    .spawn_scoped(scope, run)
+                    .expect("Failed to spawn thread of the runner.");
+            }
+        });
+
+        let mut failures = failures.into_inner().unwrap();
+        failures.sort_by_key(|failure| failure.seed);
+        let report = SeedReport {
+            total: total as usize,
+            failures,
+        };
+        if !report.is_success() {
+            eprintln!("{}", report);
+        }
+        report
+    }
+}
+
+/// Returns command, which runs the current test with the seed.
+///
+/// Test harness names threads of the tests by their paths. Package and test target
+/// are determined from the environment of `cargo test` and the name of the test executable.
+fn default_reproduce_command() -> String {
+    let package = std::env::var("CARGO_PKG_NAME")
+        .map(|package| format!(" -p {}", package))
+        .unwrap_or_default();
+    let target = test_target()
+        .map(|target| format!(" {}", target))
+        .unwrap_or_default();
+    let test = std::thread::current()
+        .name()
+        .filter(|name| *name != "main")
+        .map(|name| format!(" {} -- --exact", name))
+        .unwrap_or_default();
+    format!(
+        "{}={{seed}} cargo test{}{}{}",
+        SEED_ENV_VAR, package, target, test
+    )
+}
+
+/// Returns `--test <name>` if the current executable is the integration test of the package,
+/// and `--lib` otherwise.
+fn test_target() -> Option<String> {
+    let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR")?;
+    let exe = std::env::current_exe().ok()?;
+    // Cargo appends hash to the names of the test executables.
+    let stem = exe.file_stem()?.to_str()?;
+    let name = stem.rsplit_once('-').map_or(stem, |(name, _)| name);
+    let tests = Path::new(&manifest_dir).join("tests");
+    if tests.join(format!("{}.rs", name)).exists() || tests.join(name).join("main.rs").exists() {
+        Some(format!("--test {}", name))
+    } else {
+        Some("--lib".to_owned())
+    }
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        format!("panicked: {}", message)
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        format!("panicked: {}", message)
+    } else {
+        "panicked".to_owned()
+    }
+}
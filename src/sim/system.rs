@@ -1,22 +1,42 @@
 //! Simulation.
 
 use std::{
+    any::Any,
     cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    io::{BufWriter, Write},
+    path::Path,
     rc::Rc,
     sync::{Arc, RwLock},
 };
 
 use dslab_async_mp::system::System as DSLabSimulation;
 
-use super::{node::NodeManager, process::VirtualProcessWrapper};
+use super::{
+    diagram::{Diagram, DiagramFilter, DiagramFormat},
+    invariant::{Invariant, SimView},
+    mc::Controller,
+    network::{LinkConfig, LinkSettings, MessageAction, ReorderMode},
+    node::NodeManager,
+    process::VirtualProcessWrapper,
+    trace::{TraceEvent, TraceEventKind},
+};
 
 use crate::{
-    common::process::{Process, ProcessWrapper},
+    common::{
+        membership::{
+            Member, MembershipConfig, MembershipProcess, MEMBERSHIP_PROCESS, MEMBERSHIP_START,
+        },
+        process::{Address, Process, ProcessWrapper},
+    },
     Message,
 };
 
 ////////////////////////////////////////////////////////////////////////////////
 
+/// Number of the last trace events included into the report of the invariant violation.
+const REPORTED_EVENTS: usize = 10;
+
 /// Represensts simulation of real world system environment: nodes, network, time and file system.
 ///
 /// Simulation is event-driven: in every moment there are pending events, ordered by time.
@@ -32,10 +52,22 @@ use crate::{
 ///
 /// Simulation allows to configure network settings.
 /// For example, user can set [delays][Sim::set_network_delays] of the network and its
-/// [drop-rate][Sim::set_network_drop_rate].
+/// [drop-rate][Sim::set_network_drop_rate], or configure [delays][Sim::set_link_delays]
+/// and [drop rate][Sim::set_link_drop_rate] of the links between particular nodes.
+/// Also, network can [duplicate][Sim::set_network_duplicate_rate],
+/// [corrupt][Sim::set_network_corrupt_rate] and [reorder][Sim::set_reorder_mode] messages.
+/// Network can be [partitioned][Sim::partition] into groups of nodes,
+/// which is reverted with [`Sim::heal_network`].
+///
+/// To investigate behavior of the system, user can enable [trace][Sim::enable_trace] of the simulation events.
+/// Global properties of the system can be checked after every step with [invariants][Sim::add_invariant].
 pub struct Sim {
     inner: DSLabSimulation,
     node_manager: Rc<RefCell<NodeManager>>,
+    seed: u64,
+    /// Wrappers of the processes by node and process names.
+    processes: BTreeMap<(String, String), Box<dyn Any>>,
+    invariants: Vec<(String, Invariant)>,
 }
 
 impl Sim {
@@ -45,44 +77,424 @@ impl Sim {
         inner.network().set_corrupt_rate(0.0);
         inner.network().set_drop_rate(0.0);
         inner.network().set_delays(0.5, 1.0);
+        let mut node_manager = NodeManager::new(seed);
+        node_manager.links_mut().set_network_delays(0.5, 1.0);
         Self {
             inner,
-            node_manager: Rc::new(RefCell::new(NodeManager::default())),
+            node_manager: Rc::new(RefCell::new(node_manager)),
+            seed,
+            processes: BTreeMap::new(),
+            invariants: Vec::new(),
         }
     }
 
+    /// Returns seed of the simulation.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
     ////////////////////////////////////////////////////////////////////////////////
     // Network
     ////////////////////////////////////////////////////////////////////////////////
 
     /// Set the fixed network delay.
     pub fn set_network_delay(&self, delay: f64) {
-        self.inner.network().set_delay(delay)
+        self.set_network_delays(delay, delay)
     }
 
     /// Set the minimum and maximum network delays.
     pub fn set_network_delays(&self, min_delay: f64, max_delay: f64) {
-        self.inner.network().set_delays(min_delay, max_delay)
+        self.inner.network().set_delays(min_delay, max_delay);
+        self.node_manager
+            .borrow_mut()
+            .links_mut()
+            .set_network_delays(min_delay, max_delay);
     }
 
     /// Set drop rate of the network.
     pub fn set_network_drop_rate(&self, drop_rate: f64) {
-        self.inner.network().set_drop_rate(drop_rate)
+        self.node_manager
+            .borrow_mut()
+            .links_mut()
+            .set_network_drop_rate(drop_rate);
+    }
+
+    /// Set probability of corruption of the messages sent over the network.
+    ///
+    /// Data of the corrupted message is changed, so receiver can fail to deserialize it
+    /// or get different values. As for drops, reliable sends are not affected.
+    /// Every copy of the [duplicated][Sim::set_network_duplicate_rate] message is corrupted
+    /// independently. Messages sent within the node are not corrupted.
+    ///
+    /// # Panics
+    ///
+    /// - If corrupt rate is not in range `[0, 1]`.
+    pub fn set_network_corrupt_rate(&self, corrupt_rate: f64) {
+        self.node_manager
+            .borrow_mut()
+            .links_mut()
+            .set_corrupt_rate(corrupt_rate);
+    }
+
+    /// Set probability of duplication of the messages sent over the network.
+    ///
+    /// Duplicate of the message is sent independently of the original one,
+    /// so receiver can get it before or after the original message.
+    /// Unlike drops, duplication also affects reliable sends, which models retries of the sender:
+    /// the sender observes single delivery, but the receiver can get the message more than once.
+    /// Messages sent within the node are not duplicated.
+    ///
+    /// # Panics
+    ///
+    /// - If duplicate rate is not in range `[0, 1]`.
+    pub fn set_network_duplicate_rate(&self, duplicate_rate: f64) {
+        self.node_manager
+            .borrow_mut()
+            .links_mut()
+            .set_duplicate_rate(duplicate_rate);
+    }
+
+    /// Set [mode][ReorderMode] of reordering of the messages sent over the network.
+    ///
+    /// Delay of the reordering is added to the network and link delays of the message,
+    /// including reliable sends. Messages sent within the node are not reordered.
+    ///
+    /// # Panics
+    ///
+    /// - If reorder window is negative.
+    pub fn set_reorder_mode(&self, mode: ReorderMode) {
+        self.node_manager
+            .borrow_mut()
+            .links_mut()
+            .set_reorder_mode(mode);
+    }
+
+    /// Set filter, which intercepts every message sent between processes
+    /// before it is passed to the network.
+    ///
+    /// Filter receives addresses of the sender and the receiver and the message,
+    /// which can be modified, and returns [action][MessageAction] applied to the message.
+    /// It allows to express targeted losses and Byzantine behaviour, e.g. drop every message
+    /// with specific tip sent by some process. Filter is applied to the reliable sends too:
+    /// dropped reliable message results in the send timeout.
+    /// Local messages are not intercepted. Previous filter is replaced.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use dsbuild::{MessageAction, Sim};
+    /// let sim = Sim::new(12345);
+    /// sim.set_message_filter(|from, _to, msg| {
+    ///     if from.host == "node_2" && msg.tip() == "append_entries_response" {
+    ///         MessageAction::Drop
+    ///     } else {
+    ///         MessageAction::Deliver
+    ///     }
+    /// });
+    /// ```
+    pub fn set_message_filter(
+        &self,
+        filter: impl FnMut(&Address, &Address, &mut Message) -> MessageAction + 'static,
+    ) {
+        self.node_manager
+            .borrow_mut()
+            .set_message_filter(Some(Box::new(filter)));
+    }
+
+    /// Remove [message filter][Sim::set_message_filter].
+    pub fn clear_message_filter(&self) {
+        self.node_manager.borrow_mut().set_message_filter(None);
     }
 
     /// Connect node to the network
     pub fn connect_node_to_network(&self, node: &str) {
-        self.inner.network().connect_node(node)
+        self.inner.network().connect_node(node);
+        self.node_manager.borrow_mut().links_mut().connect(node);
     }
 
     /// Disconnect node from the network
     pub fn disconnect_node_from_network(&self, node: &str) {
-        self.inner.network().disconnect_node(node)
+        self.inner.network().disconnect_node(node);
+        self.node_manager.borrow_mut().links_mut().disconnect(node);
     }
 
     /// Allows to disable pairwise connections between groups.
+    ///
+    /// Connections can be restored with [`Sim::heal_network`].
     pub fn split_network(&self, group1: &[&str], group2: &[&str]) {
-        self.inner.network().make_partition(group1, group2)
+        self.inner.network().make_partition(group1, group2);
+        let mut node_manager = self.node_manager.borrow_mut();
+        for from in group1 {
+            for to in group2 {
+                node_manager.links_mut().partition(from, to);
+                node_manager.links_mut().partition(to, from);
+            }
+        }
+    }
+
+    /// Split network into the specified groups of nodes,
+    /// so messages are delivered only between nodes of the same group.
+    ///
+    /// Nodes, which are not listed in any group, form one more group.
+    /// Previous partitions of the network are removed.
+    ///
+    /// # Panics
+    ///
+    /// - If some node does not exist.
+    /// - If some node is listed in multiple groups.
+    pub fn partition(&self, groups: &[&[&str]]) {
+        let nodes = self.node_manager.borrow().node_names();
+        let mut node_groups = HashMap::new();
+        for (group_index, group) in groups.iter().enumerate() {
+            for node in group.iter() {
+                assert!(
+                    nodes.iter().any(|name| name == node),
+                    "Node with name {} does not exist.",
+                    node
+                );
+                assert!(
+                    node_groups.insert(node.to_string(), group_index).is_none(),
+                    "Node {} is listed in multiple groups.",
+                    node
+                );
+            }
+        }
+
+        self.heal_partitions();
+        let group = |node: &String| node_groups.get(node).copied().unwrap_or(groups.len());
+        let mut node_manager = self.node_manager.borrow_mut();
+        for from in nodes.iter() {
+            for to in nodes.iter().filter(|to| group(from) != group(to)) {
+                self.inner.network().disable_link(from, to);
+                node_manager.links_mut().partition(from, to);
+            }
+        }
+    }
+
+    /// Isolate node from other nodes for the specified `duration` of virtual time.
+    ///
+    /// Network heals automatically when duration passes.
+    /// Isolation is applied to the messages when they are sent,
+    /// so messages sent before isolation are still delivered.
+    ///
+    /// # Panics
+    ///
+    /// - If node does not exist.
+    /// - If duration is negative.
+    pub fn isolate_node(&self, node: &str, duration: f64) {
+        self.check_link(node, node);
+        assert!(
+            duration >= 0.0,
+            "Incorrect isolation duration: {}",
+            duration
+        );
+        let until = self.time() + duration;
+        self.node_manager
+            .borrow_mut()
+            .links_mut()
+            .isolate(node, until);
+    }
+
+    /// Set rate of the clock of the node, which measures delays of the timers
+    /// and sleeps of its processes.
+    ///
+    /// Timer set with delay `d` fires after `d / rate` of virtual time, so with rate
+    /// above `1.0` timers fire earlier, as if clock of the node runs faster, and vice versa.
+    /// Timers set before the call are not affected. Rate `1.0` restores the normal clock.
+    ///
+    /// # Panics
+    ///
+    /// - If node does not exist.
+    /// - If rate is not positive.
+    pub fn set_clock_rate(&self, node: &str, rate: f64) {
+        self.check_link(node, node);
+        assert!(rate > 0.0, "Incorrect clock rate: {}", rate);
+        self.node_manager.borrow_mut().set_clock_rate(node, rate);
+    }
+
+    /// Remove all partitions of the network and isolations of the nodes,
+    /// which were made with [`Sim::split_network`], [`Sim::partition`] and [`Sim::isolate_node`].
+    ///
+    /// [Blocked][Sim::block_link] links and [disconnected][Sim::disconnect_node_from_network]
+    /// nodes are not affected.
+    pub fn heal_network(&self) {
+        self.heal_partitions();
+        self.node_manager
+            .borrow_mut()
+            .links_mut()
+            .cancel_isolation();
+    }
+
+    /// Checks if the link from node `from` to node `to` is blocked by partition.
+    pub(crate) fn is_partitioned(&self, from: &str, to: &str) -> bool {
+        self.node_manager.borrow().links().is_partitioned(from, to)
+    }
+
+    /// Removes partition of the link from node `from` to node `to`,
+    /// other partitions and [blocks][Sim::block_link] are kept.
+    pub(crate) fn remove_partition(&self, from: &str, to: &str) {
+        if self
+            .node_manager
+            .borrow_mut()
+            .links_mut()
+            .unpartition(from, to)
+        {
+            self.inner.network().enable_link(from, to);
+        }
+    }
+
+    fn heal_partitions(&self) {
+        let links = self.node_manager.borrow_mut().links_mut().heal_partitions();
+        for (from, to) in links {
+            self.inner.network().enable_link(&from, &to);
+        }
+    }
+
+    /// Returns current layout of the network partition: sorted groups of node names.
+    ///
+    /// Nodes are in the same group if they can exchange messages with the same set of nodes.
+    /// So groups match the [partition][Sim::partition] of the network,
+    /// while isolated and disconnected nodes form separate groups.
+    pub fn partition_layout(&self) -> Vec<Vec<String>> {
+        let node_manager = self.node_manager.borrow();
+        let links = node_manager.links();
+        let nodes = node_manager.node_names();
+        let time = self.time();
+        let mut groups: BTreeMap<Vec<&String>, Vec<String>> = BTreeMap::new();
+        for node in nodes.iter() {
+            let peers = nodes
+                .iter()
+                .filter(|peer| {
+                    !links.is_blocked(node, peer, time) && !links.is_blocked(peer, node, time)
+                })
+                .collect::<Vec<_>>();
+            groups.entry(peers).or_default().push(node.clone());
+        }
+        let mut layout = groups.into_values().collect::<Vec<_>>();
+        layout.sort();
+        layout
+    }
+
+    // Links --------------------------------------------------------
+
+    /// Set the fixed additional delay of the messages sent from node `from` to node `to`.
+    ///
+    /// See [`Sim::set_link_delays`] for more details.
+    pub fn set_link_delay(&self, from: &str, to: &str, delay: f64) {
+        self.set_link_delays(from, to, delay, delay)
+    }
+
+    /// Set the minimum and maximum additional delays of the messages sent
+    /// from node `from` to node `to`.
+    ///
+    /// Links are directed, so messages sent from `to` to `from` are not affected.
+    /// Delay of the link is added to the [network delay][Sim::set_network_delays],
+    /// which allows to model slow nodes or distant data centers.
+    ///
+    /// # Panics
+    ///
+    /// - If node `from` or node `to` does not exist.
+    /// - If delays are negative or `min_delay` is greater than `max_delay`.
+    pub fn set_link_delays(&self, from: &str, to: &str, min_delay: f64, max_delay: f64) {
+        self.check_link(from, to);
+        self.node_manager
+            .borrow_mut()
+            .links_mut()
+            .set_delays(from, to, min_delay, max_delay);
+    }
+
+    /// Set drop rate of the messages sent from node `from` to node `to`.
+    ///
+    /// Messages are dropped by the link in addition to the [network drops][Sim::set_network_drop_rate].
+    /// As for the network, reliable sends are not affected by drop rate of the link.
+    ///
+    /// # Panics
+    ///
+    /// - If node `from` or node `to` does not exist.
+    /// - If drop rate is not in range `[0, 1]`.
+    pub fn set_link_drop_rate(&self, from: &str, to: &str, drop_rate: f64) {
+        self.check_link(from, to);
+        self.node_manager
+            .borrow_mut()
+            .links_mut()
+            .set_drop_rate(from, to, drop_rate);
+    }
+
+    /// Block messages sent from node `from` to node `to`.
+    ///
+    /// Messages sent in the opposite direction are still delivered.
+    ///
+    /// # Panics
+    ///
+    /// - If node `from` or node `to` does not exist.
+    pub fn block_link(&self, from: &str, to: &str) {
+        self.check_link(from, to);
+        self.inner.network().disable_link(from, to);
+        self.node_manager.borrow_mut().links_mut().block(from, to);
+    }
+
+    /// Unblock previously [blocked][Sim::block_link] link from node `from` to node `to`.
+    ///
+    /// # Panics
+    ///
+    /// - If node `from` or node `to` does not exist.
+    pub fn unblock_link(&self, from: &str, to: &str) {
+        self.check_link(from, to);
+        if self.node_manager.borrow_mut().links_mut().unblock(from, to) {
+            self.inner.network().enable_link(from, to);
+        }
+    }
+
+    /// Reset settings of the link from node `from` to node `to`,
+    /// so messages sent over it behave as the rest of the network.
+    ///
+    /// # Panics
+    ///
+    /// - If node `from` or node `to` does not exist.
+    pub fn reset_link(&self, from: &str, to: &str) {
+        self.check_link(from, to);
+        if self.node_manager.borrow_mut().links_mut().reset(from, to) {
+            self.inner.network().enable_link(from, to);
+        }
+    }
+
+    /// Returns settings of the link from node `from` to node `to` without network settings.
+    pub(crate) fn link_config(&self, from: &str, to: &str) -> Option<LinkConfig> {
+        self.node_manager.borrow().links().config(from, to)
+    }
+
+    /// Restores settings of the link from node `from` to node `to` returned by [`Sim::link_config`],
+    /// [blocks][Sim::block_link] of the link are kept.
+    pub(crate) fn restore_link_config(&self, from: &str, to: &str, config: Option<LinkConfig>) {
+        self.node_manager
+            .borrow_mut()
+            .links_mut()
+            .set_config(from, to, config);
+    }
+
+    /// Returns effective settings of the link from node `from` to node `to`,
+    /// which combine network settings and settings of the link.
+    ///
+    /// # Panics
+    ///
+    /// - If node `from` or node `to` does not exist.
+    pub fn link_settings(&self, from: &str, to: &str) -> LinkSettings {
+        self.check_link(from, to);
+        self.node_manager
+            .borrow()
+            .links()
+            .settings(from, to, self.time())
+    }
+
+    fn check_link(&self, from: &str, to: &str) {
+        let node_manager = self.node_manager.borrow();
+        for node in [from, to] {
+            assert!(
+                node_manager.check_node_exists(node),
+                "Node with name {} does not exist.",
+                node
+            );
+        }
     }
 
     ////////////////////////////////////////////////////////////////////////////////
@@ -136,6 +548,7 @@ impl Sim {
     pub fn crash_node(&mut self, node_name: &str) {
         self.inner.crash_node(node_name);
         self.node_manager.borrow_mut().clear_node(node_name);
+        self.record_node_event(node_name, TraceEventKind::NodeCrashed);
     }
 
     /// Recovers the previously crashed node.
@@ -144,17 +557,22 @@ impl Sim {
     /// The delivery of events to the node is enabled.
     pub fn recover_node(&mut self, node_name: &str) {
         self.inner.recover_node(node_name);
+        self.clear_processes(node_name);
+        self.record_node_event(node_name, TraceEventKind::NodeRecovered);
     }
 
     /// Shutdowns the specified node with saving storage.
     pub fn shutdown_node(&mut self, node_name: &str) {
         self.inner.shutdown_node(node_name);
         self.node_manager.borrow_mut().clear_node(node_name);
+        self.record_node_event(node_name, TraceEventKind::NodeShutdown);
     }
 
     /// Reruns previously shut node.
     pub fn rerun_node(&mut self, node_name: &str) {
         self.inner.rerun_node(node_name);
+        self.clear_processes(node_name);
+        self.record_node_event(node_name, TraceEventKind::NodeRerun);
     }
 
     /// Checks if the node is crashed.
@@ -196,8 +614,17 @@ impl Sim {
 
         // Configure virtual process wrapper.
         let node_manager_ref = self.node_manager.clone();
-        let virtual_proc_wrapper =
-            VirtualProcessWrapper::new(process_ref.clone(), node_manager_ref);
+        let membership = self
+            .node_manager
+            .borrow()
+            .membership_view(node_name)
+            .unwrap();
+        let virtual_proc_wrapper = VirtualProcessWrapper::new(
+            process_ref.clone(),
+            node_manager_ref,
+            membership,
+            process_address,
+        );
 
         // Configure wrapper to the dslab.
         let process_wrapper = ProcessWrapper { process_ref };
@@ -207,10 +634,245 @@ impl Sim {
         self.inner
             .add_process(&full_process_name, boxed_wrapper, node_name);
 
+        // Register process wrapper for invariants.
+        self.processes.insert(
+            (node_name.to_owned(), process_name.to_owned()),
+            Box::new(ProcessWrapper {
+                process_ref: process_wrapper.process_ref.clone(),
+            }),
+        );
+
         // Return process wrapper to user.
         process_wrapper
     }
 
+    /// Returns names of the nodes and processes together with the process wrappers.
+    pub(crate) fn registered_processes(&self) -> impl Iterator<Item = (&str, &str, &dyn Any)> {
+        self.processes
+            .iter()
+            .map(|((node, process), wrapper)| (node.as_str(), process.as_str(), wrapper.as_ref()))
+    }
+
+    fn clear_processes(&mut self, node_name: &str) {
+        self.processes.retain(|(node, _), _| node != node_name);
+    }
+
+    // Membership ---------------------------------------------------
+
+    /// Enables [membership service][MembershipConfig] on the node.
+    ///
+    /// Method adds [membership process][MEMBERSHIP_PROCESS] on the node and starts it.
+    /// Processes of the node can query cluster members using
+    /// [`Context::members`][crate::Context::members] and subscribe to membership changes.
+    ///
+    /// After the node is crashed or shut down, membership must be enabled again
+    /// when node is recovered.
+    ///
+    /// Note that membership process periodically sets timers, so
+    /// [`step_until_no_events`][Sim::step_until_no_events] will not return while it is running.
+    /// Use [`step_for_duration`][Sim::step_for_duration] instead.
+    ///
+    /// # Panics
+    ///
+    /// - If node with such name `node_name` does not exists.
+    /// - If membership is already enabled on the node.
+    pub fn enable_membership(&mut self, node_name: &str, config: MembershipConfig) {
+        let node_address = self
+            .node_manager
+            .borrow()
+            .get_node_address(node_name)
+            .unwrap();
+        let membership = self
+            .node_manager
+            .borrow()
+            .membership_view(node_name)
+            .unwrap();
+        let process =
+            MembershipProcess::new(config, membership, &node_address.host, node_address.port);
+        self.add_process(MEMBERSHIP_PROCESS, process, node_name);
+        self.send_local_message(
+            MEMBERSHIP_PROCESS,
+            node_name,
+            Message::new(MEMBERSHIP_START, &()).unwrap(),
+        );
+    }
+
+    /// Returns members of the cluster known to the node.
+    ///
+    /// See [`Context::members`][crate::Context::members] for more details.
+    pub fn members(&self, node_name: &str) -> Vec<Member> {
+        self.node_manager
+            .borrow()
+            .membership_view(node_name)
+            .unwrap()
+            .members()
+    }
+
+    // Trace --------------------------------------------------------
+
+    /// Enable recording of the simulation events to the trace.
+    ///
+    /// Trace contains [events][TraceEventKind] with their virtual time, node and process:
+    /// sends, deliveries and drops of the messages, local messages, timers, file operations,
+    /// crashes and recoveries of the nodes. Events, which happened before the trace is enabled,
+    /// are not recorded.
+    pub fn enable_trace(&self) {
+        self.node_manager.borrow_mut().trace_mut().enable();
+    }
+
+    /// Returns events recorded in the [trace][Sim::enable_trace] in the order they happened.
+    pub fn trace(&self) -> Vec<TraceEvent> {
+        self.node_manager.borrow().trace().events().to_vec()
+    }
+
+    /// Writes events recorded in the [trace][Sim::enable_trace] to the file
+    /// in JSON lines format, one event per line.
+    pub fn export_trace(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut output = BufWriter::new(std::fs::File::create(path)?);
+        for event in self.node_manager.borrow().trace().events() {
+            serde_json::to_writer(&mut output, event)?;
+            output.write_all(b"\n")?;
+        }
+        output.flush()
+    }
+
+    /// Returns sequence diagram of the message flows recorded in the [trace][Sim::enable_trace].
+    ///
+    /// Diagram shows delivered and dropped messages, local messages, crashes and recoveries
+    /// of the nodes. Events can be selected with the [`filter`][DiagramFilter].
+    pub fn sequence_diagram(&self, format: DiagramFormat, filter: &DiagramFilter) -> String {
+        let node_manager = self.node_manager.borrow();
+        Diagram::build(node_manager.trace().events(), filter, |address| {
+            node_manager.node_name(address)
+        })
+        .render(format)
+    }
+
+    /// Writes [sequence diagram][Sim::sequence_diagram] to the file.
+    pub fn export_sequence_diagram(
+        &self,
+        path: impl AsRef<Path>,
+        format: DiagramFormat,
+        filter: &DiagramFilter,
+    ) -> std::io::Result<()> {
+        std::fs::write(path, self.sequence_diagram(format, filter))
+    }
+
+    fn record_node_event(&self, node: &str, kind: TraceEventKind) {
+        let event = TraceEvent {
+            time: self.time(),
+            node: node.to_owned(),
+            process: None,
+            kind,
+        };
+        self.node_manager.borrow_mut().trace_mut().record(event);
+    }
+
+    // Invariants ---------------------------------------------------
+
+    /// Add invariant, which is checked after every step of the simulation.
+    ///
+    /// Invariant gets [view][SimView] of the simulation with read access to the processes
+    /// and returns description of the violation in case of error.
+    /// Invariants are checked in the order they were added.
+    ///
+    /// On the first violation simulation panics with report, which contains name of the invariant,
+    /// description of the violation, virtual time and the last events of the [trace][Sim::enable_trace]
+    /// if it is enabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dsbuild::{Address, Context, Message, Process, Sim};
+    ///
+    /// #[derive(Default)]
+    /// struct Counter {
+    ///     value: u64,
+    /// }
+    ///
+    /// impl Process for Counter {
+    ///     fn on_local_message(&mut self, _msg: Message, _ctx: Context) {
+    ///         self.value += 1;
+    ///     }
+    ///
+    ///     fn on_timer(&mut self, _name: String, _ctx: Context) {}
+    ///
+    ///     fn on_message(&mut self, _msg: Message, _from: Address, _ctx: Context) {}
+    /// }
+    ///
+    /// let mut sim = Sim::new(12345);
+    /// sim.add_node("node", "10.0.0.1", 10024);
+    /// sim.add_process("counter", Counter::default(), "node");
+    /// sim.add_invariant("counter is small", |view| {
+    ///     let counter = view.process::<Counter>("node", "counter").unwrap();
+    ///     if counter.value > 10 {
+    ///         Err(format!("value is {}", counter.value))
+    ///     } else {
+    ///         Ok(())
+    ///     }
+    /// });
+    ///
+    /// sim.send_local_message("counter", "node", Message::new("inc", &()).unwrap());
+    /// sim.step_until_no_events();
+    /// ```
+    pub fn add_invariant(
+        &mut self,
+        name: &str,
+        invariant: impl Fn(&SimView) -> Result<(), String> + 'static,
+    ) {
+        self.invariants.push((name.to_owned(), Box::new(invariant)));
+    }
+
+    /// Checks invariants and panics with report on the first violation.
+    fn check_invariants(&self) {
+        let view = SimView::new(self);
+        for (name, invariant) in self.invariants.iter() {
+            if let Err(reason) = invariant(&view) {
+                panic!("{}", self.violation_report(name, &reason));
+            }
+        }
+    }
+
+    fn violation_report(&self, name: &str, reason: &str) -> String {
+        let mut report = format!(
+            "Invariant '{}' violated at time {:.3}: {}",
+            name,
+            self.time(),
+            reason
+        );
+        let node_manager = self.node_manager.borrow();
+        let events = node_manager.trace().events();
+        if !events.is_empty() {
+            report.push_str("\nLast events:");
+            for event in &events[events.len().saturating_sub(REPORTED_EVENTS)..] {
+                report.push('\n');
+                report.push_str(&serde_json::to_string(event).unwrap());
+            }
+        }
+        report
+    }
+
+    // Model checking -----------------------------------------------
+
+    /// Enable model checking mode, in which messages sent with [`send`][crate::Context::send]
+    /// and timers are held until [model checker][crate::ModelChecker] applies them.
+    pub(crate) fn enable_controlled_delivery(&self) {
+        self.node_manager.borrow_mut().enable_controller();
+    }
+
+    /// Calls `f` with the controller of the messages and timers.
+    ///
+    /// # Panics
+    ///
+    /// - If model checking mode is not enabled.
+    pub(crate) fn with_controller<R>(&self, f: impl FnOnce(&mut Controller) -> R) -> R {
+        f(self
+            .node_manager
+            .borrow_mut()
+            .controller_mut()
+            .expect("Model checking mode is not enabled."))
+    }
+
     /// Get names of all processes in the system.
     pub fn process_names(&self) -> Vec<String> {
         self.inner.process_names()
@@ -263,7 +925,11 @@ impl Sim {
 
     /// Steps through the simulation until there are no pending events left.
     pub fn step_until_no_events(&mut self) {
-        self.inner.step_until_no_events()
+        if self.invariants.is_empty() {
+            self.inner.step_until_no_events()
+        } else {
+            while self.step() {}
+        }
     }
 
     /// Steps through the simulation until there are no local messages.
@@ -278,12 +944,35 @@ impl Sim {
             .construct_full_process_name(proc, node)
             .unwrap();
 
+        if !self.invariants.is_empty() {
+            loop {
+                if let Some(messages) = self.read_local_messages(proc, node) {
+                    return Ok(messages);
+                }
+                if !self.step() {
+                    return Err("No events left".to_owned());
+                }
+            }
+        }
+
         self.inner
             .step_until_local_message(&full_process_name)
             .map_err(|str| str.to_owned())
             .map(|v| v.into_iter().map(|m| m.into()).collect())
     }
 
+    /// Steps through the simulation until the specified duration in seconds passes
+    /// or there are no pending events left.
+    pub fn step_for_duration(&mut self, duration: f64) {
+        let end_time = self.time() + duration;
+        while self.time() < end_time && self.step() {}
+    }
+
+    /// Returns current time of the simulation in seconds.
+    pub fn time(&self) -> f64 {
+        self.inner.time()
+    }
+
     /// Perform specified number of steps through the simulation.
     pub fn make_steps(&mut self, steps: u32) {
         for _ in 0..steps {
@@ -295,7 +984,15 @@ impl Sim {
     }
 
     /// Perform single step through the simulation.
+    ///
+    /// # Panics
+    ///
+    /// - If some of the [invariants][Sim::add_invariant] is violated after the step.
     pub fn step(&mut self) -> bool {
-        self.inner.step()
+        let something_happen = self.inner.step();
+        if something_happen {
+            self.check_invariants();
+        }
+        something_happen
     }
 }
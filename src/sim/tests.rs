@@ -1,9 +1,21 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
+
 use rand::{distributions::Alphanumeric, seq::SliceRandom, Rng};
 use rand_pcg::Pcg64;
 use rand_seeder::Seeder;
 use serde::{Deserialize, Serialize};
 
-use crate::{Address, Context, Message, Process, Sim};
+use crate::{
+    Address, ClusterError, ClusterSpec, Context, DetectorEvent, DiagramFilter, DiagramFormat,
+    FailureDetector, Fault, FaultPlan, FsError, History, LinearizabilityError, LinkSettings,
+    McEvent, McStats, Member, MemberState, MembershipConfig, Message, MessageAction, ModelChecker,
+    Nemesis, Process, ProcessFactory, ReorderMode, RetryPolicy, ScheduledFault, SearchStrategy,
+    SeedRunner, SendError, Sim, TraceEvent, TraceEventKind, MAX_MESSAGE_SIZE, MEMBERSHIP_EVENT,
+};
 
 struct StorageProc {}
 
@@ -168,3 +180,2009 @@ fn storage_stress() {
         }
     }
 }
+
+struct RetryingSender {
+    receiver: Address,
+}
+
+impl Process for RetryingSender {
+    fn on_local_message(&mut self, msg: Message, ctx: Context) {
+        let to = self.receiver.clone();
+        ctx.clone().spawn(async move {
+            let result = ctx
+                .with_retry_policy(RetryPolicy::new(5).with_backoff(1.0, 1.0))
+                .send_with_ack(msg, to, 5.0)
+                .await;
+            ctx.send_local(Message::new("result", &result.is_ok()).unwrap());
+        });
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {
+        unreachable!()
+    }
+
+    fn on_message(&mut self, _msg: Message, _from: Address, _ctx: Context) {
+        unreachable!()
+    }
+}
+
+struct Receiver {}
+
+impl Process for Receiver {
+    fn on_local_message(&mut self, _msg: Message, _ctx: Context) {
+        unreachable!()
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {
+        unreachable!()
+    }
+
+    fn on_message(&mut self, msg: Message, _from: Address, ctx: Context) {
+        ctx.send_local(msg);
+    }
+}
+
+#[test]
+fn send_with_ack_retries_works() {
+    let mut sys = Sim::new(12345);
+    sys.add_node("sender", "sender", 10);
+    sys.add_node("receiver", "receiver", 10);
+    sys.add_process(
+        "sender",
+        RetryingSender {
+            receiver: Address::new_ref("receiver", 10, "receiver"),
+        },
+        "sender",
+    );
+
+    // First attempt fails, because receiver process does not exist yet.
+    sys.send_local_message("sender", "sender", "hello".into());
+    sys.step();
+    sys.add_process("receiver", Receiver {}, "receiver");
+    sys.step_until_no_events();
+
+    let messages = sys.read_local_messages("sender", "sender").unwrap();
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].data::<bool>().unwrap());
+    let messages = sys.read_local_messages("receiver", "receiver").unwrap();
+    assert_eq!(messages, vec!["hello".into()]);
+}
+
+#[test]
+fn send_with_ack_retries_exhausted() {
+    let mut sys = Sim::new(12345);
+    sys.add_node("sender", "sender", 10);
+    sys.add_node("receiver", "receiver", 10);
+    sys.add_process(
+        "sender",
+        RetryingSender {
+            receiver: Address::new_ref("receiver", 10, "receiver"),
+        },
+        "sender",
+    );
+
+    sys.send_local_message("sender", "sender", "hello".into());
+    sys.step_until_no_events();
+
+    let messages = sys.read_local_messages("sender", "sender").unwrap();
+    assert_eq!(messages.len(), 1);
+    assert!(!messages[0].data::<bool>().unwrap());
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+struct MembershipWatcher {}
+
+impl Process for MembershipWatcher {
+    fn on_local_message(&mut self, msg: Message, ctx: Context) {
+        match msg.data::<String>().unwrap().as_str() {
+            "subscribe" => ctx.subscribe_members(),
+            "leave" => ctx.leave_cluster(),
+            _ => {}
+        }
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {}
+
+    fn on_message(&mut self, msg: Message, _from: Address, ctx: Context) {
+        if msg.tip() == MEMBERSHIP_EVENT {
+            ctx.send_local(msg);
+        }
+    }
+}
+
+fn build_membership_sim(seed: u64, nodes: usize) -> Sim {
+    let mut sys = Sim::new(seed);
+    sys.set_network_delays(0.05, 0.1);
+    let config = MembershipConfig::new(vec![Address::new_ref("node0", 10, "")])
+        .with_protocol_period(0.5, 0.25)
+        .with_suspect_timeout(2.0);
+    for i in 0..nodes {
+        let node = format!("node{}", i);
+        sys.add_node(&node, &node, 10);
+        sys.add_process("watcher", MembershipWatcher {}, &node);
+        sys.send_local_message("watcher", &node, "subscribe".into());
+        sys.enable_membership(&node, config.clone());
+    }
+    sys
+}
+
+fn member_states(sys: &Sim, node: &str) -> Vec<(String, MemberState)> {
+    sys.members(node)
+        .into_iter()
+        .map(|member| (member.address.host, member.state))
+        .collect()
+}
+
+#[test]
+fn membership_converges() {
+    let mut sys = build_membership_sim(123, 4);
+    sys.step_for_duration(10.0);
+
+    for i in 0..4 {
+        let mut states = member_states(&sys, &format!("node{}", i));
+        states.sort_by(|a, b| a.0.cmp(&b.0));
+        let expected = (0..4)
+            .map(|j| (format!("node{}", j), MemberState::Alive))
+            .collect::<Vec<_>>();
+        assert_eq!(states, expected);
+    }
+
+    let events = sys.read_local_messages("watcher", "node1").unwrap();
+    assert_eq!(events.len(), 3);
+    for event in events {
+        assert_eq!(event.data::<Member>().unwrap().state, MemberState::Alive);
+    }
+}
+
+#[test]
+fn membership_ignores_corrupted_messages() {
+    let mut sys = build_membership_sim(123, 4);
+    sys.set_network_corrupt_rate(0.3);
+    sys.step_for_duration(10.0);
+
+    // Malformed messages are ignored, and members made up from the corrupted ones
+    // do not respond to probes, so they are not alive.
+    sys.set_network_corrupt_rate(0.0);
+    sys.step_for_duration(20.0);
+    let nodes = (0..4).map(|i| format!("node{}", i)).collect::<Vec<_>>();
+    for node in nodes.iter() {
+        let mut alive = member_states(&sys, node)
+            .into_iter()
+            .filter(|(_, state)| *state == MemberState::Alive)
+            .map(|(host, _)| host)
+            .collect::<Vec<_>>();
+        alive.sort();
+        assert_eq!(alive, nodes);
+    }
+}
+
+#[test]
+fn membership_detects_crash() {
+    let mut sys = build_membership_sim(123, 4);
+    sys.step_for_duration(10.0);
+    sys.read_local_messages("watcher", "node1");
+
+    sys.crash_node("node3");
+    sys.step_for_duration(20.0);
+
+    for i in 0..3 {
+        let states = member_states(&sys, &format!("node{}", i));
+        assert!(states.contains(&("node3".to_owned(), MemberState::Dead)));
+        assert_eq!(
+            states
+                .iter()
+                .filter(|(_, state)| *state == MemberState::Alive)
+                .count(),
+            3
+        );
+    }
+
+    let events = sys
+        .read_local_messages("watcher", "node1")
+        .unwrap()
+        .into_iter()
+        .map(|event| event.data::<Member>().unwrap())
+        .collect::<Vec<_>>();
+    assert!(events
+        .iter()
+        .all(|member| member.address.host == "node3" && member.state != MemberState::Alive));
+    assert_eq!(events.last().unwrap().state, MemberState::Dead);
+
+    // Recovered node rejoins with higher incarnation.
+    sys.recover_node("node3");
+    sys.enable_membership(
+        "node3",
+        MembershipConfig::new(vec![Address::new_ref("node0", 10, "")])
+            .with_protocol_period(0.5, 0.25)
+            .with_suspect_timeout(2.0),
+    );
+    sys.step_for_duration(10.0);
+    for i in 0..4 {
+        let states = member_states(&sys, &format!("node{}", i));
+        assert!(states.iter().all(|(_, state)| *state == MemberState::Alive));
+    }
+}
+
+#[test]
+fn membership_leave_works() {
+    let mut sys = build_membership_sim(123, 3);
+    sys.step_for_duration(10.0);
+
+    sys.send_local_message("watcher", "node2", "leave".into());
+    sys.step_for_duration(5.0);
+
+    for i in 0..3 {
+        let states = member_states(&sys, &format!("node{}", i));
+        assert!(states.contains(&("node2".to_owned(), MemberState::Left)));
+    }
+}
+
+#[test]
+fn membership_is_deterministic() {
+    let run = || {
+        let mut sys = build_membership_sim(321, 5);
+        sys.set_network_drop_rate(0.1);
+        sys.step_for_duration(30.0);
+        (0..5)
+            .map(|i| sys.members(&format!("node{}", i)))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(run(), run());
+}
+
+#[test]
+fn membership_probe_order_depends_on_seed() {
+    // Returns nodes probed by the first node, network delays are fixed,
+    // so only the seed of the membership process affects the order.
+    let probes = |seed: u64| {
+        let mut sys = build_membership_sim(seed, 8);
+        sys.set_network_delays(0.05, 0.05);
+        sys.enable_trace();
+        sys.step_for_duration(20.0);
+        sys.trace()
+            .into_iter()
+            .filter(|event| event.node == "node0")
+            .filter_map(|event| match event.kind {
+                TraceEventKind::MessageSent { to, tip, .. } if tip == "swim_ping" => Some(to.host),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(probes(1), probes(1));
+    assert_ne!(probes(1), probes(2));
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+struct MonitoringProcess {
+    detector: FailureDetector,
+}
+
+impl MonitoringProcess {
+    fn report(events: Vec<DetectorEvent>, ctx: &Context) {
+        for event in events {
+            let msg = match event {
+                DetectorEvent::Suspect(peer) => Message::new("suspect", &peer.host),
+                DetectorEvent::Alive(peer) => Message::new("alive", &peer.host),
+            };
+            ctx.send_local(msg.unwrap());
+        }
+    }
+}
+
+impl Process for MonitoringProcess {
+    fn on_local_message(&mut self, _msg: Message, ctx: Context) {
+        self.detector.start(&ctx);
+    }
+
+    fn on_timer(&mut self, name: String, ctx: Context) {
+        let events = self.detector.on_timer(&name, &ctx).unwrap();
+        Self::report(events, &ctx);
+    }
+
+    fn on_message(&mut self, msg: Message, from: Address, ctx: Context) {
+        let events = self.detector.on_message(&msg, &from, &ctx).unwrap();
+        Self::report(events, &ctx);
+    }
+}
+
+fn check_failure_detector(make_detector: impl Fn(Vec<Address>) -> FailureDetector) {
+    let mut sys = Sim::new(12345);
+    sys.set_network_delays(0.05, 0.15);
+    let nodes = ["node1", "node2", "node3"];
+    for node in nodes {
+        sys.add_node(node, node, 10);
+    }
+    for node in nodes {
+        let peers = nodes
+            .iter()
+            .filter(|peer| **peer != node)
+            .map(|peer| Address::new_ref(peer, 10, "monitor"))
+            .collect();
+        let detector = make_detector(peers);
+        sys.add_process("monitor", MonitoringProcess { detector }, node);
+        sys.send_local_message("monitor", node, "start".into());
+    }
+
+    let events = |sys: &mut Sim, node: &str| {
+        sys.read_local_messages("monitor", node)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|msg| (msg.tip().clone(), msg.data::<String>().unwrap()))
+            .collect::<Vec<_>>()
+    };
+
+    // No false suspicions while network is stable.
+    sys.step_for_duration(20.0);
+    for node in nodes {
+        assert!(events(&mut sys, node).is_empty());
+    }
+
+    // Partitioned node is suspected and suspects others.
+    sys.disconnect_node_from_network("node3");
+    sys.step_for_duration(10.0);
+    assert_eq!(
+        events(&mut sys, "node1"),
+        vec![("suspect".to_owned(), "node3".to_owned())]
+    );
+    let mut node3_events = events(&mut sys, "node3");
+    node3_events.sort();
+    assert_eq!(
+        node3_events,
+        vec![
+            ("suspect".to_owned(), "node1".to_owned()),
+            ("suspect".to_owned(), "node2".to_owned())
+        ]
+    );
+
+    // Node is alive again after partition heals.
+    sys.connect_node_to_network("node3");
+    sys.step_for_duration(10.0);
+    assert_eq!(
+        events(&mut sys, "node2"),
+        vec![
+            ("suspect".to_owned(), "node3".to_owned()),
+            ("alive".to_owned(), "node3".to_owned())
+        ]
+    );
+}
+
+struct TimerSharingProcess {
+    detector: FailureDetector,
+}
+
+impl Process for TimerSharingProcess {
+    fn on_local_message(&mut self, _msg: Message, ctx: Context) {
+        self.detector.start(&ctx);
+        ctx.set_timer("failure_detector", 1.0);
+    }
+
+    fn on_timer(&mut self, name: String, ctx: Context) {
+        match self.detector.on_timer(&name, &ctx) {
+            Some(events) => MonitoringProcess::report(events, &ctx),
+            None => ctx.send_local(Message::new("timer", &name).unwrap()),
+        }
+    }
+
+    fn on_message(&mut self, msg: Message, from: Address, ctx: Context) {
+        let events = self.detector.on_message(&msg, &from, &ctx).unwrap();
+        MonitoringProcess::report(events, &ctx);
+    }
+}
+
+#[test]
+fn failure_detector_uses_timer_name() {
+    let mut sys = Sim::new(12345);
+    sys.set_network_delays(0.05, 0.15);
+    for (node, peer) in [("node1", "node2"), ("node2", "node1")] {
+        sys.add_node(node, node, 10);
+        let detector =
+            FailureDetector::heartbeat(vec![Address::new_ref(peer, 10, "monitor")], 0.5, 2.0)
+                .with_timer_name("heartbeat");
+        sys.add_process("monitor", TimerSharingProcess { detector }, node);
+    }
+    for node in ["node1", "node2"] {
+        sys.send_local_message("monitor", node, "start".into());
+    }
+
+    let events = |sys: &mut Sim, node: &str| {
+        sys.read_local_messages("monitor", node)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|msg| (msg.tip().clone(), msg.data::<String>().unwrap()))
+            .collect::<Vec<_>>()
+    };
+
+    // Timer of the process is not taken by detector, which keeps sending heartbeats.
+    sys.step_for_duration(10.0);
+    for node in ["node1", "node2"] {
+        assert_eq!(
+            events(&mut sys, node),
+            vec![("timer".to_owned(), "failure_detector".to_owned())]
+        );
+    }
+
+    sys.disconnect_node_from_network("node2");
+    sys.step_for_duration(10.0);
+    assert_eq!(
+        events(&mut sys, "node1"),
+        vec![("suspect".to_owned(), "node2".to_owned())]
+    );
+}
+
+#[test]
+fn heartbeat_failure_detector_works() {
+    check_failure_detector(|peers| FailureDetector::heartbeat(peers, 0.5, 2.0));
+}
+
+#[test]
+fn phi_accrual_failure_detector_works() {
+    check_failure_detector(|peers| FailureDetector::phi_accrual(peers, 0.5, 8.0));
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+struct AckSender {
+    receiver: Address,
+}
+
+impl Process for AckSender {
+    fn on_local_message(&mut self, msg: Message, ctx: Context) {
+        let to = self.receiver.clone();
+        ctx.clone().spawn(async move {
+            let result = ctx.send_with_ack(msg, to, 5.0).await;
+            ctx.send_local(
+                Message::new("result", &(result == Err(SendError::ProcessNotFound))).unwrap(),
+            );
+        });
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {}
+
+    fn on_message(&mut self, _msg: Message, _from: Address, _ctx: Context) {}
+}
+
+#[test]
+fn send_to_unknown_process_fails() {
+    let mut sys = Sim::new(12345);
+    sys.add_node("sender", "sender", 10);
+    sys.add_node("receiver", "receiver", 10);
+    sys.add_process(
+        "sender",
+        AckSender {
+            receiver: Address::new_ref("receiver", 10, "missing"),
+        },
+        "sender",
+    );
+
+    sys.send_local_message("sender", "sender", "hello".into());
+    sys.step_until_no_events();
+
+    let messages = sys.read_local_messages("sender", "sender").unwrap();
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].data::<bool>().unwrap());
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+struct ErrorProc {}
+
+impl Process for ErrorProc {
+    fn on_local_message(&mut self, _msg: Message, ctx: Context) {
+        ctx.clone().spawn(async move {
+            let invalid_name = ctx.create_file("../file").await.err();
+            let mut file = ctx.create_file("file").await.unwrap();
+            let no_space = file.append(&[0u8; 2048]).await.err();
+            let too_large = ctx
+                .send_with_ack(
+                    Message::new_raw("tip", &vec![0u8; MAX_MESSAGE_SIZE]).unwrap(),
+                    Address::new_ref("node", 10, "process"),
+                    1.0,
+                )
+                .await
+                .err();
+            assert_eq!(
+                invalid_name,
+                Some(FsError::InvalidName("../file".to_owned()))
+            );
+            assert_eq!(no_space, Some(FsError::BufferSizeExceed));
+            assert_eq!(
+                too_large,
+                Some(SendError::PayloadTooLarge(MAX_MESSAGE_SIZE + 3))
+            );
+            ctx.send_local("done".into());
+        });
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {}
+
+    fn on_message(&mut self, _msg: Message, _from: Address, _ctx: Context) {}
+}
+
+#[test]
+fn errors_are_classified() {
+    let mut sys = Sim::new(12345);
+    sys.add_node_with_storage("node", "node", 10, 1024);
+    sys.add_process("process", ErrorProc {}, "node");
+
+    sys.send_local_message("process", "node", "start".into());
+    sys.step_until_no_events();
+
+    let messages = sys.read_local_messages("process", "node").unwrap();
+    assert_eq!(messages, vec!["done".into()]);
+}
+
+struct FileNameProc {}
+
+impl Process for FileNameProc {
+    fn on_local_message(&mut self, msg: Message, ctx: Context) {
+        let name = msg.data::<String>().unwrap();
+        ctx.clone().spawn(async move {
+            let invalid = FsError::InvalidName(name.clone());
+            assert_eq!(ctx.create_file(&name).await.err(), Some(invalid.clone()));
+            assert_eq!(ctx.open_file(&name).await.err(), Some(invalid.clone()));
+            assert_eq!(ctx.file_exists(&name).await, Err(invalid.clone()));
+            assert_eq!(ctx.delete_file(&name).await, Err(invalid));
+            ctx.send_local("done".into());
+        });
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {}
+
+    fn on_message(&mut self, _msg: Message, _from: Address, _ctx: Context) {}
+}
+
+#[test]
+fn file_names_outside_mount_are_rejected() {
+    let mut sys = Sim::new(12345);
+    sys.add_node_with_storage("node", "node", 10, 1024);
+    sys.add_process("process", FileNameProc {}, "node");
+
+    // Such names were passed to the storage before.
+    for name in ["dir/file", "../file", "..", "dir\\file"] {
+        sys.send_local_message("process", "node", Message::new("name", &name).unwrap());
+        sys.step_until_no_events();
+        let messages = sys.read_local_messages("process", "node").unwrap();
+        assert_eq!(messages, vec!["done".into()]);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+struct Broadcaster {
+    greeting: String,
+    receivers: Vec<Address>,
+}
+
+impl Process for Broadcaster {
+    fn on_local_message(&mut self, _msg: Message, ctx: Context) {
+        for receiver in self.receivers.iter() {
+            ctx.send(self.greeting.as_str().into(), receiver.clone());
+        }
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {}
+
+    fn on_message(&mut self, _msg: Message, _from: Address, _ctx: Context) {}
+}
+
+fn broadcast_factory() -> ProcessFactory {
+    let mut factory = ProcessFactory::new();
+    factory
+        .register("broadcaster", |greeting: String, _, spec: &ClusterSpec| {
+            Broadcaster {
+                greeting,
+                receivers: spec.addresses("receiver"),
+            }
+        })
+        .register("receiver", |_: (), _, _| Receiver {});
+    factory
+}
+
+#[test]
+fn cluster_spec_builds_sim() {
+    let spec = ClusterSpec::from_json(
+        r#"{
+            "nodes": [
+                {
+                    "name": "node1",
+                    "host": "node1",
+                    "port": 10,
+                    "processes": [{ "name": "broadcaster", "kind": "broadcaster", "args": "hello" }]
+                },
+                {
+                    "name": "node2",
+                    "host": "node2",
+                    "port": 10,
+                    "processes": [{ "name": "receiver", "kind": "receiver" }]
+                },
+                {
+                    "name": "node3",
+                    "host": "node3",
+                    "port": 10,
+                    "processes": [{ "name": "receiver", "kind": "receiver" }]
+                }
+            ]
+        }"#,
+    )
+    .unwrap();
+
+    let mut sys = spec.build_sim(12345, &broadcast_factory()).unwrap();
+    sys.send_local_message("broadcaster", "node1", "start".into());
+    sys.step_until_no_events();
+
+    for node in ["node2", "node3"] {
+        let messages = sys.read_local_messages("receiver", node).unwrap();
+        assert_eq!(messages, vec!["hello".into()]);
+    }
+
+    let mut spec = spec;
+    spec.nodes[0].processes[0].kind = "unknown".to_owned();
+    assert_eq!(
+        spec.build_sim(12345, &broadcast_factory()).err(),
+        Some(ClusterError::UnknownKind("unknown".to_owned()))
+    );
+    spec.nodes[0].processes[0].kind = "broadcaster".to_owned();
+    spec.nodes[0].processes[0].args = serde_json::Value::Null;
+    assert!(matches!(
+        spec.build_sim(12345, &broadcast_factory()).err(),
+        Some(ClusterError::InvalidArgs(process, _)) if process == "broadcaster"
+    ));
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+struct TimeReporter {}
+
+impl Process for TimeReporter {
+    fn on_local_message(&mut self, msg: Message, ctx: Context) {
+        if msg.tip() == "sleep" {
+            ctx.set_timer("sleep", msg.data::<f64>().unwrap());
+            return;
+        }
+        for to in msg.data::<Vec<Address>>().unwrap() {
+            ctx.send("ping".into(), to);
+        }
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {}
+
+    fn on_message(&mut self, _msg: Message, from: Address, ctx: Context) {
+        ctx.send_local(Message::new(&from.host, &ctx.time()).unwrap());
+    }
+}
+
+fn build_link_sim() -> Sim {
+    let mut sys = Sim::new(12345);
+    sys.set_network_delay(1.0);
+    for node in ["a", "b", "c"] {
+        sys.add_node(node, node, 10);
+        sys.add_process("process", TimeReporter {}, node);
+    }
+    sys
+}
+
+fn ping(sys: &mut Sim, from: &str, to: &[&str]) -> Vec<(String, f64)> {
+    let receivers = to
+        .iter()
+        .map(|node| Address::new_ref(node, 10, "process"))
+        .collect::<Vec<_>>();
+    let start = sys.time();
+    sys.send_local_message("process", from, Message::new("ping", &receivers).unwrap());
+    sys.step_until_no_events();
+    let mut received = Vec::new();
+    for node in to {
+        for msg in sys.read_local_messages("process", node).unwrap_or_default() {
+            received.push((node.to_string(), msg.data::<f64>().unwrap() - start));
+        }
+    }
+    received
+}
+
+#[test]
+fn link_settings_work() {
+    let sys = build_link_sim();
+    sys.set_network_drop_rate(0.5);
+    sys.set_link_delays("a", "b", 2.0, 3.0);
+    sys.set_link_drop_rate("a", "b", 0.5);
+    sys.block_link("b", "c");
+
+    assert_eq!(
+        sys.link_settings("a", "b"),
+        LinkSettings {
+            min_delay: 3.0,
+            max_delay: 4.0,
+            drop_rate: 0.75,
+            blocked: false,
+        }
+    );
+    assert_eq!(
+        sys.link_settings("b", "a"),
+        LinkSettings {
+            min_delay: 1.0,
+            max_delay: 1.0,
+            drop_rate: 0.5,
+            blocked: false,
+        }
+    );
+    assert!(sys.link_settings("b", "c").blocked);
+    assert!(!sys.link_settings("c", "b").blocked);
+
+    sys.split_network(&["a"], &["c"]);
+    assert!(sys.link_settings("a", "c").blocked);
+    assert!(sys.link_settings("c", "a").blocked);
+
+    sys.disconnect_node_from_network("a");
+    assert!(sys.link_settings("b", "a").blocked);
+    sys.connect_node_to_network("a");
+    assert!(!sys.link_settings("b", "a").blocked);
+
+    sys.reset_link("a", "b");
+    assert_eq!(sys.link_settings("a", "b"), sys.link_settings("b", "a"));
+}
+
+#[test]
+fn clock_rate_works() {
+    let mut sys = build_link_sim();
+    let sleep = |sys: &mut Sim, duration: f64| {
+        let start = sys.time();
+        sys.send_local_message("process", "a", Message::new("sleep", &duration).unwrap());
+        sys.step_until_no_events();
+        sys.time() - start
+    };
+
+    sys.set_clock_rate("a", 2.0);
+    assert_eq!(sleep(&mut sys, 2.0), 1.0);
+    sys.set_clock_rate("a", 0.5);
+    assert_eq!(sleep(&mut sys, 2.0), 4.0);
+    sys.set_clock_rate("a", 1.0);
+    assert_eq!(sleep(&mut sys, 2.0), 2.0);
+}
+
+#[test]
+fn link_delays_and_drops_work() {
+    let mut sys = build_link_sim();
+    sys.set_link_delay("a", "b", 5.0);
+    sys.set_link_drop_rate("a", "c", 1.0);
+
+    assert_eq!(
+        ping(&mut sys, "a", &["b", "c"]),
+        vec![("b".to_owned(), 6.0)]
+    );
+
+    // Links are directed.
+    assert_eq!(ping(&mut sys, "b", &["a"]), vec![("a".to_owned(), 1.0)]);
+    assert_eq!(ping(&mut sys, "c", &["a"]), vec![("a".to_owned(), 1.0)]);
+
+    sys.reset_link("a", "b");
+    sys.reset_link("a", "c");
+    assert_eq!(
+        ping(&mut sys, "a", &["b", "c"]),
+        vec![("b".to_owned(), 1.0), ("c".to_owned(), 1.0)]
+    );
+}
+
+#[test]
+fn asymmetric_link_block_works() {
+    let mut sys = build_link_sim();
+    sys.block_link("a", "b");
+
+    assert_eq!(ping(&mut sys, "a", &["b"]), vec![]);
+    assert_eq!(ping(&mut sys, "b", &["a"]), vec![("a".to_owned(), 1.0)]);
+
+    sys.unblock_link("a", "b");
+    assert_eq!(ping(&mut sys, "a", &["b"]), vec![("b".to_owned(), 1.0)]);
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Serialize, Deserialize)]
+struct SendSequence {
+    to: Address,
+    count: u64,
+    reliable: bool,
+}
+
+struct SequenceSender {}
+
+impl Process for SequenceSender {
+    fn on_local_message(&mut self, msg: Message, ctx: Context) {
+        let request = msg.data::<SendSequence>().unwrap();
+        for i in 0..request.count {
+            let msg = Message::new("seq", &i).unwrap();
+            if request.reliable {
+                let to = request.to.clone();
+                let ctx_clone = ctx.clone();
+                ctx.spawn(async move {
+                    let result = ctx_clone.send_with_ack(msg, to, 10.0).await;
+                    ctx_clone.send_local(Message::new("acked", &result.is_ok()).unwrap());
+                });
+            } else {
+                ctx.send(msg, request.to.clone());
+            }
+        }
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {}
+
+    fn on_message(&mut self, _msg: Message, _from: Address, _ctx: Context) {}
+}
+
+fn send_sequence(sys: &mut Sim, count: u64, reliable: bool) -> Vec<u64> {
+    let request = SendSequence {
+        to: Address::new_ref("receiver", 10, "receiver"),
+        count,
+        reliable,
+    };
+    sys.send_local_message("sender", "sender", Message::new("send", &request).unwrap());
+    sys.step_until_no_events();
+    sys.read_local_messages("receiver", "receiver")
+        .unwrap_or_default()
+        .into_iter()
+        .map(|msg| msg.data::<u64>().unwrap())
+        .collect()
+}
+
+fn build_sequence_sim() -> Sim {
+    let mut sys = Sim::new(12345);
+    sys.set_network_delay(1.0);
+    sys.add_node("sender", "sender", 10);
+    sys.add_node("receiver", "receiver", 10);
+    sys.add_process("sender", SequenceSender {}, "sender");
+    sys.add_process("receiver", Receiver {}, "receiver");
+    sys
+}
+
+#[test]
+fn message_duplication_works() {
+    let mut sys = build_sequence_sim();
+    sys.set_network_duplicate_rate(1.0);
+
+    let mut received = send_sequence(&mut sys, 5, false);
+    received.sort();
+    assert_eq!(received, vec![0, 0, 1, 1, 2, 2, 3, 3, 4, 4]);
+
+    // Reliable sender observes single delivery.
+    let mut received = send_sequence(&mut sys, 5, true);
+    received.sort();
+    assert_eq!(received, vec![0, 0, 1, 1, 2, 2, 3, 3, 4, 4]);
+    let acks = sys.read_local_messages("sender", "sender").unwrap();
+    assert_eq!(acks.len(), 5);
+    assert!(acks.iter().all(|msg| msg.data::<bool>().unwrap()));
+
+    sys.set_network_duplicate_rate(0.0);
+    assert_eq!(send_sequence(&mut sys, 5, false), vec![0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn message_corruption_works() {
+    let mut sys = build_sequence_sim();
+    sys.set_network_corrupt_rate(1.0);
+
+    let request = SendSequence {
+        to: Address::new_ref("receiver", 10, "receiver"),
+        count: 20,
+        reliable: false,
+    };
+    sys.send_local_message("sender", "sender", Message::new("send", &request).unwrap());
+    sys.step_until_no_events();
+    let received = sys.read_local_messages("receiver", "receiver").unwrap();
+    assert_eq!(received.len(), 20);
+    for (i, msg) in received.iter().enumerate() {
+        assert_eq!(msg.tip(), "seq");
+        assert_ne!(*msg, Message::new("seq", &(i as u64)).unwrap());
+    }
+
+    // Reliable sends are not corrupted.
+    let mut received = send_sequence(&mut sys, 5, true);
+    received.sort();
+    assert_eq!(received, vec![0, 1, 2, 3, 4]);
+    let acks = sys.read_local_messages("sender", "sender").unwrap();
+    assert!(acks.iter().all(|msg| msg.data::<bool>().unwrap()));
+
+    sys.set_network_corrupt_rate(0.0);
+    assert_eq!(send_sequence(&mut sys, 5, false), vec![0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn message_reordering_works() {
+    let mut sys = build_sequence_sim();
+    assert_eq!(
+        send_sequence(&mut sys, 20, false),
+        (0..20).collect::<Vec<_>>()
+    );
+
+    sys.set_reorder_mode(ReorderMode::Window(5.0));
+    let received = send_sequence(&mut sys, 20, false);
+    assert_ne!(received, (0..20).collect::<Vec<_>>());
+    let mut sorted = received.clone();
+    sorted.sort();
+    assert_eq!(sorted, (0..20).collect::<Vec<_>>());
+
+    sys.set_reorder_mode(ReorderMode::Network);
+    assert_eq!(
+        send_sequence(&mut sys, 20, false),
+        (0..20).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn message_filter_works() {
+    let mut sys = build_sequence_sim();
+    sys.set_message_filter(|from, to, msg| {
+        assert_eq!(
+            (from.host.as_str(), to.host.as_str()),
+            ("sender", "receiver")
+        );
+        match msg.data::<u64>().unwrap() {
+            0 => MessageAction::Drop,
+            1 => MessageAction::Delay(5.0),
+            2 => MessageAction::Duplicate,
+            3 => {
+                *msg = Message::new("seq", &30).unwrap();
+                MessageAction::Deliver
+            }
+            _ => MessageAction::Deliver,
+        }
+    });
+
+    let received = send_sequence(&mut sys, 5, false);
+    assert_eq!(received.last(), Some(&1));
+    let mut received = received;
+    received.sort();
+    assert_eq!(received, vec![1, 2, 2, 4, 30]);
+
+    // Dropped reliable message is not acknowledged.
+    let mut received = send_sequence(&mut sys, 5, true);
+    received.sort();
+    assert_eq!(received, vec![1, 2, 2, 4, 30]);
+    let acks = sys
+        .read_local_messages("sender", "sender")
+        .unwrap()
+        .into_iter()
+        .map(|msg| msg.data::<bool>().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(acks.iter().filter(|acked| **acked).count(), 4);
+    assert_eq!(acks.len(), 5);
+
+    sys.clear_message_filter();
+    assert_eq!(send_sequence(&mut sys, 5, false), vec![0, 1, 2, 3, 4]);
+}
+
+/// Keeps context of the process, so it can be used outside of the process.
+struct ContextKeeper {
+    ctx: Arc<Mutex<Option<Context>>>,
+}
+
+impl Process for ContextKeeper {
+    fn on_local_message(&mut self, _msg: Message, ctx: Context) {
+        self.ctx.lock().unwrap().replace(ctx);
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {}
+
+    fn on_message(&mut self, _msg: Message, _from: Address, _ctx: Context) {}
+}
+
+#[test]
+fn message_filter_can_send_messages() {
+    let mut sys = build_sequence_sim();
+    let ctx = Arc::new(Mutex::new(None));
+    sys.add_process("keeper", ContextKeeper { ctx: ctx.clone() }, "sender");
+    sys.send_local_message("keeper", "sender", Message::from("keep"));
+    sys.step_until_no_events();
+
+    // Messages sent by the filter are not filtered.
+    sys.set_message_filter(move |_from, to, msg| {
+        if msg.tip() == "seq" && msg.data::<u64>().unwrap() == 0 {
+            let ctx = ctx.lock().unwrap();
+            ctx.as_ref()
+                .unwrap()
+                .send(Message::new("seq", &0).unwrap(), to.clone());
+            MessageAction::Drop
+        } else {
+            MessageAction::Deliver
+        }
+    });
+    let mut received = send_sequence(&mut sys, 3, false);
+    received.sort();
+    assert_eq!(received, vec![0, 1, 2]);
+}
+
+fn sleep(sys: &mut Sim, duration: f64) {
+    sys.send_local_message("process", "a", Message::new("sleep", &duration).unwrap());
+    sys.step_until_no_events();
+}
+
+fn layout(groups: &[&[&str]]) -> Vec<Vec<String>> {
+    groups
+        .iter()
+        .map(|group| group.iter().map(|node| node.to_string()).collect())
+        .collect()
+}
+
+#[test]
+fn network_partition_and_heal_works() {
+    let mut sys = build_link_sim();
+    assert_eq!(sys.partition_layout(), layout(&[&["a", "b", "c"]]));
+
+    // Node c is not listed, so it forms separate group.
+    sys.partition(&[&["a"], &["b"]]);
+    assert_eq!(sys.partition_layout(), layout(&[&["a"], &["b"], &["c"]]));
+    assert_eq!(ping(&mut sys, "a", &["b", "c"]), vec![]);
+
+    // Previous partition is replaced.
+    sys.partition(&[&["a", "b"]]);
+    assert_eq!(sys.partition_layout(), layout(&[&["a", "b"], &["c"]]));
+    assert_eq!(
+        ping(&mut sys, "a", &["b", "c"]),
+        vec![("b".to_owned(), 1.0)]
+    );
+
+    sys.heal_network();
+    assert_eq!(sys.partition_layout(), layout(&[&["a", "b", "c"]]));
+    assert_eq!(
+        ping(&mut sys, "a", &["b", "c"]),
+        vec![("b".to_owned(), 1.0), ("c".to_owned(), 1.0)]
+    );
+
+    sys.split_network(&["a"], &["b", "c"]);
+    assert_eq!(sys.partition_layout(), layout(&[&["a"], &["b", "c"]]));
+    sys.heal_network();
+    assert_eq!(ping(&mut sys, "a", &["b"]), vec![("b".to_owned(), 1.0)]);
+
+    // Blocked links are not healed.
+    sys.block_link("a", "b");
+    sys.partition(&[&["a"], &["b", "c"]]);
+    sys.unblock_link("b", "a");
+    assert_eq!(ping(&mut sys, "b", &["a"]), vec![]);
+    sys.heal_network();
+    assert_eq!(ping(&mut sys, "a", &["b"]), vec![]);
+    assert_eq!(ping(&mut sys, "b", &["a"]), vec![("a".to_owned(), 1.0)]);
+    assert_eq!(sys.partition_layout(), layout(&[&["a"], &["b"], &["c"]]));
+}
+
+#[test]
+#[should_panic(expected = "Node a is listed in multiple groups.")]
+fn network_partition_with_repeated_node() {
+    let sys = build_link_sim();
+    sys.partition(&[&["a", "b"], &["a"]]);
+}
+
+#[test]
+fn node_isolation_works() {
+    let mut sys = build_link_sim();
+    sys.isolate_node("a", 5.0);
+    assert_eq!(sys.partition_layout(), layout(&[&["a"], &["b", "c"]]));
+    assert!(sys.link_settings("b", "a").blocked);
+
+    assert_eq!(ping(&mut sys, "a", &["b"]), vec![]);
+    assert_eq!(
+        ping(&mut sys, "b", &["a", "c"]),
+        vec![("c".to_owned(), 1.0)]
+    );
+
+    // Network heals automatically.
+    sleep(&mut sys, 5.0);
+    assert_eq!(sys.partition_layout(), layout(&[&["a", "b", "c"]]));
+    assert_eq!(ping(&mut sys, "a", &["b"]), vec![("b".to_owned(), 1.0)]);
+
+    sys.isolate_node("a", 100.0);
+    assert_eq!(ping(&mut sys, "b", &["a"]), vec![]);
+    sys.heal_network();
+    assert_eq!(ping(&mut sys, "b", &["a"]), vec![("a".to_owned(), 1.0)]);
+}
+
+#[test]
+fn node_isolation_fails_reliable_sends() {
+    let mut sys = build_sequence_sim();
+    sys.isolate_node("receiver", 100.0);
+
+    assert!(send_sequence(&mut sys, 3, true).is_empty());
+    let acks = sys.read_local_messages("sender", "sender").unwrap();
+    assert_eq!(acks.len(), 3);
+    assert!(acks.iter().all(|msg| !msg.data::<bool>().unwrap()));
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+fn trace_kinds(sys: &Sim) -> Vec<(String, Option<String>, TraceEventKind)> {
+    sys.trace()
+        .into_iter()
+        .map(|event| (event.node, event.process, event.kind))
+        .collect()
+}
+
+#[test]
+fn trace_is_disabled_by_default() {
+    let mut sys = build_link_sim();
+    ping(&mut sys, "a", &["b"]);
+    assert!(sys.trace().is_empty());
+}
+
+#[test]
+fn trace_records_events() {
+    let mut sys = build_link_sim();
+    sys.enable_trace();
+
+    let b = Address::new_ref("b", 10, "process");
+    let c = Address::new_ref("c", 10, "process");
+    let process = Some("process".to_owned());
+    let ping_data = serde_json::to_string("ping").unwrap();
+    let receivers = Message::new("ping", &vec![b.clone()]).unwrap();
+
+    ping(&mut sys, "a", &["b"]);
+    let events = sys.trace();
+    assert_eq!(events.len(), 4);
+    assert_eq!(events[1].time, 0.0);
+    assert_eq!(events[2].time, 1.0);
+    assert_eq!(
+        trace_kinds(&sys),
+        vec![
+            (
+                "a".to_owned(),
+                process.clone(),
+                TraceEventKind::local_message_received(&receivers),
+            ),
+            (
+                "a".to_owned(),
+                process.clone(),
+                TraceEventKind::MessageSent {
+                    to: b.clone(),
+                    tip: "info".to_owned(),
+                    data: ping_data.clone(),
+                    reliable: false,
+                },
+            ),
+            (
+                "b".to_owned(),
+                process.clone(),
+                TraceEventKind::MessageReceived {
+                    from: Address::new_ref("a", 10, "process"),
+                    tip: "info".to_owned(),
+                    data: ping_data.clone(),
+                },
+            ),
+            (
+                "b".to_owned(),
+                process.clone(),
+                TraceEventKind::local_message_sent(&Message::new("a", &1.0).unwrap()),
+            ),
+        ]
+    );
+
+    // Drops, timers and crashes.
+    sys.set_link_drop_rate("a", "c", 1.0);
+    ping(&mut sys, "a", &["c"]);
+    sleep(&mut sys, 2.0);
+    sys.crash_node("b");
+    sys.recover_node("b");
+
+    let kinds = trace_kinds(&sys);
+    assert_eq!(
+        kinds[6],
+        (
+            "a".to_owned(),
+            process.clone(),
+            TraceEventKind::MessageDropped {
+                to: c,
+                tip: "info".to_owned(),
+                data: ping_data,
+            }
+        )
+    );
+    assert_eq!(
+        kinds[8..],
+        vec![
+            (
+                "a".to_owned(),
+                process.clone(),
+                TraceEventKind::TimerSet {
+                    name: "sleep".to_owned(),
+                    delay: 2.0,
+                    once: false,
+                },
+            ),
+            (
+                "a".to_owned(),
+                process,
+                TraceEventKind::TimerFired {
+                    name: "sleep".to_owned(),
+                },
+            ),
+            ("b".to_owned(), None, TraceEventKind::NodeCrashed),
+            ("b".to_owned(), None, TraceEventKind::NodeRecovered),
+        ]
+    );
+
+    // Export to JSON lines.
+    let path = std::env::temp_dir().join(format!("dsbuild-trace-{}.jsonl", std::process::id()));
+    sys.export_trace(&path).unwrap();
+    let exported = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let lines = exported.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), sys.trace().len());
+    assert!(lines[10].contains(r#""event":"node_crashed""#));
+    let parsed = lines
+        .iter()
+        .map(|line| serde_json::from_str::<TraceEvent>(line).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(parsed, sys.trace());
+}
+
+#[test]
+fn trace_records_network_drops() {
+    let mut sys = build_link_sim();
+    sys.enable_trace();
+
+    let dropped = |sys: &Sim| {
+        sys.trace()
+            .into_iter()
+            .filter_map(|event| match event.kind {
+                TraceEventKind::MessageDropped { to, .. } => Some((event.node, to.host)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    sys.set_network_drop_rate(1.0);
+    assert!(ping(&mut sys, "a", &["b"]).is_empty());
+    assert_eq!(dropped(&sys), vec![("a".to_owned(), "b".to_owned())]);
+    sys.set_network_drop_rate(0.0);
+
+    sys.split_network(&["a"], &["b", "c"]);
+    assert_eq!(
+        ping(&mut sys, "b", &["a", "c"]),
+        vec![("c".to_owned(), 1.0)]
+    );
+    sys.heal_network();
+    sys.disconnect_node_from_network("c");
+    assert!(ping(&mut sys, "a", &["c"]).is_empty());
+    sys.connect_node_to_network("c");
+    assert_eq!(
+        dropped(&sys)[1..],
+        vec![
+            ("b".to_owned(), "a".to_owned()),
+            ("a".to_owned(), "c".to_owned())
+        ]
+    );
+
+    // Link is blocked while message is held on it.
+    sys.set_link_delay("a", "b", 5.0);
+    let receivers = vec![Address::new_ref("b", 10, "process")];
+    sys.send_local_message("process", "a", Message::new("ping", &receivers).unwrap());
+    sys.send_local_message("process", "c", Message::new("sleep", &1.0).unwrap());
+    sys.step_for_duration(1.0);
+    sys.block_link("a", "b");
+    sys.step_until_no_events();
+    assert!(sys.read_local_messages("process", "b").is_none());
+    assert_eq!(dropped(&sys)[3..], vec![("a".to_owned(), "b".to_owned())]);
+}
+
+#[test]
+fn trace_records_file_operations() {
+    let mut sys = Sim::new(12345);
+    sys.add_node_with_storage("node", "node", 12345, 1 << 20);
+    sys.add_process("storage_process", StorageProc {}, "node");
+    sys.enable_trace();
+
+    sys.send_local_message(
+        "storage_process",
+        "node",
+        Message::new(
+            "append",
+            &AppendRequest {
+                file: "file".to_owned(),
+                data: "data".to_owned(),
+            },
+        )
+        .unwrap(),
+    );
+    sys.step_until_no_events();
+
+    let file_events = sys
+        .trace()
+        .into_iter()
+        .map(|event| event.kind)
+        .filter(|kind| {
+            !matches!(
+                kind,
+                TraceEventKind::LocalMessageReceived { .. }
+                    | TraceEventKind::LocalMessageSent { .. }
+            )
+        })
+        .collect::<Vec<_>>();
+    let file = "file".to_owned();
+    assert_eq!(
+        file_events,
+        vec![
+            TraceEventKind::FileCreated { file: file.clone() },
+            TraceEventKind::FileOpened { file: file.clone() },
+            TraceEventKind::FileAppended {
+                file: file.clone(),
+                size: 4,
+            },
+            TraceEventKind::FileAppended { file, size: 0 },
+        ]
+    );
+}
+
+#[test]
+fn sequence_diagram_works() {
+    let mut sys = build_link_sim();
+    sys.enable_trace();
+    ping(&mut sys, "a", &["b"]);
+    sys.set_link_drop_rate("a", "c", 1.0);
+    ping(&mut sys, "a", &["c"]);
+    sys.crash_node("b");
+
+    let all = DiagramFilter::new();
+    assert_eq!(
+        sys.sequence_diagram(DiagramFormat::Mermaid, &all),
+        "sequenceDiagram
+    participant p0 as a/process
+    participant p1 as b/process
+    participant p2 as c/process
+    Note over p0,p0: local in: ping @ 0.000
+    p0->>p1: info @ 1.000
+    Note over p1,p1: local out: a @ 1.000
+    Note over p0,p0: local in: ping @ 1.000
+    p0-xp2: info @ 1.000 (dropped)
+    Note over p1,p1: b crashed @ 1.000
+"
+    );
+    assert_eq!(
+        sys.sequence_diagram(DiagramFormat::PlantUml, &all),
+        "@startuml
+participant \"a/process\" as p0
+participant \"b/process\" as p1
+participant \"c/process\" as p2
+note over p0, p0 : local in: ping @ 0.000
+p0 -> p1 : info @ 1.000
+note over p1, p1 : local out: a @ 1.000
+note over p0, p0 : local in: ping @ 1.000
+p0 ->x p2 : info @ 1.000 (dropped)
+note over p1, p1 : b crashed @ 1.000
+@enduml
+"
+    );
+
+    // Filters.
+    let filter = DiagramFilter::new()
+        .with_tips(&["info"])
+        .with_nodes(&["a", "c"]);
+    assert_eq!(
+        sys.sequence_diagram(DiagramFormat::Mermaid, &filter),
+        "sequenceDiagram
+    participant p0 as a/process
+    participant p1 as c/process
+    p0-xp1: info @ 1.000 (dropped)
+"
+    );
+    let filter = DiagramFilter::new()
+        .with_time_window(0.5, 1.0)
+        .with_processes(&["process"]);
+    let diagram = sys.sequence_diagram(DiagramFormat::Mermaid, &filter);
+    assert!(!diagram.contains("@ 0.000"));
+    assert!(diagram.contains("p0->>p1: info @ 1.000"));
+
+    // Html timeline.
+    let html = sys.sequence_diagram(DiagramFormat::Html, &all);
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<svg"));
+    assert!(html.contains(">b/process</text>"));
+    assert_eq!(html.matches("<g class=\"dropped\">").count(), 1);
+    assert_eq!(html.matches("<g class=\"message\">").count(), 1);
+    assert_eq!(html.matches("<g class=\"note\">").count(), 4);
+
+    let path = std::env::temp_dir().join("dsbuild_sequence_diagram_works.mmd");
+    sys.export_sequence_diagram(&path, DiagramFormat::Mermaid, &all)
+        .unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        sys.sequence_diagram(DiagramFormat::Mermaid, &all)
+    );
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn sequence_diagram_escapes_labels() {
+    let mut sys = Sim::new(12345);
+    sys.add_node("node", "node", 10);
+    sys.add_process("say \"hi\": 1", TimeReporter {}, "node");
+    sys.enable_trace();
+    let msg = Message::new("ping; #1\nnow", &Vec::<Address>::new()).unwrap();
+    sys.send_local_message("say \"hi\": 1", "node", msg);
+    sys.step_until_no_events();
+
+    let all = DiagramFilter::new();
+    assert_eq!(
+        sys.sequence_diagram(DiagramFormat::Mermaid, &all),
+        "sequenceDiagram
+    participant p0 as node/say \"hi\"#58; 1
+    Note over p0,p0: local in: ping#59; #35;1<br>now @ 0.000
+"
+    );
+    assert_eq!(
+        sys.sequence_diagram(DiagramFormat::PlantUml, &all),
+        "@startuml
+participant \"node/say <U+0022>hi<U+0022>: 1\" as p0
+note over p0, p0 : local in: ping; #1\\nnow @ 0.000
+@enduml
+"
+    );
+}
+
+struct Writer {
+    name: String,
+    to: Option<Address>,
+}
+
+impl Process for Writer {
+    fn on_local_message(&mut self, msg: Message, ctx: Context) {
+        let to = msg.data::<Address>().unwrap();
+        if msg.tip() == "send_now" {
+            ctx.send(Message::new("value", &self.name).unwrap(), to);
+        } else {
+            self.to = Some(to);
+            let delay = if msg.tip() == "send_much_later" {
+                2.0
+            } else {
+                1.0
+            };
+            ctx.set_timer("send", delay);
+        }
+    }
+
+    fn on_timer(&mut self, _name: String, ctx: Context) {
+        let to = self.to.clone().unwrap();
+        ctx.send(Message::new("value", &self.name).unwrap(), to);
+    }
+
+    fn on_message(&mut self, _msg: Message, _from: Address, _ctx: Context) {}
+}
+
+fn build_writers(sys: &mut Sim, b_tip: &str) {
+    let receiver = Address::new_ref("r", 10, "r");
+    for node in ["a", "b", "r"] {
+        sys.add_node(node, node, 10);
+    }
+    for name in ["a", "b"] {
+        let writer = Writer {
+            name: name.to_owned(),
+            to: None,
+        };
+        sys.add_process(name, writer, name);
+    }
+    sys.add_process("r", Receiver {}, "r");
+    sys.send_local_message("a", "a", Message::new("send_now", &receiver).unwrap());
+    sys.send_local_message("b", "b", Message::new(b_tip, &receiver).unwrap());
+}
+
+fn a_received_first(sys: &mut Sim) -> Result<(), String> {
+    let first = sys
+        .read_local_messages("r", "r")
+        .unwrap_or_default()
+        .first()
+        .map(|msg| msg.data::<String>().unwrap());
+    match first.as_deref() {
+        Some("b") => Err("value of b is received first".to_owned()),
+        _ => Ok(()),
+    }
+}
+
+#[test]
+fn model_checker_finds_minimal_violation() {
+    let b = Address::new_ref("b", 10, "b");
+    let expected = vec![
+        McEvent::TimerFired {
+            process: b.clone(),
+            name: "send".to_owned(),
+        },
+        McEvent::MessageDelivered {
+            from: b,
+            to: Address::new_ref("r", 10, "r"),
+            tip: "value".to_owned(),
+            data: serde_json::to_string("b").unwrap(),
+        },
+    ];
+
+    for strategy in [SearchStrategy::Bfs, SearchStrategy::Dfs] {
+        let mut checker =
+            ModelChecker::new(|sys| build_writers(sys, "send_later")).with_strategy(strategy);
+        checker.add_invariant(a_received_first);
+        let violation = checker.run().unwrap_err();
+        assert_eq!(violation.reason, "value of b is received first");
+        assert_eq!(violation.events, expected);
+        assert!(violation.to_string().contains("2. b:10/b -> r:10/r: value"));
+    }
+
+    // Violation is not reachable within one event.
+    let mut checker = ModelChecker::new(|sys| build_writers(sys, "send_later")).with_max_depth(1);
+    checker.add_invariant(a_received_first);
+    assert_eq!(
+        checker.run(),
+        Ok(McStats {
+            explored_states: 3,
+            max_depth: 1
+        })
+    );
+}
+
+#[test]
+fn model_checker_respects_timer_delays() {
+    let build = |sys: &mut Sim| {
+        build_writers(sys, "send_much_later");
+        let receiver = Address::new_ref("r", 10, "r");
+        sys.send_local_message("a", "a", Message::new("send_later", &receiver).unwrap());
+    };
+    let timer = |process: &str| McEvent::TimerFired {
+        process: Address::new_ref(process, 10, process),
+        name: "send".to_owned(),
+    };
+    let from_b = McEvent::MessageDelivered {
+        from: Address::new_ref("b", 10, "b"),
+        to: Address::new_ref("r", 10, "r"),
+        tip: "value".to_owned(),
+        data: serde_json::to_string("b").unwrap(),
+    };
+
+    // Timer of b can fire first, although its delay is longer.
+    let mut checker = ModelChecker::new(build);
+    checker.add_invariant(a_received_first);
+    assert_eq!(
+        checker.run().unwrap_err().events,
+        vec![timer("b"), from_b.clone()]
+    );
+
+    // Timer of a must fire first, but the message of a can still be delayed.
+    let mut checker = ModelChecker::new(build).with_timer_delays(true);
+    checker.add_invariant(a_received_first);
+    assert_eq!(
+        checker.run().unwrap_err().events,
+        vec![timer("a"), timer("b"), from_b]
+    );
+}
+
+#[test]
+fn model_checker_explores_all_states() {
+    let build = |sys: &mut Sim| build_writers(sys, "send_now");
+
+    // Initial state, two states after one delivery and two orderings of both deliveries.
+    let checker = ModelChecker::new(build);
+    assert_eq!(
+        checker.run(),
+        Ok(McStats {
+            explored_states: 5,
+            max_depth: 2
+        })
+    );
+
+    // Drops of the messages lead to five more states,
+    // but the order of the drops does not matter.
+    for strategy in [SearchStrategy::Bfs, SearchStrategy::Dfs] {
+        let checker = ModelChecker::new(build)
+            .with_drops(true)
+            .with_strategy(strategy);
+        assert_eq!(
+            checker.run(),
+            Ok(McStats {
+                explored_states: 10,
+                max_depth: 2
+            })
+        );
+    }
+}
+
+fn full_fault_plan() -> FaultPlan {
+    FaultPlan::new(&["a", "b", "c"])
+        .with_interval(1.0, 2.0)
+        .with_duration(1.0, 3.0)
+        .with_crashes()
+        .with_partitions()
+        .with_link_degradation(1.0, 2.0, 0.5)
+        .with_pauses()
+        .with_clock_skew(0.5, 2.0)
+}
+
+#[test]
+fn fault_plan_schedule_works() {
+    let plan = full_fault_plan();
+    let schedule = plan.schedule(42, 10.0, 60.0);
+    assert_eq!(schedule, plan.schedule(42, 10.0, 60.0));
+    assert_ne!(schedule, plan.schedule(43, 10.0, 60.0));
+    assert!(schedule[0].time >= 11.0);
+    assert!(schedule.windows(2).all(|w| w[0].time <= w[1].time));
+
+    // Every fault except pause is followed by its repair.
+    let mut faults = schedule.iter().map(|scheduled| &scheduled.fault);
+    while let Some(fault) = faults.next() {
+        let repair = match fault {
+            Fault::CrashNode { node } => Fault::RecoverNode { node: node.clone() },
+            Fault::Partition { group1, group2 } => {
+                let mut nodes = [group1.clone(), group2.clone()].concat();
+                nodes.sort();
+                assert_eq!(nodes, vec!["a", "b", "c"]);
+                Fault::HealNetwork
+            }
+            Fault::DegradeLink { from, to, .. } => {
+                assert_ne!(from, to);
+                Fault::RestoreLink {
+                    from: from.clone(),
+                    to: to.clone(),
+                }
+            }
+            Fault::PauseNode { duration, .. } => {
+                assert!((1.0..=3.0).contains(duration));
+                continue;
+            }
+            Fault::SkewClock { node, rate } => {
+                assert!((0.5..=2.0).contains(rate));
+                Fault::RestoreClock { node: node.clone() }
+            }
+            fault => panic!("unexpected fault: {:?}", fault),
+        };
+        assert_eq!(faults.next(), Some(&repair));
+    }
+
+    assert!(FaultPlan::new(&["a"]).schedule(42, 0.0, 100.0).is_empty());
+}
+
+fn restart_time_reporter(sys: &mut Sim, node: &str) {
+    sys.add_process("process", TimeReporter {}, node);
+}
+
+fn run_pings_with_nemesis(nemesis: &mut Nemesis) -> Vec<TraceEvent> {
+    let mut sys = build_link_sim();
+    sys.enable_trace();
+    let nodes = ["a", "b", "c"];
+    let receivers = nodes
+        .iter()
+        .map(|node| Address::new_ref(node, 10, "process"))
+        .collect::<Vec<_>>();
+    for _ in 0..20 {
+        for from in nodes {
+            if !sys.is_node_crashed(from) {
+                sys.send_local_message("process", from, Message::new("ping", &receivers).unwrap());
+            }
+        }
+        nemesis.step_for_duration(&mut sys, 2.0);
+    }
+    sys.trace()
+}
+
+#[test]
+fn nemesis_replay_works() {
+    let plan = full_fault_plan();
+    let sys = build_link_sim();
+    let mut nemesis = Nemesis::new(&plan, &sys, 30.0).with_restart_hook(restart_time_reporter);
+    assert_eq!(nemesis.schedule(), plan.schedule(sys.seed(), 0.0, 30.0));
+
+    let trace = run_pings_with_nemesis(&mut nemesis);
+    assert!(nemesis.applied().len() >= 8);
+    assert!(trace
+        .iter()
+        .any(|event| event.kind == TraceEventKind::NodeCrashed));
+
+    // Faults change the run.
+    let mut no_faults = Nemesis::replay(Vec::new());
+    assert_ne!(trace, run_pings_with_nemesis(&mut no_faults));
+
+    // Recorded schedule reproduces the run exactly.
+    let path = std::env::temp_dir().join("dsbuild_nemesis_replay_works.jsonl");
+    nemesis.export_schedule(&path).unwrap();
+    let mut replayed = Nemesis::replay_file(&path)
+        .unwrap()
+        .with_restart_hook(restart_time_reporter);
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(replayed.schedule(), nemesis.applied());
+    assert_eq!(trace, run_pings_with_nemesis(&mut replayed));
+    assert_eq!(replayed.applied(), nemesis.applied());
+}
+
+#[test]
+fn nemesis_keeps_user_network_settings() {
+    let mut sys = build_link_sim();
+    sys.block_link("a", "b");
+    sys.set_link_delay("b", "c", 5.0);
+    let links = [("a", "b"), ("b", "a"), ("a", "c"), ("c", "a"), ("b", "c")];
+    let settings = |sys: &Sim| {
+        links
+            .iter()
+            .map(|(from, to)| sys.link_settings(from, to))
+            .collect::<Vec<_>>()
+    };
+    let before = settings(&sys);
+
+    let degrade = |from: &str, to: &str| Fault::DegradeLink {
+        from: from.to_owned(),
+        to: to.to_owned(),
+        min_delay: 1.0,
+        max_delay: 2.0,
+        drop_rate: 0.5,
+    };
+    let restore = |from: &str, to: &str| Fault::RestoreLink {
+        from: from.to_owned(),
+        to: to.to_owned(),
+    };
+    let at = |time: f64, fault: Fault| ScheduledFault { time, fault };
+    let mut nemesis = Nemesis::replay(vec![
+        at(
+            0.0,
+            Fault::Partition {
+                group1: vec!["a".to_owned()],
+                group2: vec!["b".to_owned(), "c".to_owned()],
+            },
+        ),
+        at(0.0, degrade("a", "b")),
+        at(0.0, degrade("b", "c")),
+        at(1.0, Fault::HealNetwork),
+        at(1.0, restore("a", "b")),
+        at(1.0, restore("b", "c")),
+    ]);
+
+    nemesis.apply_due(&mut sys);
+    assert!(sys.link_settings("c", "a").blocked);
+    assert_eq!(sys.link_settings("b", "c").max_delay, 3.0);
+
+    sys.send_local_message("process", "b", Message::new("sleep", &1.0).unwrap());
+    nemesis.step_until_no_events(&mut sys);
+    nemesis.apply_due(&mut sys);
+    assert_eq!(nemesis.applied().len(), 6);
+
+    // Faults are repaired, while the block and the delay of the user are kept.
+    assert_eq!(settings(&sys), before);
+    assert!(sys.link_settings("a", "b").blocked);
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Registers identified by name, which support writes and reads.
+struct RegisterModel {}
+
+#[derive(Debug)]
+enum RegisterInput {
+    Write(String, u64),
+    Read(String),
+}
+
+impl crate::Model for RegisterModel {
+    type State = u64;
+    type Input = RegisterInput;
+    type Output = u64;
+
+    fn init(&self) -> u64 {
+        0
+    }
+
+    fn step(&self, state: &u64, input: &RegisterInput, output: Option<&u64>) -> Option<u64> {
+        match input {
+            RegisterInput::Write(_, value) => Some(*value),
+            RegisterInput::Read(_) => output.is_none_or(|value| value == state).then_some(*state),
+        }
+    }
+
+    fn partition(&self, input: &RegisterInput) -> String {
+        match input {
+            RegisterInput::Write(register, _) | RegisterInput::Read(register) => register.clone(),
+        }
+    }
+}
+
+#[test]
+fn linearizable_history_works() {
+    let mut history = History::new();
+
+    // Concurrent write and reads, first read observes old value and second observes new one.
+    let write = history.invoke("c1", RegisterInput::Write("x".to_owned(), 1), 0.0);
+    let read1 = history.invoke("c2", RegisterInput::Read("x".to_owned()), 1.0);
+    history.complete(read1, 0, 2.0);
+    let read2 = history.invoke("c3", RegisterInput::Read("x".to_owned()), 1.5);
+    history.complete(read2, 1, 2.5);
+    history.complete(write, 1, 3.0);
+
+    // Write is not completed, but read observes it.
+    let lost_write = history.invoke("c1", RegisterInput::Write("y".to_owned(), 5), 4.0);
+    let read3 = history.invoke("c2", RegisterInput::Read("y".to_owned()), 10.0);
+    history.complete(read3, 5, 11.0);
+
+    // Repeated completion is ignored.
+    history.complete(read3, 0, 12.0);
+
+    assert_eq!(history.operations().len(), 5);
+    assert_eq!(history.operations()[lost_write].output, None);
+    assert_eq!(history.operations()[read3].output, Some(5));
+    assert_eq!(history.check(&RegisterModel {}), Ok(()));
+}
+
+#[test]
+fn stale_read_is_not_linearizable() {
+    let mut history = History::new();
+
+    let write = history.invoke("c1", RegisterInput::Write("x".to_owned(), 1), 0.0);
+    history.complete(write, 1, 1.0);
+    let read1 = history.invoke("c2", RegisterInput::Read("x".to_owned()), 2.0);
+    history.complete(read1, 1, 3.0);
+    let read2 = history.invoke("c3", RegisterInput::Read("x".to_owned()), 2.5);
+    history.complete(read2, 0, 4.0);
+
+    // Other partition does not affect the result.
+    let other = history.invoke("c1", RegisterInput::Read("y".to_owned()), 2.0);
+    history.complete(other, 0, 3.0);
+
+    let err = history.check(&RegisterModel {}).unwrap_err();
+    assert_eq!(
+        err,
+        LinearizabilityError {
+            partition: "x".to_owned(),
+            linearized: vec![write, read1],
+            not_linearized: vec![read2],
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "Partition 'x': history is not linearizable, longest linearization is [0, 1], \
+         operations [2] can not be linearized after it"
+    );
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Default)]
+struct Counter {
+    value: u64,
+}
+
+impl Process for Counter {
+    fn on_local_message(&mut self, _msg: Message, _ctx: Context) {
+        self.value += 1;
+    }
+
+    fn on_timer(&mut self, _name: String, _ctx: Context) {}
+
+    fn on_message(&mut self, _msg: Message, _from: Address, _ctx: Context) {}
+}
+
+fn build_counters(sys: &mut Sim) {
+    sys.add_node("node1", "10.0.0.1", 10024);
+    sys.add_node("node2", "10.0.0.2", 10024);
+    sys.add_process("counter1", Counter::default(), "node1");
+    sys.add_process("counter2", Counter::default(), "node2");
+}
+
+fn add_sum_invariant(sys: &mut Sim) {
+    sys.add_invariant("sum is small", |view| {
+        let sum = view
+            .processes::<Counter>()
+            .map(|(_, _, counter)| counter.value)
+            .sum::<u64>();
+        if sum < 3 {
+            Ok(())
+        } else {
+            Err(format!("sum is {}", sum))
+        }
+    });
+}
+
+fn increment(sys: &mut Sim, counter: &str, node: &str) {
+    sys.send_local_message(counter, node, Message::new("inc", &()).unwrap());
+}
+
+#[test]
+fn invariants_view_works() {
+    let mut sys = Sim::new(12345);
+    build_counters(&mut sys);
+    let checks = Rc::new(RefCell::new(Vec::new()));
+    let checks_clone = checks.clone();
+    sys.add_invariant("view is correct", move |view| {
+        let counter = view.process::<Counter>("node1", "counter1").unwrap();
+        assert!(view.process::<Counter>("node2", "counter1").is_none());
+        assert!(view.process::<StorageProc>("node1", "counter1").is_none());
+        let names = view
+            .processes::<Counter>()
+            .map(|(node, process, _)| format!("{}/{}", node, process))
+            .collect::<Vec<_>>();
+        checks_clone
+            .borrow_mut()
+            .push((counter.value, view.is_node_crashed("node2"), names));
+        Ok(())
+    });
+
+    increment(&mut sys, "counter1", "node1");
+    increment(&mut sys, "counter1", "node1");
+    sys.step_until_no_events();
+
+    sys.crash_node("node2");
+    increment(&mut sys, "counter1", "node1");
+    sys.step_until_no_events();
+
+    // Processes of the crashed node are cleared on recovery.
+    sys.recover_node("node2");
+    increment(&mut sys, "counter1", "node1");
+    sys.step_until_no_events();
+
+    let all = vec!["node1/counter1".to_owned(), "node2/counter2".to_owned()];
+    assert_eq!(
+        *checks.borrow(),
+        vec![
+            (1, false, all.clone()),
+            (2, false, all.clone()),
+            (3, true, all),
+            (4, false, vec!["node1/counter1".to_owned()]),
+        ]
+    );
+}
+
+#[test]
+#[should_panic(expected = "Invariant 'sum is small' violated at time 0.000: sum is 3")]
+fn invariant_violation_stops_simulation() {
+    let mut sys = Sim::new(12345);
+    build_counters(&mut sys);
+    add_sum_invariant(&mut sys);
+    sys.enable_trace();
+
+    increment(&mut sys, "counter1", "node1");
+    increment(&mut sys, "counter2", "node2");
+    sys.step_until_no_events();
+
+    increment(&mut sys, "counter2", "node2");
+    sys.step_until_no_events();
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn seed_runner_works() {
+    let scenario = |seed: u64| {
+        let mut sys = Sim::new(seed);
+        build_counters(&mut sys);
+        add_sum_invariant(&mut sys);
+        for _ in 0..seed % 4 {
+            increment(&mut sys, "counter1", "node1");
+        }
+        sys.step_until_no_events();
+        if seed == 5 {
+            return Err("unlucky seed".to_owned());
+        }
+        Ok(())
+    };
+
+    let report = SeedRunner::new(0..12)
+        .with_threads(3)
+        .with_reproduce_command("run --seed {seed}")
+        .run(scenario);
+    assert_eq!(report.total, 12);
+    assert_eq!(report.failed_seeds(), vec![3, 5, 7, 11]);
+    assert_eq!(
+        report.failures[0].reason,
+        "panicked: Invariant 'sum is small' violated at time 0.000: sum is 3"
+    );
+    assert_eq!(report.failures[1].reason, "unlucky seed");
+    assert_eq!(report.failures[1].reproduce, "run --seed 5");
+    assert!(report
+        .to_string()
+        .starts_with("4 of 12 seeds failed: [3, 5, 7, 11]\n\nSeed 3: panicked"));
+
+    // Default command reproduces the current test.
+    let report = SeedRunner::new(5..6).run(scenario);
+    assert_eq!(
+        report.failures[0].reproduce,
+        "DSBUILD_SEED=5 cargo test -p dsbuild --lib sim::tests::seed_runner_works -- --exact"
+    );
+
+    let report = SeedRunner::new(0..3).with_threads(8).run(scenario);
+    assert!(report.is_success());
+    assert_eq!(report.to_string(), "All 3 seeds passed");
+
+    // The only seed replaces the range and can be the last one.
+    let report = SeedRunner::new(0..12).with_only_seed(u64::MAX).run(|seed| {
+        assert_eq!(
+            std::thread::current().name(),
+            Some("sim::tests::seed_runner_works")
+        );
+        Err(seed.to_string())
+    });
+    assert_eq!(report.total, 1);
+    assert_eq!(report.failures[0].reason, u64::MAX.to_string());
+}
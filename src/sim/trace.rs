@@ -0,0 +1,210 @@
+//! Definition of events, which are recorded in the [trace][crate::Sim::enable_trace] of the simulation.
+
+use serde::{Deserialize, Serialize};
+
+use crate::common::{message::Message, process::Address};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Represents event of the simulation, which is recorded in the
+/// [trace][crate::Sim::enable_trace].
+///
+/// In JSON representation fields of the event [kind][TraceEventKind] are placed
+/// next to the common fields and the kind is specified with `event` field.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TraceEvent {
+    /// Virtual time of the event.
+    pub time: f64,
+    /// Name of the node, where event happened.
+    pub node: String,
+    /// Name of the process, where event happened, or `None` for events of the node.
+    pub process: Option<String>,
+    /// Kind of the event with its details.
+    #[serde(flatten)]
+    pub kind: TraceEventKind,
+}
+
+/// Represents kinds of [trace events][TraceEvent].
+///
+/// Message data is recorded as string, which is JSON for messages
+/// created with [`Message::new`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TraceEventKind {
+    /// Process sent message over the network.
+    MessageSent {
+        /// Address of the receiver.
+        to: Address,
+        /// Tip of the message.
+        tip: String,
+        /// Data of the message.
+        data: String,
+        /// Specifies if message was sent reliably.
+        reliable: bool,
+    },
+    /// Process received message from the network.
+    MessageReceived {
+        /// Address of the sender.
+        from: Address,
+        /// Tip of the message.
+        tip: String,
+        /// Data of the message.
+        data: String,
+    },
+    /// Message sent by the process was dropped according to the drop rate of the network
+    /// or the link, because the link is blocked, the network is partitioned, one of the nodes
+    /// is disconnected or isolated, or because receiver does not exist.
+    MessageDropped {
+        /// Address of the receiver.
+        to: Address,
+        /// Tip of the message.
+        tip: String,
+        /// Data of the message.
+        data: String,
+    },
+    /// User sent local message to the process.
+    LocalMessageReceived {
+        /// Tip of the message.
+        tip: String,
+        /// Data of the message.
+        data: String,
+    },
+    /// Process sent local message to the user.
+    LocalMessageSent {
+        /// Tip of the message.
+        tip: String,
+        /// Data of the message.
+        data: String,
+    },
+    /// Process set timer.
+    TimerSet {
+        /// Name of the timer.
+        name: String,
+        /// Delay of the timer.
+        delay: f64,
+        /// Specifies if timer is not overridden when it already exists.
+        once: bool,
+    },
+    /// Timer of the process fired.
+    TimerFired {
+        /// Name of the timer.
+        name: String,
+    },
+    /// Process cancelled timer.
+    TimerCancelled {
+        /// Name of the timer.
+        name: String,
+    },
+    /// Process created file.
+    FileCreated {
+        /// Name of the file.
+        file: String,
+    },
+    /// Process opened file.
+    FileOpened {
+        /// Name of the file.
+        file: String,
+    },
+    /// Process deleted file.
+    FileDeleted {
+        /// Name of the file.
+        file: String,
+    },
+    /// Process read data from file.
+    FileRead {
+        /// Name of the file.
+        file: String,
+        /// Offset of the read data.
+        offset: u64,
+        /// Number of read bytes.
+        size: u64,
+    },
+    /// Process appended data to file.
+    FileAppended {
+        /// Name of the file.
+        file: String,
+        /// Number of appended bytes.
+        size: u64,
+    },
+    /// Node crashed.
+    NodeCrashed,
+    /// Node recovered after crash.
+    NodeRecovered,
+    /// Node was shut down.
+    NodeShutdown,
+    /// Node was rerun after shutdown.
+    NodeRerun,
+}
+
+impl TraceEventKind {
+    pub(crate) fn message_sent(to: &Address, msg: &Message, reliable: bool) -> Self {
+        Self::MessageSent {
+            to: to.clone(),
+            tip: msg.tip().clone(),
+            data: message_data(msg),
+            reliable,
+        }
+    }
+
+    pub(crate) fn message_received(from: &Address, msg: &Message) -> Self {
+        Self::MessageReceived {
+            from: from.clone(),
+            tip: msg.tip().clone(),
+            data: message_data(msg),
+        }
+    }
+
+    pub(crate) fn message_dropped(to: &Address, msg: &Message) -> Self {
+        Self::MessageDropped {
+            to: to.clone(),
+            tip: msg.tip().clone(),
+            data: message_data(msg),
+        }
+    }
+
+    pub(crate) fn local_message_received(msg: &Message) -> Self {
+        Self::LocalMessageReceived {
+            tip: msg.tip().clone(),
+            data: message_data(msg),
+        }
+    }
+
+    pub(crate) fn local_message_sent(msg: &Message) -> Self {
+        Self::LocalMessageSent {
+            tip: msg.tip().clone(),
+            data: message_data(msg),
+        }
+    }
+}
+
+fn message_data(msg: &Message) -> String {
+    String::from_utf8_lossy(msg.raw_data()).into_owned()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Stores events of the simulation if trace is enabled.
+#[derive(Default)]
+pub(crate) struct Trace {
+    events: Option<Vec<TraceEvent>>,
+}
+
+impl Trace {
+    pub fn enable(&mut self) {
+        self.events.get_or_insert_with(Vec::new);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.events.is_some()
+    }
+
+    pub fn record(&mut self, event: TraceEvent) {
+        if let Some(events) = self.events.as_mut() {
+            events.push(event);
+        }
+    }
+
+    pub fn events(&self) -> &[TraceEvent] {
+        self.events.as_deref().unwrap_or_default()
+    }
+}